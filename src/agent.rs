@@ -4,8 +4,16 @@
 // has orientation
 // moves are relative to its current orientation
 
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
+  error::Result,
+  dynamic_object::{ObstacleOwner, ObstacleSegment},
+  collision::{self, SlideResult},
   common_structs::{
+    AlignedBox,
     Coord,
     Angle,
     RGBAColor,
@@ -15,9 +23,12 @@ use crate::{
   linear_texture::{
     LinearTexture,
   },
-  shape::Shape,
+  rgba_canvas::RGBACanvas,
+  shape::{AgentDescription, Shape, WorldSetup},
+  wall_grid::WallGrid,
 };
 
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub enum Direction {
   Forward,
   Backward,
@@ -25,24 +36,137 @@ pub enum Direction {
   Right,
 }
 
+// height of walls in world units, for first-person view
+const WALL_HEIGHT: f32 = 1.0;
+const CEILING_COLOR: RGBAColor = RGBAColor{r: 70, g: 80, b: 100, a: 255};
+const FLOOR_COLOR: RGBAColor = RGBAColor{r: 90, g: 75, b: 55, a: 255};
+
+// outline collisions: how many times a move can be deflected within one step,
+// and how finely a blocked step is shortened
+const OUTLINE_MAX_SLIDES: usize = 4;
+const OUTLINE_BISECTIONS: usize = 12;
+const OUTLINE_SKIN: f32 = 0.0001; // gap kept between the outline and the wall it stopped at
+
+// what a view ray has hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HitTarget {
+  Wall(usize), // index of the static wall, see World::shape_of_wall for the shape it belongs to
+  Object(usize), // id of the dynamic object
+  Agent(usize), // id of the other agent
+}
+
+// nearest wall hit by one of the view rays
+#[derive(Clone, Copy)]
+pub struct WallHit {
+  pub distance: f32, // along the ray
+  pub perpendicular_distance: f32, // to the plane of view, used for fisheye-free wall heights
+  pub point: Coord, // where the ray hit, in world coordinates
+  pub normal: Coord, // unit normal of the hit wall, facing the agent
+  pub target: HitTarget,
+  pub texture_position: f32, // distance from the wall base to the hit point
+  pub color: RGBAColor, // texture color at the hit point, not shaded
+}
+
+// which sides of the agent were pushed back by walls or obstacles, relative to its heading
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CollisionFlags {
+  pub front: bool,
+  pub back: bool,
+  pub left: bool,
+  pub right: bool,
+}
+
+impl CollisionFlags {
+  pub fn any(&self) -> bool {
+    return self.front || self.back || self.left || self.right;
+  }
+}
+
+// a single thing the agent can be told to do
+// Move and Rotate push the agent during the current tick only, so they have to be repeated
+// for as long as the agent should keep speeding up (e.g. while a key is held)
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum Action {
+  Move(Direction),
+  Turn(f32), // degrees, positive is to the right, instant
+  Rotate(f32), // angular thrust, from -1.0 (full to the left) to 1.0 (full to the right)
+}
+
+// what the agent bumps into walls and others with
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum CollisionModel {
+  #[default]
+  Circle, // circle of shape.radius around the center, swept, cheap, but keeps the agent further from walls than it looks
+  Outline, // convex hull of the agent's shape, moved in small steps, the agent can go right up to walls
+}
+
+// how the agent speeds up, slows down and how fast it can go
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct MotionParams {
+  pub acceleration: f32, // world units per second^2, at full thrust
+  pub angular_acceleration: f32, // degrees per second^2, at full thrust
+  pub friction: f32, // fraction of linear and angular velocity lost per second
+  pub max_speed: f32, // world units per second
+  pub max_angular_speed: f32, // degrees per second
+}
+
+impl Default for MotionParams {
+  fn default() -> MotionParams {
+    return MotionParams {
+      acceleration: 30.0,
+      angular_acceleration: 1440.0,
+      friction: 8.0,
+      max_speed: 3.0,
+      max_angular_speed: 150.0,
+    };
+  }
+}
+
+// everything but the walls it knows and the obstacles it sees is saved in snapshots,
+// those two are given again by the world the agent is restored into
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Agent {
+  pub id: usize,
   pub center: Coord,
   angle: Angle,
   pub shape: Shape,
-  step_size: f32,
+  pub velocity: Coord, // world units per second
+  pub angular_velocity: f32, // degrees per second
+  pub motion: MotionParams,
+  pub collision_model: CollisionModel,
+  thrust: Coord, // in agent's own frame (x forward, y to the right), collected during current tick
+  angular_thrust: f32,
   f_o_v: Angle, // field of view
   m_v_d: f32, // max view distance
-  visible_walls: Vec<Vector2D>,
+  #[serde(skip, default = "empty_wall_index")]
+  visible_walls: Arc<WallGrid>, // usually shared with the world and the other agents
+  #[serde(skip)]
+  dynamic_obstacles: Vec<ObstacleSegment>,
+  collisions: CollisionFlags, // since the last reset_collisions
   pub is_updated: bool,
 }
 
 impl Agent {
   pub fn new(init_coord: Coord, init_angle: Angle, f_o_v: Angle) -> Agent {
+    return Agent::new_with_id(0, init_coord, init_angle, f_o_v);
+  }
+
+  pub fn new_with_id(id: usize, init_coord: Coord, init_angle: Angle, f_o_v: Angle) -> Agent {
+    // body color tells agents apart in the top view
+    let body_color: RGBAColor = match id % 6 {
+      0 => RGBAColor::new_p(Palette::Green),
+      1 => RGBAColor::new_p(Palette::Cyan),
+      2 => RGBAColor::new_p(Palette::Yellow),
+      3 => RGBAColor::new_p(Palette::Magenta),
+      4 => RGBAColor::new_p(Palette::Orange),
+      _ => RGBAColor::new_p(Palette::Blue),
+    };
+
     let mut shape: Shape = Shape::new_box(
       String::from("Agent's shape"),
       0.4,
       0.3,
-      LinearTexture::new_plain(RGBAColor::new_p(Palette::Green)),
+      LinearTexture::new_plain(body_color),
     ).unwrap();
     let inner_shape: Shape = Shape::new_regular_polygon(
       String::from("Agent's inner shape"),
@@ -56,188 +180,736 @@ impl Agent {
 
 
     return Agent{
+      id,
       center: init_coord,
       angle: init_angle,
       shape,
-      step_size: 0.05,
+      velocity: Coord::new(0.0, 0.0),
+      angular_velocity: 0.0,
+      motion: MotionParams::default(),
+      collision_model: CollisionModel::Circle,
+      thrust: Coord::new(0.0, 0.0),
+      angular_thrust: 0.0,
       f_o_v,
       m_v_d: 10.0,
-      visible_walls: Vec::new(),
+      visible_walls: empty_wall_index(),
+      dynamic_obstacles: Vec::new(),
+      collisions: CollisionFlags::default(),
       is_updated: true,
     };
   }
 
+  pub fn from_setup(setup: &WorldSetup) -> Agent {
+    let mut agent: Agent = Agent::new(
+      setup.initial_coord,
+      Angle::new_deg(setup.initial_angle_deg),
+      Angle::new_deg(setup.agents_field_of_view_deg),
+    );
+
+    agent.collision_model = setup.agents_collision_model;
+
+    return agent;
+  }
+
+  pub fn from_descr(id: usize, descr: &AgentDescription, setup: &WorldSetup) -> Agent {
+    // whatever the description leaves out is the same as for the first agent
+    let mut agent: Agent = Agent::new_with_id(
+      id,
+      descr.initial_coord,
+      Angle::new_deg(descr.initial_angle_deg),
+      Angle::new_deg(descr.field_of_view_deg.unwrap_or(setup.agents_field_of_view_deg)),
+    );
+
+    agent.collision_model = descr.collision_model.unwrap_or(setup.agents_collision_model);
+
+    return agent;
+  }
+
+  pub fn segments(&self) -> Vec<ObstacleSegment> {
+    // outline of the agent in world coordinates, as seen and bumped into by other agents
+    let mut segments: Vec<ObstacleSegment> = Vec::with_capacity(self.shape.elements.len());
+
+    for i in 0..self.shape.elements.len() {
+      segments.push(ObstacleSegment {
+        segment: self.shape.elements[i].new_shifted(self.shape.anchor),
+        owner: ObstacleOwner::Agent(self.id),
+        is_pushable: false,
+      });
+    }
+
+    return segments;
+  }
+
   /* pub fn draw(&self, canvas: &mut RGBACanvas) {
     self.shape.draw(canvas);
   } */
   
   pub fn agent_move(&mut self, direction: Direction) {
-    let directed_step: Coord;
+    // thrust in given direction for the current tick, the agent moves in integrate()
 
     match direction {
-      Direction::Forward => { directed_step = Coord::new(self.step_size, 0.0).new_rotated(self.angle); }
-      Direction::Backward => { directed_step = Coord::new(-self.step_size, 0.0).new_rotated(self.angle); }
-      Direction::Left => { directed_step = Coord::new(0.0, -self.step_size).new_rotated(self.angle); }
-      Direction::Right => { directed_step = Coord::new(0.0, self.step_size).new_rotated(self.angle); }
+      Direction::Forward => { self.thrust = self.thrust.new_offset(Coord::new(1.0, 0.0)); }
+      Direction::Backward => { self.thrust = self.thrust.new_offset(Coord::new(-1.0, 0.0)); }
+      Direction::Left => { self.thrust = self.thrust.new_offset(Coord::new(0.0, -1.0)); }
+      Direction::Right => { self.thrust = self.thrust.new_offset(Coord::new(0.0, 1.0)); }
+    }
+  }
+
+  pub fn rotate(&mut self, angular_thrust: f32) {
+    self.angular_thrust += angular_thrust;
+  }
+
+  pub fn integrate(&mut self, dt: f32) {
+    // one fixed time step of the kinematic model:
+    // thrust collected during the tick accelerates, friction slows down, speeds are capped,
+    // then the agent is moved and pushed out of whatever it ran into
+
+    // several directions at once are combined, but never give more than full thrust
+    let mut thrust: Coord = self.thrust;
+    let thrust_length: f32 = f32::sqrt(thrust.x() * thrust.x() + thrust.y() * thrust.y());
+
+    if thrust_length > 1.0 {
+      thrust = thrust.new_scaled(1.0 / thrust_length);
+    }
+
+    let angular_thrust: f32 = f32::max(-1.0, f32::min(1.0, self.angular_thrust));
+
+    self.thrust = Coord::new(0.0, 0.0);
+    self.angular_thrust = 0.0;
+
+    let drag: f32 = f32::max(0.0, 1.0 - self.motion.friction * dt);
+
+    self.velocity = self.velocity
+      .new_offset(thrust.new_rotated(self.angle).new_scaled(self.motion.acceleration * dt))
+      .new_scaled(drag);
+
+    let speed: f32 = f32::sqrt(self.velocity.x() * self.velocity.x() + self.velocity.y() * self.velocity.y());
+
+    if speed > self.motion.max_speed {
+      self.velocity = self.velocity.new_scaled(self.motion.max_speed / speed);
+    } else if speed < 0.001 {
+      self.velocity = Coord::new(0.0, 0.0);
+    }
+
+    self.angular_velocity = (self.angular_velocity + angular_thrust * self.motion.angular_acceleration * dt) * drag;
+    self.angular_velocity = f32::max(-self.motion.max_angular_speed, f32::min(self.motion.max_angular_speed, self.angular_velocity));
+
+    if f32::abs(self.angular_velocity) < 0.01 {
+      self.angular_velocity = 0.0;
+    }
+
+    if self.angular_velocity != 0.0 {
+      self.turn_sideways(self.angular_velocity * dt);
+      self.is_updated = true;
+    }
+
+    if self.velocity.x() != 0.0 || self.velocity.y() != 0.0 {
+      self.move_by(self.velocity.new_scaled(dt));
+
+      self.is_updated = true;
     }
+  }
 
-    // let directed_step: Coord = Coord::new(self.step_size, 0.0).new_rotated(self.angle);
+  pub fn bump(&mut self, correction: Coord) {
+    // moved back by something it ran into (e.g. a crate stuck at a wall)
+    self.shift(correction);
+    self.stop_against(correction);
+  }
 
-    self.center = self.center.new_offset(directed_step);
+  fn stop_against(&mut self, push: Coord) {
+    // drop the part of velocity that goes against the push
+    let push_length: f32 = f32::sqrt(push.x() * push.x() + push.y() * push.y());
 
-    self.shape.shift(directed_step);
+    if push_length > 0.0 {
+      let normal: Coord = push.new_scaled(1.0 / push_length);
+      let into_obstacle: f32 = self.velocity.x() * normal.x() + self.velocity.y() * normal.y();
 
-    self.collide();
+      if into_obstacle < 0.0 {
+        self.velocity = self.velocity.new_offset(normal.new_scaled(-into_obstacle));
+      }
+    }
   }
 
   pub fn turn_sideways(&mut self, degrees: f32) {
+    // the circle is the same whichever way the agent looks, the outline is not:
+    // a turn that would put a corner into a wall is not done
+    // (unless the outline was overlapping already, e.g. pushed by a patrol)
+    let is_checked: bool = self.collision_model == CollisionModel::Outline && !self.is_outline_overlapping();
+    let previous: (Angle, Shape) = (self.angle, self.shape.clone());
+
     self.angle.turn_deg(degrees);
     self.shape.rotate(Angle::new_deg(degrees));
+
+    if is_checked && self.is_outline_overlapping() {
+      // restored rather than turned back, which would leave rounding errors
+      (self.angle, self.shape) = previous;
+      self.angular_velocity = 0.0;
+    }
+  }
+
+  pub fn apply_action(&mut self, action: Action) {
+    match action {
+      Action::Move(direction) => self.agent_move(direction),
+      Action::Turn(degrees) => self.turn_sideways(degrees),
+      Action::Rotate(angular_thrust) => self.rotate(angular_thrust),
+    }
+
+    self.is_updated = true;
+  }
+
+  pub fn angle(&self) -> Angle {
+    return self.angle;
+  }
+
+  pub fn field_of_view(&self) -> Angle {
+    return self.f_o_v;
+  }
+
+  pub fn max_view_distance(&self) -> f32 {
+    return self.m_v_d;
+  }
+
+  pub fn collisions(&self) -> CollisionFlags {
+    return self.collisions;
+  }
+
+  pub fn reset_collisions(&mut self) {
+    self.collisions = CollisionFlags::default();
   }
 
   pub fn update_visible_walls(&mut self, walls: Vec<Vector2D>) {
-    self.visible_walls = walls;
+    self.visible_walls = Arc::new(WallGrid::from_walls(walls));
+  }
+
+  pub fn update_dynamic_obstacles(&mut self, obstacles: Vec<ObstacleSegment>) {
+    self.dynamic_obstacles = obstacles;
+  }
+
+  pub fn shift(&mut self, shift: Coord) {
+    self.center = self.center.new_offset(shift);
+    self.shape.shift(shift);
+  }
+
+  pub fn update_wall_index(&mut self, wall_index: Arc<WallGrid>) {
+    // same as update_visible_walls, but shares an already built index (e.g. World::wall_index)
+    self.visible_walls = wall_index;
   }
 
   pub fn get_view(&self, size: i32) -> Vec<RGBAColor> {
-    let angle_between_rays: Angle = Angle::new_deg(self.f_o_v.get_deg() / size as f32);
-    let mut view_line: Vec<RGBAColor> = Vec::with_capacity(size.abs() as usize);
+    // one color per ray, darker with distance
+    let view_rays: Vec<Option<WallHit>> = self.cast_view_rays(size);
+    let mut view_line: Vec<RGBAColor> = Vec::with_capacity(view_rays.len());
+
+    for i in 0..view_rays.len() {
+      view_line.push(self.view_color(&view_rays[i]));
+    }
+    
+    return view_line;
+  }
+
+  pub fn view_color(&self, view_ray: &Option<WallHit>) -> RGBAColor {
+    // color of a single ray as the agent sees it
+    match view_ray {
+      Some(hit) => {
+        return hit.color.new_scaled(get_scaling_factor(hit.distance, self.m_v_d));
+      }
+      None => {
+        return RGBAColor::new_p(Palette::Black);
+      }
+    }
+  }
+
+  pub fn get_depth_view(&self, size: i32) -> Vec<f32> {
+    // one distance per ray, max view distance where nothing is seen
+    let view_rays: Vec<Option<WallHit>> = self.cast_view_rays(size);
+    let mut depths: Vec<f32> = Vec::with_capacity(view_rays.len());
+
+    for i in 0..view_rays.len() {
+      match view_rays[i] {
+        Some(hit) => depths.push(hit.distance),
+        None => depths.push(self.m_v_d),
+      }
+    }
+
+    return depths;
+  }
 
-    let mut ray1: Vector2D;
+  pub fn cast_view_rays(&self, size: i32) -> Vec<Option<WallHit>> {
+    return self.cast_view_rays_from(self.shape.anchor, size);
+  }
+
+  pub fn cast_view_rays_from(&self, eye: Coord, size: i32) -> Vec<Option<WallHit>> {
+    // rays are spread evenly over a flat projection plane in front of the agent
+    // (not evenly by angle), so that straight walls stay straight in the rendered view;
+    // the eye is normally the anchor of the agent, see eye_position for the eyes of stereo views
 
-    let mut intersections_list_v: Vec<Vector2D>;
-    let mut shortest_distance: f32;
-    let mut current_distance: f32;
-    let mut current_dot_index: usize = 0;
-    let mut column_color: RGBAColor;
+    let mut view_rays: Vec<Option<WallHit>> = Vec::with_capacity(size.abs() as usize);
 
-    ray1 = Vector2D::new(
-      self.shape.anchor,
+    let mut central_ray: Vector2D = Vector2D::new(
+      eye,
       Coord::new(self.m_v_d, 0.0),
       LinearTexture::new_plain(RGBAColor::new()),
     );
-    ray1.rotate(self.angle);
-    ray1.rotate(Angle::new_rad(-self.f_o_v.get_rad()/2.0));
+    central_ray.rotate(self.angle);
 
     for view_column in 0..size {
+      let ray_angle: Angle = self.view_ray_angle(view_column, size);
+      let ray: Vector2D = central_ray.new_rotated(ray_angle);
 
-      intersections_list_v = Vec::new();
+      let mut nearest: Option<(&Vector2D, f32, f32, HitTarget)> = None;
+
+      if let Some((wall_index, t, u)) = self.visible_walls.cast_ray_params(&ray) {
+        nearest = Some((self.visible_walls.wall(wall_index), t, u, HitTarget::Wall(wall_index)));
+      }
 
-      
-      for i in 0..self.visible_walls.len() {
-        match ray1.new_rotated(Angle::new_rad(angle_between_rays.get_rad() * view_column as f32)).intersect(&self.visible_walls[i]) {
-          Some(int_vec) => {
-            intersections_list_v.push(int_vec);
+      for obstacle in self.dynamic_obstacles.iter() {
+        if let Some((t, u)) = ray.intersection_params(&obstacle.segment) {
+          if nearest.is_none() || t < nearest.unwrap().1 {
+            let target: HitTarget = match obstacle.owner {
+              ObstacleOwner::Object(id) => HitTarget::Object(id),
+              ObstacleOwner::Agent(id) => HitTarget::Agent(id),
+            };
+
+            nearest = Some((&obstacle.segment, t, u, target));
           }
-          None => {}
         }
       }
 
-      shortest_distance = self.m_v_d * 2.0;
-      column_color = RGBAColor::new_p(Palette::Black);
-      if intersections_list_v.len() != 0 {
-
-        for i in 0..intersections_list_v.len() {
+      match nearest {
+        Some((wall, t, u, target)) => {
+          let distance: f32 = t * ray.length();
 
-          current_distance = intersections_list_v[i].length();
+          // normal of the wall turned to face the ray's origin
+          let mut normal: Coord = Coord::new(-wall.tip.y(), wall.tip.x()).new_scaled(1.0 / wall.length());
 
-          if current_distance < shortest_distance {
-            shortest_distance = current_distance;
-            current_dot_index = i;
+          if normal.x() * ray.tip.x() + normal.y() * ray.tip.y() > 0.0 {
+            normal = normal.new_scaled(-1.0);
           }
+
+          view_rays.push(Some(WallHit {
+            distance,
+            perpendicular_distance: distance * f32::cos(ray_angle.get_rad()),
+            point: ray.base.new_offset(ray.tip.new_scaled(t)),
+            normal,
+            target,
+            texture_position: u * wall.length(),
+            color: wall.texture.get_color(wall.length(), u * wall.length()),
+          }));
+        }
+        None => {
+          view_rays.push(None);
         }
+      }
+    }
+
+    return view_rays;
+  }
+
+  pub fn view_ray_angle(&self, view_column: i32, size: i32) -> Angle {
+    // direction of one of the view rays cast by cast_view_rays, relative to the heading
+    let half_plane_width: f32 = f32::tan(self.f_o_v.get_rad() / 2.0);
+    let plane_position: f32 = ((view_column as f32 + 0.5) / size as f32 * 2.0 - 1.0) * half_plane_width;
+
+    return Angle::new_rad(f32::atan(plane_position));
+  }
+
+  pub fn eye_position(&self, sideways: f32) -> Coord {
+    // point beside the anchor, to the right of the heading for positive distances
+    return self.shape.anchor.new_offset(Coord::new(0.0, sideways).new_rotated(self.angle));
+  }
+
+  pub fn render_first_person_view(&self, canvas_width: i32, canvas_height: i32) -> Result<RGBACanvas> {
+    return self.render_first_person_view_from(self.shape.anchor, canvas_width, canvas_height);
+  }
 
-        column_color = 
-          intersections_list_v[current_dot_index]
-            .texture
-            .get_color(
-              0.0,
-              0.0,
-            )
-            .new_scaled(
-              get_scaling_factor(
-                intersections_list_v[current_dot_index].length(),
-                self.m_v_d,
-              )
-            );
+  pub fn render_first_person_view_from(&self, eye: Coord, canvas_width: i32, canvas_height: i32) -> Result<RGBACanvas> {
+    // pseudo-3d view: every column of the canvas is one ray,
+    // wall slice height is inversely proportional to perpendicular distance (no fisheye),
+    // wall color comes from the wall texture at the hit position, so texture patterns run across the wall,
+    // ceiling above and floor below are shaded by the distance of the floor point they show
+
+    let mut rendered_view: RGBACanvas = RGBACanvas::new_black(canvas_width, canvas_height)?;
+    let view_rays: Vec<Option<WallHit>> = self.cast_view_rays_from(eye, canvas_width);
+
+    // distance from the eye to projection plane, in pixels
+    let projection_distance: f32 = (canvas_width as f32 / 2.0) / f32::tan(self.f_o_v.get_rad() / 2.0);
+    let horizon: f32 = canvas_height as f32 / 2.0;
+
+    let mut background: Vec<RGBAColor> = Vec::with_capacity(canvas_height as usize);
+
+    for j in 0..canvas_height {
+      let rows_from_horizon: f32 = f32::abs(j as f32 + 0.5 - horizon);
+      let floor_distance: f32 = (WALL_HEIGHT / 2.0) * projection_distance / rows_from_horizon;
+      let shading: f32 = get_scaling_factor(f32::min(floor_distance, self.m_v_d), self.m_v_d);
+
+      if (j as f32) < horizon {
+        background.push(CEILING_COLOR.new_scaled(shading));
+      } else {
+        background.push(FLOOR_COLOR.new_scaled(shading));
+      }
+    }
+
+    for i in 0..canvas_width {
+      let mut wall_top: i32 = canvas_height;
+      let mut wall_bottom: i32 = canvas_height;
+      let mut wall_color: RGBAColor = RGBAColor::new_p(Palette::Black);
+
+      if let Some(hit) = view_rays[i as usize] {
+        let wall_height: f32 = WALL_HEIGHT * projection_distance / f32::max(hit.perpendicular_distance, 0.001);
+
+        wall_top = f32::max(horizon - wall_height / 2.0, 0.0) as i32;
+        wall_bottom = f32::min(horizon + wall_height / 2.0, canvas_height as f32) as i32;
+        wall_color = hit.color.new_scaled(get_scaling_factor(hit.distance, self.m_v_d));
       }
 
-      view_line.push(column_color);
+      for j in 0..canvas_height {
+        if j >= wall_top && j < wall_bottom {
+          rendered_view.put_pixel_simple(i, j, wall_color);
+        } else {
+          rendered_view.put_pixel_simple(i, j, background[j as usize]);
+        }
+      }
     }
-    
-    return view_line;
-  }
 
-  fn collide(&mut self) {
-    // simplest -- the agent collider is a circle
-    // check if distance to any Vector2D is less than a radius
-    // if true
-    //   move agent by difference
-    // else
-    // check distances to wall ends to eliminate weirdness around corners
+    return Ok(rendered_view);
+  }
 
-    let mut is_collided_to_wall: bool = false;
-    // let mut collisions_count: i32 = 0;
+  pub fn render_depth_view(&self, canvas_width: i32, canvas_height: i32) -> Result<RGBACanvas> {
+    // depth map with the same geometry as the first-person view:
+    // walls are grey, the closer the brighter, nothing else is drawn
 
-    for i in 0..self.visible_walls.len() {
-      match self.visible_walls[i].new_orthogonal_from_point(self.center) {
-        Some(vec_to_wall) => {
-          if vec_to_wall.length() < self.shape.radius {
-            // collisions_count += 1;
+    let mut rendered_view: RGBACanvas = RGBACanvas::new_black(canvas_width, canvas_height)?;
+    let view_rays: Vec<Option<WallHit>> = self.cast_view_rays(canvas_width);
 
-            let dt: f32 = 1.0 - vec_to_wall.length() / self.shape.radius;
+    let projection_distance: f32 = (canvas_width as f32 / 2.0) / f32::tan(self.f_o_v.get_rad() / 2.0);
+    let horizon: f32 = canvas_height as f32 / 2.0;
 
-            self.center = self.center.new_offset(Coord::new(
-              -dt * vec_to_wall.tip.x(),
-              -dt * vec_to_wall.tip.y(),
-            ));
+    for i in 0..canvas_width {
+      if let Some(hit) = view_rays[i as usize] {
+        let wall_height: f32 = WALL_HEIGHT * projection_distance / f32::max(hit.perpendicular_distance, 0.001);
+        let wall_top: i32 = f32::max(horizon - wall_height / 2.0, 0.0) as i32;
+        let wall_bottom: i32 = f32::min(horizon + wall_height / 2.0, canvas_height as f32) as i32;
 
-            self.shape.shift(Coord::new(
-              -dt * vec_to_wall.tip.x(),
-              -dt * vec_to_wall.tip.y(),
-            ));
+        let closeness: f32 = f32::max(1.0 - hit.distance / self.m_v_d, 0.0);
+        let grey: u8 = (closeness * 255.0) as u8;
+        let depth_color: RGBAColor = RGBAColor::new_rgb(grey, grey, grey);
 
-            is_collided_to_wall = true;
-          }
+        for j in wall_top..wall_bottom {
+          rendered_view.put_pixel_simple(i, j, depth_color);
         }
-        None => {}
       }
     }
 
-    if !is_collided_to_wall {
-      // check if in the vicinity of corner
-      for i in 0..self.visible_walls.len() {
-        let vec_to_corner: Vector2D = Vector2D::from_coord(
-          Coord::new(
-            self.visible_walls[i].base.x() - self.center.x(),
-            self.visible_walls[i].base.y() - self.center.y(),
-          ),
-          LinearTexture::new_plain(RGBAColor::new()),
-        );
-
-        if vec_to_corner.length() < self.shape.radius {
-          // collisions_count += 1;
-          let dt: f32 = 1.0 - vec_to_corner.length() / self.shape.radius;
-
-            self.center = self.center.new_offset(Coord::new(
-              -dt * vec_to_corner.tip.x(),
-              -dt * vec_to_corner.tip.y(),
-            ));
-
-            self.shape.shift(Coord::new(
-              -dt * vec_to_corner.tip.x(),
-              -dt * vec_to_corner.tip.y(),
-            ));
+    return Ok(rendered_view);
+  }
+
+  fn move_by(&mut self, displacement: Coord) {
+    // swept move: the agent stops at the first wall on its way and slides along it,
+    // so even a fast agent can't pass through a thin wall
+
+    if self.collision_model == CollisionModel::Outline && !self.is_outline_overlapping() {
+      self.move_outline(displacement);
+
+      let push: Coord = self.collide();
+      self.stop_against(push);
+
+      return;
+    }
+
+    // an outline that already overlaps something is moved as a circle,
+    // which pushes it out completely, as the circle encloses the outline
+
+    let start: Coord = self.center;
+    let radius: f32 = self.shape.radius;
+
+    // sliding can turn the move sideways, but never makes it longer
+    let reach: f32 = f32::sqrt(displacement.x() * displacement.x() + displacement.y() * displacement.y());
+    let swept_area: AlignedBox = AlignedBox::around_point(start, reach + radius);
+
+    let nearby: Vec<Vector2D> = self.nearby_obstacles(&swept_area);
+    let slide: SlideResult = collision::slide_circle(start, radius, displacement, &nearby);
+
+    self.shift(Coord::new(slide.center.x() - start.x(), slide.center.y() - start.y()));
+
+    for i in 0..slide.normals.len() {
+      self.record_collision(slide.normals[i]);
+      self.stop_against(slide.normals[i]);
+    }
+
+    // moving obstacles (other agents, patrols) could have come into the agent meanwhile
+    let push: Coord = self.collide();
+    self.stop_against(push);
+  }
+
+  fn collide(&mut self) -> Coord {
+    // the agent collider is a circle, it is pushed out of every wall it overlaps,
+    // both ends of walls included
+    // moving obstacles push first, and that push is swept against static walls,
+    // so an agent caught between a patrol and a wall is never squeezed through the wall
+    // returns how far the agent was pushed back in total
+
+    if self.collision_model == CollisionModel::Outline && !self.is_outline_overlapping() {
+      // the outline is free, even if the circle around it is not
+      return Coord::new(0.0, 0.0);
+    }
+
+    let start: Coord = self.center;
+    let radius: f32 = self.shape.radius;
+    let area: AlignedBox = AlignedBox::around_point(start, radius);
+
+    let dynamic_push: Coord = collision::resolve_overlaps(start, radius, &self.nearby_dynamic_obstacles(&area));
+
+    if dynamic_push.x() != 0.0 || dynamic_push.y() != 0.0 {
+      let reach: f32 = f32::sqrt(dynamic_push.x() * dynamic_push.x() + dynamic_push.y() * dynamic_push.y());
+      let swept_area: AlignedBox = AlignedBox::around_point(start, reach + radius);
+      let slide: SlideResult = collision::slide_circle(start, radius, dynamic_push, &self.nearby_walls(&swept_area));
+
+      self.shift(Coord::new(slide.center.x() - start.x(), slide.center.y() - start.y()));
+    }
+
+    let wall_push: Coord = collision::resolve_overlaps(self.center, radius, &self.nearby_walls(&area));
+    self.shift(wall_push);
+
+    let push: Coord = Coord::new(self.center.x() - start.x(), self.center.y() - start.y());
+
+    if push.x() != 0.0 || push.y() != 0.0 {
+      self.record_collision(push);
+    }
+
+    return push;
+  }
+
+  fn move_outline(&mut self, displacement: Coord) {
+    // the outline is moved in steps shorter than the agent itself, so that no wall fits between two steps;
+    // a blocked step is shortened by bisection until the outline is free, the rest of the move
+    // slides along the wall that blocked it
+
+    let start: Coord = self.center;
+    let reach: f32 = f32::sqrt(displacement.x() * displacement.x() + displacement.y() * displacement.y());
+    let swept_area: AlignedBox = AlignedBox::around_point(start, reach + self.shape.radius);
+    let nearby: Vec<Vector2D> = self.nearby_obstacles(&swept_area);
+
+    let hull: Vec<Coord> = self.shape.convex_hull();
+    let max_step: f32 = f32::max(self.shape.radius / 2.0, 0.001);
+
+    let mut moved: Coord = Coord::new(0.0, 0.0);
+    let mut remaining: Coord = displacement;
+    let mut slides: usize = 0;
+
+    while slides <= OUTLINE_MAX_SLIDES {
+      let remaining_length: f32 = f32::sqrt(remaining.x() * remaining.x() + remaining.y() * remaining.y());
+
+      if remaining_length < 0.00001 {
+        break;
+      }
+
+      let step: Coord = if remaining_length > max_step {
+        remaining.new_scaled(max_step / remaining_length)
+      } else {
+        remaining
+      };
+
+      let blocking: usize = match collision::polygon_hit(&shifted_coords(&hull, moved.new_offset(step)), &nearby) {
+        Some(blocking) => blocking,
+        None => {
+          moved = moved.new_offset(step);
+          remaining = Coord::new(remaining.x() - step.x(), remaining.y() - step.y());
+          continue;
+        }
+      };
+
+      // the largest free part of the step
+      let mut free: f32 = 0.0;
+      let mut blocked: f32 = 1.0;
+
+      for _ in 0..OUTLINE_BISECTIONS {
+        let middle: f32 = (free + blocked) / 2.0;
+
+        if collision::polygon_hit(&shifted_coords(&hull, moved.new_offset(step.new_scaled(middle))), &nearby).is_some() {
+          blocked = middle;
+        } else {
+          free = middle;
         }
       }
+
+      // and a little gap, so that rounding errors of later moves don't bring the outline into the wall
+      let step_length: f32 = f32::sqrt(step.x() * step.x() + step.y() * step.y());
+      free = f32::max(0.0, free - OUTLINE_SKIN / step_length);
+
+      moved = moved.new_offset(step.new_scaled(free));
+
+      let normal: Coord = collision::normal_towards(&nearby[blocking], start.new_offset(moved));
+      let left_over: Coord = Coord::new(remaining.x() - step.x() * free, remaining.y() - step.y() * free);
+      let into_wall: f32 = left_over.x() * normal.x() + left_over.y() * normal.y();
+
+      remaining = if into_wall < 0.0 {
+        left_over.new_offset(normal.new_scaled(-into_wall))
+      } else {
+        left_over
+      };
+
+      self.record_collision(normal);
+      self.stop_against(normal);
+
+      slides += 1;
+    }
+
+    self.shift(moved);
+  }
+
+  fn is_outline_overlapping(&self) -> bool {
+    let area: AlignedBox = AlignedBox::around_point(self.center, self.shape.radius);
+
+    return collision::polygon_hit(&self.shape.convex_hull(), &self.nearby_obstacles(&area)).is_some();
+  }
+
+  fn nearby_obstacles(&self, area: &AlignedBox) -> Vec<Vector2D> {
+    // only walls near the agent can touch it, plus sides of solid dynamic objects
+    // (pushable ones are handled by World::push_objects)
+    let mut nearby: Vec<Vector2D> = self.nearby_walls(area);
+    nearby.append(&mut self.nearby_dynamic_obstacles(area));
+
+    return nearby;
+  }
+
+  fn nearby_walls(&self, area: &AlignedBox) -> Vec<Vector2D> {
+    let mut nearby: Vec<Vector2D> = Vec::new();
+
+    for &i in self.visible_walls.query_box(area).iter() {
+      nearby.push(*self.visible_walls.wall(i));
     }
 
-    // println!("Collided to {} walls", collisions_count);
+    return nearby;
+  }
+
+  fn nearby_dynamic_obstacles(&self, area: &AlignedBox) -> Vec<Vector2D> {
+    let mut nearby: Vec<Vector2D> = Vec::new();
+
+    for obstacle in self.dynamic_obstacles.iter() {
+      if !obstacle.is_pushable && obstacle.segment.bounding_box().box_intersection(area).is_some() {
+        nearby.push(obstacle.segment);
+      }
+    }
+
+    return nearby;
+  }
+
+  fn record_collision(&mut self, push: Coord) {
+    // push direction in agent's own frame: x is forward, y is to the right
+    // being pushed backward means something is in front
+    let local_push: Coord = push.new_rotated(Angle::new_rad(-self.angle.get_rad()));
+    let is_along: bool = f32::abs(local_push.x()) >= f32::abs(local_push.y());
+
+    if is_along && local_push.x() < 0.0 {
+      self.collisions.front = true;
+    } else if is_along {
+      self.collisions.back = true;
+    } else if local_push.y() < 0.0 {
+      self.collisions.right = true;
+    } else {
+      self.collisions.left = true;
+    }
   }
 }
 
+fn shifted_coords(coords: &[Coord], shift: Coord) -> Vec<Coord> {
+  let mut shifted: Vec<Coord> = Vec::with_capacity(coords.len());
+
+  for i in 0..coords.len() {
+    shifted.push(coords[i].new_offset(shift));
+  }
+
+  return shifted;
+}
+
 fn get_scaling_factor(dist: f32, max: f32) -> f32 {
   let scaled_distance: f32 = dist / max;
 
   return (1.0 - scaled_distance) * (1.0 - scaled_distance);
-}
\ No newline at end of file
+}
+
+fn empty_wall_index() -> Arc<WallGrid> {
+  return Arc::new(WallGrid::new());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DT: f32 = 1.0 / 60.0;
+
+  fn wall(from: (f32, f32), to: (f32, f32)) -> Vector2D {
+    return Vector2D::new(
+      Coord::new(from.0, from.1),
+      Coord::new(to.0 - from.0, to.1 - from.1),
+      LinearTexture::new_plain(RGBAColor::new()),
+    );
+  }
+
+  fn fast_agent(center: Coord, velocity: Coord, walls: &Vec<Vector2D>) -> Agent {
+    // fast enough to be well past any of the walls in one tick without collisions
+    let mut agent: Agent = Agent::new(center, Angle::new_deg(0.0), Angle::new_deg(90.0));
+
+    agent.motion.max_speed = 1000.0;
+    agent.motion.friction = 0.0;
+    agent.velocity = velocity;
+    agent.update_visible_walls(walls.clone());
+
+    return agent;
+  }
+
+  fn overlap(agent: &Agent, walls: &Vec<Vector2D>) -> f32 {
+    // how far the agent would have to be pushed to be out of all the walls
+    let push: Coord = collision::resolve_overlaps(agent.center, agent.shape.radius, walls);
+
+    return f32::sqrt(push.x() * push.x() + push.y() * push.y());
+  }
+
+  #[test]
+  fn fast_agent_does_not_tunnel_through_wall_ends() {
+    // a wall across the way that ends on the agent's path, and a wall that points at the agent
+    let cases: Vec<Vector2D> = vec![
+      wall((5.0, 0.0), (5.0, 10.0)),
+      wall((5.0, 0.0), (5.0, -10.0)),
+      wall((5.0, 0.0), (15.0, 0.0)),
+    ];
+
+    for i in 0..cases.len() {
+      let walls: Vec<Vector2D> = vec![cases[i]];
+      let mut agent: Agent = fast_agent(Coord::new(0.0, 0.0), Coord::new(600.0, 0.0), &walls);
+
+      for tick in 0..10 {
+        agent.integrate(DT);
+
+        assert!(agent.center.x() < 5.0, "wall {}, tick {}: agent at {}", i, tick, agent.center.x());
+        assert!(overlap(&agent, &walls) < 0.001, "wall {}, tick {}: agent in the wall", i, tick);
+      }
+
+      assert!(agent.collisions().any(), "wall {}: no collision", i);
+    }
+  }
+
+  #[test]
+  fn agent_in_a_corner_is_out_of_both_walls() {
+    let walls: Vec<Vector2D> = vec![wall((5.0, -10.0), (5.0, 5.0)), wall((-10.0, 5.0), (5.0, 5.0))];
+
+    // driven fast into the corner
+    let mut agent: Agent = fast_agent(Coord::new(0.0, 0.0), Coord::new(400.0, 300.0), &walls);
+
+    for tick in 0..10 {
+      agent.integrate(DT);
+
+      assert!(agent.center.x() < 5.0 && agent.center.y() < 5.0, "tick {}: agent out of the room", tick);
+      assert!(overlap(&agent, &walls) < 0.001, "tick {}: agent in a wall", tick);
+    }
+
+    // already overlapping both walls (e.g. shoved there by something), and still moving into the corner
+    let radius: f32 = agent.shape.radius;
+    let mut agent: Agent = fast_agent(Coord::new(5.0 - radius / 2.0, 5.0 - radius / 2.0), Coord::new(1.0, 1.0), &walls);
+
+    agent.integrate(DT);
+
+    assert!(agent.center.x() < 5.0 && agent.center.y() < 5.0);
+    assert!(overlap(&agent, &walls) < 0.001);
+  }
+}
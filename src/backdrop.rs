@@ -0,0 +1,239 @@
+// cached drawing of the static shapes for the top view, so that only objects and agents
+// are drawn every frame
+//
+// the static layer is drawn a bit larger than the view and reused while the view stays within it
+// and the scale, turn, render mode and static shapes of the world stay the same; views are aligned
+// to whole pixels, so moving the view is only copying another part of the layer
+//
+// while the view keeps turning (e.g. with the agent) a new layer would be needed every frame,
+// which costs several plain renders, so then views are rendered without it
+
+use crate::{
+    common_structs::{Angle, Coord},
+    error::Result,
+    rgba_canvas::RGBACanvas,
+    view::{TopViewCamera, TopViewRenderMode},
+    world::World,
+};
+
+// what the layer was drawn for
+#[derive(Clone, Copy, PartialEq)]
+struct BackdropKey {
+    scale: f32,
+    angle_rad: f32,
+    render_mode: TopViewRenderMode,
+    static_version: u64,
+}
+
+pub struct TopViewBackdrop {
+    layer: Option<RGBACanvas>,
+    key: Option<BackdropKey>,
+    // top left corner of the layer, in pixels of the turned and scaled world around the world origin
+    left: i32,
+    top: i32,
+    last_angle_rad: Option<f32>, // turn of the previous view
+    redraw_count: u64,
+}
+
+impl TopViewBackdrop {
+    pub fn new() -> TopViewBackdrop {
+        return TopViewBackdrop {
+            layer: None,
+            key: None,
+            left: 0,
+            top: 0,
+            last_angle_rad: None,
+            redraw_count: 0,
+        };
+    }
+
+    pub fn invalidate(&mut self) {
+        // the next view draws the layer again
+        self.layer = None;
+        self.key = None;
+    }
+
+    pub fn redraw_count(&self) -> u64 {
+        // how many times the layer was drawn, e.g. to see how well it is reused
+        return self.redraw_count;
+    }
+
+    pub fn render(&mut self, world: &World, camera: &TopViewCamera, canvas_width: i32, canvas_height: i32) -> Result<RGBACanvas> {
+        // the same picture as World::render_top_view_from with the pixel aligned camera
+        let camera: TopViewCamera = camera.pixel_aligned();
+
+        if let TopViewRenderMode::OccludedWireframe(_) = camera.render_mode {
+            // walls are hidden depending on where the agent is, nothing to keep
+            return world.render_top_view_from(&camera, canvas_width, canvas_height);
+        }
+
+        let key: BackdropKey = BackdropKey {
+            scale: camera.scale,
+            angle_rad: camera.angle.get_rad(),
+            render_mode: camera.render_mode,
+            static_version: world.static_version(),
+        };
+
+        let corner: Coord = camera.center.new_rotated(camera.angle).new_scaled(camera.scale);
+        let left: i32 = corner.x().round() as i32 - canvas_width / 2;
+        let top: i32 = corner.y().round() as i32 - canvas_height / 2;
+
+        let is_covered: bool = match &self.layer {
+            Some(layer) => {
+                self.key == Some(key)
+                    && left >= self.left
+                    && top >= self.top
+                    && left + canvas_width <= self.left + layer.width
+                    && top + canvas_height <= self.top + layer.height
+            }
+            None => false,
+        };
+
+        let is_turning: bool = matches!(self.last_angle_rad, Some(angle_rad) if angle_rad != key.angle_rad);
+        self.last_angle_rad = Some(key.angle_rad);
+
+        if !is_covered && is_turning {
+            return world.render_top_view_from(&camera, canvas_width, canvas_height);
+        }
+
+        if !is_covered {
+            // half a view around the view on every side
+            self.redraw(world, key, left - canvas_width / 2, top - canvas_height / 2, 2 * canvas_width, 2 * canvas_height)?;
+        }
+
+        // the layer covers the whole view by now, so every pixel of the blank canvas is copied over
+        let mut rendered_view: RGBACanvas = RGBACanvas::new(canvas_width, canvas_height)?;
+
+        if let Some(layer) = &self.layer {
+            rendered_view.put_canvas(self.left - left, self.top - top, layer);
+        }
+
+        world.draw_dynamic_layer(&mut rendered_view, &camera);
+
+        return Ok(rendered_view);
+    }
+
+    fn redraw(&mut self, world: &World, key: BackdropKey, left: i32, top: i32, width: i32, height: i32) -> Result<()> {
+        // the layer is seen by a camera of the same scale and turn, centered on the middle of the layer
+        let middle: Coord = Coord::new((left + width / 2) as f32, (top + height / 2) as f32)
+            .new_scaled(1.0 / key.scale)
+            .new_rotated(Angle::new_rad(-key.angle_rad));
+
+        let mut layer_camera: TopViewCamera = TopViewCamera::new(middle, key.scale);
+        layer_camera.angle = Angle::new_rad(key.angle_rad);
+        layer_camera.render_mode = key.render_mode;
+
+        let mut layer: RGBACanvas = RGBACanvas::new_black(width, height)?;
+        world.draw_static_layer(&mut layer, &layer_camera);
+
+        self.layer = Some(layer);
+        self.key = Some(key);
+        self.left = left;
+        self.top = top;
+        self.redraw_count += 1;
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        common_structs::{Coord, RGBAColor},
+        linear_texture::LinearTexture,
+        shape::{Shape, WorldSetup},
+    };
+
+    const WIDTH: i32 = 160;
+    const HEIGHT: i32 = 120;
+
+    fn test_world() -> World {
+        let setup: WorldSetup = WorldSetup::from_file(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/world-shapes.ron")).unwrap();
+
+        return World::from_setup(&setup).unwrap();
+    }
+
+    fn assert_same_as_plain_render(backdrop: &mut TopViewBackdrop, world: &World, camera: &TopViewCamera) {
+        let view: RGBACanvas = backdrop.render(world, camera, WIDTH, HEIGHT).unwrap();
+        let plain: RGBACanvas = world.render_top_view_from(&camera.pixel_aligned(), WIDTH, HEIGHT).unwrap();
+
+        assert!(view.data == plain.data, "view centered on {}, {} differs from the plain render", camera.center.x(), camera.center.y());
+    }
+
+    #[test]
+    fn panning_within_the_layer_reuses_it() {
+        let world: World = test_world();
+        let mut backdrop: TopViewBackdrop = TopViewBackdrop::new();
+        let mut camera: TopViewCamera = TopViewCamera::new(Coord::new(-18.0, 0.0), 10.0);
+
+        // a quarter of the view either way stays within the layer, also off whole pixels
+        for k in 0..20 {
+            camera.center = Coord::new(-18.0 + (k % 5) as f32 * 0.83, (k / 5) as f32 * -0.61);
+            assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        }
+
+        assert_eq!(backdrop.redraw_count(), 1);
+
+        // a whole view away
+        camera.center = Coord::new(-18.0 + WIDTH as f32 / 10.0, 0.0);
+        assert_same_as_plain_render(&mut backdrop, &world, &camera);
+
+        assert_eq!(backdrop.redraw_count(), 2);
+    }
+
+    #[test]
+    fn changes_of_scale_mode_and_shapes_draw_the_layer_again() {
+        let mut world: World = test_world();
+        let mut backdrop: TopViewBackdrop = TopViewBackdrop::new();
+        let mut camera: TopViewCamera = TopViewCamera::new(Coord::new(-10.0, 2.0), 8.0);
+
+        assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        assert_eq!(backdrop.redraw_count(), 1);
+
+        camera.scale = 12.0;
+        assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        assert_eq!(backdrop.redraw_count(), 2);
+
+        camera.render_mode = TopViewRenderMode::FilledWireframe;
+        assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        assert_eq!(backdrop.redraw_count(), 3);
+
+        let coords: Vec<Coord> = vec![Coord::new(-11.0, 1.0), Coord::new(-9.0, 1.0), Coord::new(-9.0, 3.0)];
+        world.add_shape(Shape::from_coord_list(String::from("new"), coords, LinearTexture::new_plain(RGBAColor::new_rgb(200, 100, 0))).unwrap());
+
+        assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        assert_eq!(backdrop.redraw_count(), 4);
+
+        // and nothing changed
+        assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        assert_eq!(backdrop.redraw_count(), 4);
+    }
+
+    #[test]
+    fn turning_view_is_rendered_without_the_layer() {
+        let world: World = test_world();
+        let mut backdrop: TopViewBackdrop = TopViewBackdrop::new();
+        let mut camera: TopViewCamera = TopViewCamera::new(Coord::new(-20.0, 0.0), 10.0);
+
+        assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        assert_eq!(backdrop.redraw_count(), 1);
+
+        for k in 1..30 {
+            camera.angle = Angle::new_deg(k as f32 * 3.0);
+            camera.center = Coord::new(-20.0 + k as f32 * 0.1, 0.0);
+            assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        }
+
+        assert_eq!(backdrop.redraw_count(), 1);
+
+        // once the view stops turning the layer is drawn for the new angle and kept
+        for _ in 0..5 {
+            assert_same_as_plain_render(&mut backdrop, &world, &camera);
+        }
+
+        assert_eq!(backdrop.redraw_count(), 2);
+    }
+}
@@ -0,0 +1,512 @@
+// command line of the moving_agent binary
+//
+// moving_agent [world.ron] [options]            -- interactive window
+// moving_agent validate <world.ron>             -- load and check a world file
+// moving_agent render <world.ron> --out <file>  -- render one view into an image
+// moving_agent headless <world.ron> [script.ron] [--ticks N]
+// moving_agent replay <log.ron> [--ticks N]     -- repeat a logged run, check it ends the same
+// the window and headless runs can be recorded with --record <directory>
+// and their actions logged with --log-actions <file>,
+// both can start from a snapshot (--restore <snapshot.ron>) instead of a world file
+
+use std::path::PathBuf;
+
+use moving_agent::{
+    common_structs::Coord,
+    recording::{RecordingFormat, RecordingOptions},
+    shape::WorldSetup,
+    view::{self, SplitLayout, TopViewRenderMode, ViewMode, DEFAULT_EYE_DISTANCE, DEFAULT_TOP_VIEW_SCALE, MAX_SPLIT_SHARE, MIN_SPLIT_SHARE},
+};
+
+pub const DEFAULT_WORLD_FILE: &str = "data/world-shapes.ron";
+
+// largest window or image side, in pixels
+const MAX_VIEW_SIDE: i32 = 16384;
+
+pub const USAGE: &str = "usage:
+  moving_agent [world.ron] [options]
+  moving_agent validate <world.ron>
+  moving_agent render <world.ron> --out <image.png|image.ppm> [options]
+  moving_agent headless <world.ron> [script.ron] [--ticks N] [options]
+  moving_agent headless --restore <snapshot.ron> [script.ron] [--ticks N] [options]
+  moving_agent replay <log.ron> [--ticks N] [options]
+
+options:
+  --width <px>          window / image width, up to 16384
+  --height <px>         window / image height, up to 16384
+  --fov <deg>           agent's field of view, between 0 and 180
+  --x <x> --y <y>       agent's starting position
+  --angle <deg>         agent's starting direction
+  --view <mode>         top | first-person | depth | split | parallel | cross-eye | anaglyph
+  --scale <px>          pixels per world unit in top view, 1 to 1000
+  --top-view <mode>     wireframe | filled | solid | occluded (what the agent sees) | squares | discs (vertices only)
+  --split <layout>      side (top view on the left) | stacked (top view above), for the split view
+  --split-share <part>  part of the split view taken by the top view, 0.1 to 0.9
+  --eye-distance <d>    between the eyes of the stereo views, in world units
+
+recording (window and headless; in the window 'r' starts and stops recording):
+  --record <dir>        record from the start into the directory
+  --record-views <list> views to record, comma separated, or both (top,first-person)
+  --record-format <f>   png | ppm (numbered images) | gif (animated)
+  --record-every <n>    record every n-th tick
+
+action logs:
+  --log-actions <file>  log every action of every agent with its tick (window and headless)
+  --replay <log.ron>    play a logged run back in the window instead of loading a world
+
+snapshots (in the window 'o' saves one, 'l' goes back to the last one saved or restored):
+  --restore <file>      continue a saved simulation instead of loading a world (window and headless)
+  --save-snapshot <f>   save the simulation when the headless run ends";
+
+#[derive(Clone)]
+pub struct ViewOptions {
+    pub width: i32,
+    pub height: i32,
+    pub view_mode: ViewMode,
+    pub split_layout: SplitLayout,
+    pub eye_distance: f32,
+    pub scale: f32,
+    pub top_view_mode: TopViewRenderMode,
+    pub fov_deg: Option<f32>,
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    pub angle_deg: Option<f32>,
+    pub record_directory: Option<PathBuf>,
+    pub record_views: Vec<ViewMode>, // current view mode if empty
+    pub record_format: RecordingFormat,
+    pub record_every: u64,
+    pub log_actions: Option<PathBuf>,
+    pub replay: Option<PathBuf>, // window only
+    pub restore: Option<PathBuf>,
+    pub save_snapshot: Option<PathBuf>, // headless only
+}
+
+impl ViewOptions {
+    pub fn new() -> ViewOptions {
+        return ViewOptions {
+            width: 1000,
+            height: 720,
+            view_mode: ViewMode::Top,
+            split_layout: SplitLayout::default(),
+            eye_distance: DEFAULT_EYE_DISTANCE,
+            scale: DEFAULT_TOP_VIEW_SCALE,
+            top_view_mode: TopViewRenderMode::Wireframe,
+            fov_deg: None,
+            x: None,
+            y: None,
+            angle_deg: None,
+            record_directory: None,
+            record_views: Vec::new(),
+            record_format: RecordingFormat::Png,
+            record_every: 1,
+            log_actions: None,
+            replay: None,
+            restore: None,
+            save_snapshot: None,
+        };
+    }
+
+    pub fn recording_options(&self, directory: PathBuf, width: i32, height: i32) -> RecordingOptions {
+        let mut options: RecordingOptions = RecordingOptions::new(directory);
+
+        options.views = if self.record_views.is_empty() {vec![self.view_mode]} else {self.record_views.clone()};
+
+        for i in 0..options.views.len() {
+            options.views[i] = self.configured_view(options.views[i]);
+        }
+
+        options.format = self.record_format;
+        options.width = width;
+        options.height = height;
+        options.scale = self.scale;
+        options.top_view_mode = self.top_view_mode;
+        options.every = self.record_every;
+
+        return options;
+    }
+
+    pub fn configured_view(&self, view_mode: ViewMode) -> ViewMode {
+        // the view mode with the split layout and the eye distance given on the command line
+        return view_mode.with_split_layout(self.split_layout).with_eye_distance(self.eye_distance);
+    }
+
+    pub fn apply_to_setup(&self, setup: &mut WorldSetup) {
+        // command line values take precedence over the ones from world file
+
+        if let Some(fov_deg) = self.fov_deg {
+            setup.agents_field_of_view_deg = fov_deg;
+        }
+
+        if let Some(angle_deg) = self.angle_deg {
+            setup.initial_angle_deg = angle_deg;
+        }
+
+        setup.initial_coord = Coord::new(
+            self.x.unwrap_or(setup.initial_coord.x()),
+            self.y.unwrap_or(setup.initial_coord.y()),
+        );
+    }
+}
+
+pub enum CliCommand {
+    Run {
+        world: PathBuf,
+        options: ViewOptions,
+    },
+    Validate {
+        world: PathBuf,
+    },
+    Render {
+        world: PathBuf,
+        out: PathBuf,
+        options: ViewOptions,
+    },
+    Headless {
+        world: Option<PathBuf>, // none when restoring a snapshot
+        script: Option<PathBuf>,
+        ticks: Option<u64>,
+        options: ViewOptions,
+    },
+    Replay {
+        log: PathBuf,
+        ticks: Option<u64>,
+        options: ViewOptions,
+    },
+    Help,
+}
+
+pub fn parse_args(args: &[String]) -> Result<CliCommand, String> {
+    // args without the program name
+
+    match args.first().map(|arg| arg.as_str()) {
+        Some("-h") | Some("--help") | Some("help") => {
+            return Ok(CliCommand::Help);
+        }
+        Some("validate") => {
+            let (positional, _) = split_options(&args[1..], &[])?;
+
+            return Ok(CliCommand::Validate {
+                world: single_world_path(&positional)?,
+            });
+        }
+        Some("render") => {
+            let (positional, options) = split_options(&args[1..], &["out"])?;
+            let mut view_options: ViewOptions = ViewOptions::new();
+            let mut out: Option<PathBuf> = None;
+
+            for (name, value) in options.iter() {
+                if name == "out" {
+                    out = Some(PathBuf::from(value));
+                } else {
+                    set_view_option(&mut view_options, name, value)?;
+                }
+            }
+
+            return Ok(CliCommand::Render {
+                world: single_world_path(&positional)?,
+                out: out.ok_or(String::from("render needs --out <image.png>"))?,
+                options: view_options,
+            });
+        }
+        Some("headless") => {
+            let (positional, options) = split_options(
+                &args[1..],
+                &[&["ticks", "log-actions", "restore", "save-snapshot"][..], &RECORDING_OPTIONS[..]].concat(),
+            )?;
+            let mut view_options: ViewOptions = ViewOptions::new();
+            let mut ticks: Option<u64> = None;
+
+            for (name, value) in options.iter() {
+                if name == "ticks" {
+                    ticks = Some(parse_value(name, value)?);
+                } else if name == "log-actions" {
+                    view_options.log_actions = Some(PathBuf::from(value));
+                } else if name == "save-snapshot" {
+                    view_options.save_snapshot = Some(PathBuf::from(value));
+                } else if RECORDING_OPTIONS.contains(&name.as_str()) {
+                    set_recording_option(&mut view_options, name, value)?;
+                } else if name == "restore" {
+                    view_options.restore = Some(PathBuf::from(value));
+                } else {
+                    set_view_option(&mut view_options, name, value)?;
+                }
+            }
+
+            check_log_and_restore(&view_options)?;
+
+            if view_options.restore.is_some() {
+                // the world comes from the snapshot, only a script can be given
+                if positional.len() > 1 {
+                    return Err(String::from("headless --restore takes only an optional script file"));
+                }
+
+                return Ok(CliCommand::Headless {
+                    world: None,
+                    script: positional.first().map(PathBuf::from),
+                    ticks,
+                    options: view_options,
+                });
+            }
+
+            if positional.is_empty() || positional.len() > 2 {
+                return Err(String::from("headless needs a world file and optionally a script file"));
+            }
+
+            return Ok(CliCommand::Headless {
+                world: Some(PathBuf::from(&positional[0])),
+                script: positional.get(1).map(PathBuf::from),
+                ticks,
+                options: view_options,
+            });
+        }
+        Some("replay") => {
+            // the world and the agents' starting poses come from the log
+            let (positional, options) = split_options(&args[1..], &[&["ticks"][..], &RECORDING_OPTIONS[..]].concat())?;
+            let mut view_options: ViewOptions = ViewOptions::new();
+            let mut ticks: Option<u64> = None;
+
+            for (name, value) in options.iter() {
+                if name == "ticks" {
+                    ticks = Some(parse_value(name, value)?);
+                } else if RECORDING_OPTIONS.contains(&name.as_str()) {
+                    set_recording_option(&mut view_options, name, value)?;
+                } else {
+                    set_view_option(&mut view_options, name, value)?;
+                }
+            }
+
+            if positional.len() != 1 {
+                return Err(String::from("replay needs exactly one log file"));
+            }
+
+            return Ok(CliCommand::Replay {
+                log: PathBuf::from(&positional[0]),
+                ticks,
+                options: view_options,
+            });
+        }
+        _ => {
+            let (positional, options) = split_options(
+                args,
+                &[&["log-actions", "replay", "restore"][..], &RECORDING_OPTIONS[..]].concat(),
+            )?;
+            let mut view_options: ViewOptions = ViewOptions::new();
+
+            for (name, value) in options.iter() {
+                if name == "log-actions" {
+                    view_options.log_actions = Some(PathBuf::from(value));
+                } else if name == "replay" {
+                    view_options.replay = Some(PathBuf::from(value));
+                } else if RECORDING_OPTIONS.contains(&name.as_str()) {
+                    set_recording_option(&mut view_options, name, value)?;
+                } else if name == "restore" {
+                    view_options.restore = Some(PathBuf::from(value));
+                } else {
+                    set_view_option(&mut view_options, name, value)?;
+                }
+            }
+
+            check_log_and_restore(&view_options)?;
+
+            if view_options.replay.is_some() && view_options.restore.is_some() {
+                return Err(String::from("--replay and --restore can't be used together"));
+            }
+
+            if (view_options.replay.is_some() || view_options.restore.is_some()) && !positional.is_empty() {
+                return Err(String::from("a replay or a snapshot brings its own world, no world file can be given"));
+            }
+
+            let world: PathBuf = match positional.len() {
+                0 => PathBuf::from(DEFAULT_WORLD_FILE),
+                1 => PathBuf::from(&positional[0]),
+                _ => return Err(String::from("only one world file can be given")),
+            };
+
+            return Ok(CliCommand::Run { world, options: view_options });
+        }
+    }
+}
+
+fn split_options(args: &[String], extra_options: &[&str]) -> Result<(Vec<String>, Vec<(String, String)>), String> {
+    // separates positional arguments from "--name value" pairs
+    // view options are accepted everywhere, extra ones only where the command asks for them
+
+    let mut positional: Vec<String> = Vec::new();
+    let mut options: Vec<(String, String)> = Vec::new();
+    let mut i: usize = 0;
+
+    while i < args.len() {
+        match args[i].strip_prefix("--") {
+            Some(name) => {
+                if !VIEW_OPTIONS.contains(&name) && !extra_options.contains(&name) {
+                    return Err(format!("unknown option --{}", name));
+                }
+
+                match args.get(i + 1) {
+                    Some(value) => options.push((String::from(name), String::from(value))),
+                    None => return Err(format!("option --{} needs a value", name)),
+                }
+
+                i += 2;
+            }
+            None => {
+                positional.push(String::from(&args[i]));
+                i += 1;
+            }
+        }
+    }
+
+    return Ok((positional, options));
+}
+
+const VIEW_OPTIONS: [&str; 12] = [
+    "width", "height", "fov", "x", "y", "angle", "view", "scale", "top-view", "split", "split-share", "eye-distance",
+];
+
+fn set_view_option(view_options: &mut ViewOptions, name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "width" => view_options.width = parse_view_side(name, value)?,
+        "height" => view_options.height = parse_view_side(name, value)?,
+        "fov" => {
+            let fov_deg: f32 = parse_value(name, value)?;
+
+            // NaN fails this too
+            if !(fov_deg > 0.0 && fov_deg < 180.0) {
+                return Err(String::from("--fov should be more than 0 and less than 180 degrees"));
+            }
+
+            view_options.fov_deg = Some(fov_deg);
+        }
+        "x" => view_options.x = Some(parse_finite(name, value)?),
+        "y" => view_options.y = Some(parse_finite(name, value)?),
+        "angle" => view_options.angle_deg = Some(parse_finite(name, value)?),
+        "scale" => view_options.scale = view::clamp_top_view_scale(parse_positive_f32(name, value)?),
+        "view" => {
+            view_options.view_mode = ViewMode::from_name(value)
+                .ok_or(format!("unknown view mode '{}', expected top, first-person, depth, split, parallel, cross-eye or anaglyph", value))?;
+        }
+        "top-view" => {
+            view_options.top_view_mode = TopViewRenderMode::from_name(value)
+                .ok_or(format!("unknown top view mode '{}', expected wireframe, filled, solid, occluded, squares or discs", value))?;
+        }
+        "split" => {
+            let is_stacked: bool = match value {
+                "side" => false,
+                "stacked" => true,
+                _ => return Err(format!("unknown split layout '{}', expected side or stacked", value)),
+            };
+
+            view_options.split_layout = SplitLayout::new(is_stacked, view_options.split_layout.top_view_share);
+        }
+        "split-share" => {
+            let share: f32 = parse_value(name, value)?;
+
+            if !(MIN_SPLIT_SHARE..=MAX_SPLIT_SHARE).contains(&share) {
+                return Err(format!("--split-share should be between {} and {}", MIN_SPLIT_SHARE, MAX_SPLIT_SHARE));
+            }
+
+            view_options.split_layout = SplitLayout::new(view_options.split_layout.is_stacked, share);
+        }
+        "eye-distance" => {
+            view_options.eye_distance = parse_positive_f32(name, value)?;
+        }
+        _ => return Err(format!("option --{} is not valid here", name)),
+    }
+
+    // the layout and the eye distance apply to the view whichever option came first
+    view_options.view_mode = view_options.configured_view(view_options.view_mode);
+
+    return Ok(());
+}
+
+const RECORDING_OPTIONS: [&str; 4] = ["record", "record-views", "record-format", "record-every"];
+
+fn set_recording_option(view_options: &mut ViewOptions, name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "record" => view_options.record_directory = Some(PathBuf::from(value)),
+        "record-views" => {
+            view_options.record_views.clear();
+
+            for view_name in value.split(',') {
+                if view_name == "both" {
+                    view_options.record_views.push(ViewMode::Top);
+                    view_options.record_views.push(ViewMode::FirstPerson);
+                } else {
+                    view_options.record_views.push(
+                        ViewMode::from_name(view_name)
+                            .ok_or(format!("unknown view mode '{}', expected top, first-person, depth, split, parallel, cross-eye, anaglyph or both", view_name))?,
+                    );
+                }
+            }
+        }
+        "record-format" => {
+            view_options.record_format = RecordingFormat::from_name(value)
+                .ok_or(format!("unknown recording format '{}', expected png, ppm or gif", value))?;
+        }
+        "record-every" => view_options.record_every = parse_positive(name, value)? as u64,
+        _ => return Err(format!("option --{} is not valid here", name)),
+    }
+
+    return Ok(());
+}
+
+fn check_log_and_restore(view_options: &ViewOptions) -> Result<(), String> {
+    // an action log starts from a world file, a restored simulation has none
+    if view_options.log_actions.is_some() && view_options.restore.is_some() {
+        return Err(String::from("--log-actions can't be used with --restore"));
+    }
+
+    return Ok(());
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    return value.parse().map_err(|_| format!("invalid value '{}' for --{}", value, name));
+}
+
+fn parse_positive(name: &str, value: &str) -> Result<i32, String> {
+    let number: i32 = parse_value(name, value)?;
+
+    if number > 0 {
+        return Ok(number);
+    } else {
+        return Err(format!("--{} should be a positive number", name));
+    }
+}
+
+fn parse_view_side(name: &str, value: &str) -> Result<i32, String> {
+    let side: i32 = parse_positive(name, value)?;
+
+    if side > MAX_VIEW_SIDE {
+        return Err(format!("--{} should be at most {}", name, MAX_VIEW_SIDE));
+    }
+
+    return Ok(side);
+}
+
+fn parse_finite(name: &str, value: &str) -> Result<f32, String> {
+    let number: f32 = parse_value(name, value)?;
+
+    if number.is_finite() {
+        return Ok(number);
+    } else {
+        return Err(format!("--{} should be a finite number", name));
+    }
+}
+
+fn parse_positive_f32(name: &str, value: &str) -> Result<f32, String> {
+    let number: f32 = parse_value(name, value)?;
+
+    if number.is_finite() && number > 0.0 {
+        return Ok(number);
+    } else {
+        return Err(format!("--{} should be a positive number", name));
+    }
+}
+
+fn single_world_path(positional: &[String]) -> Result<PathBuf, String> {
+    if positional.len() == 1 {
+        return Ok(PathBuf::from(&positional[0]));
+    } else {
+        return Err(String::from("expected exactly one world file"));
+    }
+}
@@ -0,0 +1,392 @@
+// continuous collisions of moving circles (agents, dynamic objects) with segments (walls, sides of objects)
+//
+// a circle moving along a displacement is swept against every segment: against its inner part
+// (a line moved by the radius towards the circle) and against both of its ends (base and base + tip),
+// the earliest time of impact wins; the circle stops just before it, and the rest of the displacement
+// slides along the wall, which is again swept, a few times at most
+//
+// segments are always looked at in the order they are given, and the first of equally early contacts wins,
+// so the same input gives the same result
+//
+// for colliders that use their actual outline instead of a circle there is a plain overlap test
+// of a convex polygon with segments, moves of such colliders are done in small steps by the caller
+
+use crate::{
+    common_structs::Coord,
+    vector_2d::Vector2D,
+};
+
+// how many times a move can be deflected along walls within one step
+const MAX_SLIDES: usize = 4;
+// gap kept between the circle and the wall it stopped at
+const SKIN: f32 = 0.0001;
+
+#[derive(Clone, Copy)]
+pub struct Contact {
+    pub time: f32, // fraction of the displacement done before touching, 0.0 to 1.0
+    pub normal: Coord, // unit normal of the touched surface, pointing towards the circle
+    pub segment: usize, // index in the slice of segments
+}
+
+pub struct SlideResult {
+    pub center: Coord, // where the circle ended up
+    pub normals: Vec<Coord>, // normals of all contacts on the way, in order
+}
+
+pub fn sweep_circle(center: Coord, radius: f32, displacement: Coord, segments: &[Vector2D]) -> Option<Contact> {
+    // earliest contact of the circle moving from center by displacement with any of the segments
+
+    let mut earliest: Option<Contact> = None;
+
+    for i in 0..segments.len() {
+        if let Some((time, normal)) = sweep_against_segment(center, radius, displacement, &segments[i]) {
+            let is_earlier: bool = match earliest {
+                Some(contact) => time < contact.time,
+                None => true,
+            };
+
+            if is_earlier {
+                earliest = Some(Contact { time, normal, segment: i });
+            }
+        }
+    }
+
+    return earliest;
+}
+
+pub fn slide_circle(center: Coord, radius: f32, displacement: Coord, segments: &[Vector2D]) -> SlideResult {
+    // moves the circle as far as it can go, sliding along whatever it touches
+
+    let mut position: Coord = center;
+    let mut remaining: Coord = displacement;
+    let mut normals: Vec<Coord> = Vec::new();
+
+    for _ in 0..MAX_SLIDES {
+        let remaining_length: f32 = length(remaining);
+
+        if remaining_length <= 0.0 {
+            break;
+        }
+
+        match sweep_circle(position, radius, remaining, segments) {
+            Some(contact) => {
+                // stop a little before the contact, so that the next sweep doesn't start touching
+                let safe_time: f32 = f32::max(0.0, contact.time - SKIN / remaining_length);
+
+                position = position.new_offset(remaining.new_scaled(safe_time));
+
+                // what is left of the move goes along the wall
+                let left_over: Coord = remaining.new_scaled(1.0 - safe_time);
+                let into_wall: f32 = dot(left_over, contact.normal);
+
+                remaining = if into_wall < 0.0 {
+                    left_over.new_offset(contact.normal.new_scaled(-into_wall))
+                } else {
+                    left_over
+                };
+
+                normals.push(contact.normal);
+            }
+            None => {
+                position = position.new_offset(remaining);
+                remaining = Coord::new(0.0, 0.0);
+            }
+        }
+    }
+
+    return SlideResult { center: position, normals };
+}
+
+pub fn resolve_overlaps(center: Coord, radius: f32, segments: &[Vector2D]) -> Coord {
+    // pushes a circle that already overlaps segments out of them, one segment after another
+    // (ends included), returns the total push
+
+    let mut position: Coord = center;
+
+    for i in 0..segments.len() {
+        let closest: Coord = segments[i].closest_point(position);
+        let away: Coord = Coord::new(position.x() - closest.x(), position.y() - closest.y());
+        let distance: f32 = length(away);
+
+        if distance >= radius {
+            continue;
+        }
+
+        let direction: Coord = if distance > 0.0 {
+            away.new_scaled(1.0 / distance)
+        } else {
+            // center exactly on the segment -- use its normal
+            segment_normal(&segments[i])
+        };
+
+        position = position.new_offset(direction.new_scaled(radius - distance));
+    }
+
+    return Coord::new(position.x() - center.x(), position.y() - center.y());
+}
+
+pub fn polygon_hit(polygon: &[Coord], segments: &[Vector2D]) -> Option<usize> {
+    // first segment that overlaps a convex polygon (vertices in order, either way around):
+    // it crosses one of the polygon sides, or one of its ends is inside;
+    // touching without crossing is not an overlap
+
+    if polygon.len() < 2 {
+        return None;
+    }
+
+    for j in 0..segments.len() {
+        for i in 0..polygon.len() {
+            let start: Coord = polygon[i];
+            let end: Coord = polygon[(i + 1) % polygon.len()];
+            let side: Vector2D = Vector2D::new(
+                start,
+                Coord::new(end.x() - start.x(), end.y() - start.y()),
+                segments[j].texture,
+            );
+
+            if side.intersect(&segments[j]).is_some() {
+                return Some(j);
+            }
+        }
+
+        if is_inside_polygon(segments[j].base, polygon) || is_inside_polygon(segments[j].tip_point(), polygon) {
+            return Some(j);
+        }
+    }
+
+    return None;
+}
+
+pub fn normal_towards(segment: &Vector2D, point: Coord) -> Coord {
+    // unit normal of the segment, on the side of the point
+    let normal: Coord = segment_normal(segment);
+    let to_point: Coord = Coord::new(point.x() - segment.base.x(), point.y() - segment.base.y());
+
+    if dot(normal, to_point) < 0.0 {
+        return normal.new_scaled(-1.0);
+    }
+
+    return normal;
+}
+
+fn is_inside_polygon(point: Coord, polygon: &[Coord]) -> bool {
+    // strictly inside a convex polygon: on the same side of every polygon side
+    if polygon.len() < 3 {
+        return false;
+    }
+
+    let mut sign: f32 = 0.0;
+
+    for i in 0..polygon.len() {
+        let start: Coord = polygon[i];
+        let end: Coord = polygon[(i + 1) % polygon.len()];
+        let cross: f32 = (end.x() - start.x()) * (point.y() - start.y()) - (end.y() - start.y()) * (point.x() - start.x());
+
+        if cross == 0.0 || cross * sign < 0.0 {
+            return false;
+        }
+
+        sign = cross;
+    }
+
+    return true;
+}
+
+fn sweep_against_segment(center: Coord, radius: f32, displacement: Coord, segment: &Vector2D) -> Option<(f32, Coord)> {
+    // time of impact and contact normal of a moving circle with one segment
+
+    let mut earliest: Option<(f32, Coord)> = None;
+
+    let segment_length: f32 = segment.length();
+
+    if segment_length > 0.0 {
+        // inner part of the segment
+        let mut normal: Coord = segment_normal(segment);
+        let to_center: Coord = Coord::new(center.x() - segment.base.x(), center.y() - segment.base.y());
+
+        if dot(normal, to_center) < 0.0 {
+            normal = normal.new_scaled(-1.0);
+        }
+
+        let distance: f32 = dot(normal, to_center);
+        let approach_speed: f32 = -dot(normal, displacement);
+
+        if approach_speed > 0.0 {
+            // an already overlapping circle moving further in is stopped at once
+            let time: f32 = f32::max(0.0, (distance - radius) / approach_speed);
+
+            if time <= 1.0 {
+                let contact_center: Coord = center.new_offset(displacement.new_scaled(time));
+                let along: f32 = dot(
+                    Coord::new(contact_center.x() - segment.base.x(), contact_center.y() - segment.base.y()),
+                    segment.tip,
+                ) / (segment_length * segment_length);
+
+                if (0.0..=1.0).contains(&along) {
+                    earliest = Some((time, normal));
+                }
+            }
+        }
+    }
+
+    // both ends, the base and the tip point
+    let ends: [Coord; 2] = [segment.base, segment.tip_point()];
+
+    for end in ends {
+        if let Some((time, normal)) = sweep_against_point(center, radius, displacement, end) {
+            let is_earlier: bool = match earliest {
+                Some((earliest_time, _)) => time < earliest_time,
+                None => true,
+            };
+
+            if is_earlier {
+                earliest = Some((time, normal));
+            }
+        }
+    }
+
+    return earliest;
+}
+
+fn sweep_against_point(center: Coord, radius: f32, displacement: Coord, point: Coord) -> Option<(f32, Coord)> {
+    // solves |center + displacement * t - point| = radius for the smallest t in 0.0..1.0
+
+    let from_point: Coord = Coord::new(center.x() - point.x(), center.y() - point.y());
+
+    let a: f32 = dot(displacement, displacement);
+    let b: f32 = 2.0 * dot(from_point, displacement);
+    let c: f32 = dot(from_point, from_point) - radius * radius;
+
+    if a == 0.0 || b >= 0.0 {
+        // not moving, or moving away from the point
+        return None;
+    }
+
+    let time: f32 = if c <= 0.0 {
+        // already touching and moving closer
+        0.0
+    } else {
+        let discriminant: f32 = b * b - 4.0 * a * c;
+
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        (-b - f32::sqrt(discriminant)) / (2.0 * a)
+    };
+
+    if time > 1.0 {
+        return None;
+    }
+
+    let contact_center: Coord = center.new_offset(displacement.new_scaled(time));
+    let away: Coord = Coord::new(contact_center.x() - point.x(), contact_center.y() - point.y());
+    let away_length: f32 = length(away);
+
+    let normal: Coord = if away_length > 0.0 {
+        away.new_scaled(1.0 / away_length)
+    } else {
+        displacement.new_scaled(-1.0 / f32::sqrt(a))
+    };
+
+    return Some((time, normal));
+}
+
+fn segment_normal(segment: &Vector2D) -> Coord {
+    let segment_length: f32 = segment.length();
+
+    if segment_length == 0.0 {
+        return Coord::new(1.0, 0.0);
+    }
+
+    return Coord::new(-segment.tip.y() / segment_length, segment.tip.x() / segment_length);
+}
+
+fn dot(a: Coord, b: Coord) -> f32 {
+    return a.x() * b.x() + a.y() * b.y();
+}
+
+fn length(a: Coord) -> f32 {
+    return f32::sqrt(a.x() * a.x() + a.y() * a.y());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common_structs::RGBAColor, linear_texture::LinearTexture};
+
+    const RADIUS: f32 = 0.5;
+
+    fn wall(from: (f32, f32), to: (f32, f32)) -> Vector2D {
+        return Vector2D::new(
+            Coord::new(from.0, from.1),
+            Coord::new(to.0 - from.0, to.1 - from.1),
+            LinearTexture::new_plain(RGBAColor::new()),
+        );
+    }
+
+    fn is_close(a: f32, b: f32) -> bool {
+        return (a - b).abs() < 0.001;
+    }
+
+    #[test]
+    fn fast_circle_does_not_tunnel_through_thin_wall() {
+        // a hundred world units in one tick against a wall of no thickness
+        let walls: Vec<Vector2D> = vec![wall((5.0, -5.0), (5.0, 5.0))];
+
+        let contact: Contact = sweep_circle(Coord::new(0.0, 0.0), RADIUS, Coord::new(100.0, 0.0), &walls).expect("the wall is hit");
+        assert!(is_close(contact.time * 100.0, 5.0 - RADIUS));
+        assert!(is_close(contact.normal.x(), -1.0));
+
+        let result: SlideResult = slide_circle(Coord::new(0.0, 0.0), RADIUS, Coord::new(100.0, 0.0), &walls);
+        assert!(result.center.x() <= 5.0 - RADIUS);
+        assert!(is_close(result.center.x(), 5.0 - RADIUS));
+        assert!(is_close(result.center.y(), 0.0));
+        assert_eq!(result.normals.len(), 1);
+    }
+
+    #[test]
+    fn sliding_keeps_tangential_component() {
+        // hits the floor a quarter of the way, the rest of the move along the floor is kept
+        let walls: Vec<Vector2D> = vec![wall((-50.0, 0.0), (50.0, 0.0))];
+
+        let result: SlideResult = slide_circle(Coord::new(0.0, 1.0), RADIUS, Coord::new(3.0, -2.0), &walls);
+
+        assert!(is_close(result.center.x(), 3.0));
+        assert!(is_close(result.center.y(), RADIUS));
+        assert!(result.center.y() >= RADIUS);
+    }
+
+    #[test]
+    fn convex_corner_does_not_stop_the_circle() {
+        // the circle catches the end of a wall off center and goes around it
+        let walls: Vec<Vector2D> = vec![wall((2.0, -5.0), (2.0, 0.0))];
+        let mut center: Coord = Coord::new(0.0, 0.2);
+
+        for _ in 0..20 {
+            center = slide_circle(center, RADIUS, Coord::new(0.4, 0.0), &walls).center;
+        }
+
+        let push: Coord = resolve_overlaps(center, RADIUS, &walls);
+
+        assert!(center.x() > 2.0 + RADIUS);
+        assert!(push.x() == 0.0 && push.y() == 0.0);
+    }
+
+    #[test]
+    fn inner_corner_does_not_trap_the_circle() {
+        // pushed into a corner, the circle stays out of both walls and can move away at once
+        let walls: Vec<Vector2D> = vec![wall((0.0, 0.0), (10.0, 0.0)), wall((0.0, 0.0), (0.0, 10.0))];
+
+        let stuck: SlideResult = slide_circle(Coord::new(3.0, 3.0), RADIUS, Coord::new(-5.0, -5.0), &walls);
+
+        assert!(stuck.center.x() >= RADIUS && stuck.center.y() >= RADIUS);
+        assert!(is_close(stuck.center.x(), RADIUS) && is_close(stuck.center.y(), RADIUS));
+
+        let away: SlideResult = slide_circle(stuck.center, RADIUS, Coord::new(1.0, 0.5), &walls);
+
+        assert!(is_close(away.center.x(), stuck.center.x() + 1.0));
+        assert!(is_close(away.center.y(), stuck.center.y() + 0.5));
+        assert!(away.normals.is_empty());
+    }
+}
@@ -22,7 +22,7 @@ pub enum Palette {
     Grass,
 }
 
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub struct RGBAColor {
     pub r: u8,
     pub g: u8,
@@ -114,7 +114,7 @@ impl RGBAColor {
 }
 
 
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, Default, Deserialize, Serialize)]
 pub struct Coord {
     x: f32,
     y: f32,
@@ -191,7 +191,7 @@ impl Coord {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Marker {
     Square(i32),
     Disc(i32),
@@ -229,7 +229,7 @@ impl Dot {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Deserialize, Serialize)]
 pub struct Angle {
     a: f32,
 }
@@ -346,7 +346,33 @@ pub struct AlignedBox {
 }
 
 impl  AlignedBox {
-    // pub fn new()
+    pub fn new(x_0: f32, y_0: f32, x_1: f32, y_1: f32) -> AlignedBox {
+        // corners can be given in any order
+        return AlignedBox {
+            x_0: f32::min(x_0, x_1),
+            y_0: f32::min(y_0, y_1),
+            x_1: f32::max(x_0, x_1),
+            y_1: f32::max(y_0, y_1),
+        };
+    }
+
+    pub fn around_point(center: Coord, half_size: f32) -> AlignedBox {
+        return AlignedBox::new(
+            center.x() - half_size,
+            center.y() - half_size,
+            center.x() + half_size,
+            center.y() + half_size,
+        );
+    }
+
+    pub fn width(&self) -> f32 {
+        return self.x_1 - self.x_0;
+    }
+
+    pub fn height(&self) -> f32 {
+        return self.y_1 - self.y_0;
+    }
+
     pub fn box_intersection(&self, tested_box: &AlignedBox) -> Option<AlignedBox> {
 
         let x_intersection: Option<LineSeg> = LineSeg::linear_intersection(
@@ -0,0 +1,385 @@
+// controllers -- "brains" of agents
+// every tick a controller gets what its agent senses (colors and distances seen along view rays,
+// and which sides were bumped into during the last tick) and answers with actions for this tick
+//
+// built-in controllers: keyboard, scripted replay, random walk and wall follower;
+// anything else (e.g. a neural network) only has to implement the Controller trait
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::{Action, Agent, CollisionFlags, Direction, WallHit},
+    common_structs::RGBAColor,
+    headless::TimedAction,
+};
+
+// number of view rays a controller senses with, unless it asks for another number
+pub const DEFAULT_SENSOR_RAYS: i32 = 32;
+
+// what an agent perceives at the start of a tick
+pub struct Senses {
+    pub tick: u64,
+    pub hits: Vec<Option<WallHit>>, // one per ray, left to right, with everything known about the hit
+    pub colors: Vec<RGBAColor>, // one per ray, same as Agent::get_view, empty for depth-only controllers
+    pub distances: Vec<f32>, // one per ray, max view distance where nothing was hit
+    pub max_distance: f32,
+    pub collisions: CollisionFlags, // during the previous tick
+}
+
+impl Senses {
+    pub fn from_agent(agent: &Agent, ray_count: i32, tick: u64) -> Senses {
+        return Senses::with_options(agent, ray_count, false, tick);
+    }
+
+    pub fn with_options(agent: &Agent, ray_count: i32, is_depth_only: bool, tick: u64) -> Senses {
+        let hits: Vec<Option<WallHit>> = agent.cast_view_rays(ray_count);
+        let max_distance: f32 = agent.max_view_distance();
+
+        let mut distances: Vec<f32> = Vec::with_capacity(hits.len());
+        let mut colors: Vec<RGBAColor> = Vec::new();
+
+        for i in 0..hits.len() {
+            match hits[i] {
+                Some(hit) => distances.push(hit.distance),
+                None => distances.push(max_distance),
+            }
+
+            if !is_depth_only {
+                colors.push(agent.view_color(&hits[i]));
+            }
+        }
+
+        return Senses {
+            tick,
+            hits,
+            colors,
+            distances,
+            max_distance,
+            collisions: agent.collisions(),
+        };
+    }
+
+    pub fn front_distance(&self) -> f32 {
+        // the shorter of the two middle rays
+        if self.distances.is_empty() {
+            return self.max_distance;
+        }
+
+        let middle: usize = self.distances.len() / 2;
+
+        if self.distances.len() % 2 == 0 {
+            return f32::min(self.distances[middle - 1], self.distances[middle]);
+        } else {
+            return self.distances[middle];
+        }
+    }
+}
+
+pub trait Controller {
+    // actions for the current tick, applied in the given order
+    fn act(&mut self, senses: &Senses) -> Vec<Action>;
+
+    // how many view rays the controller wants to sense with
+    fn ray_count(&self) -> i32 {
+        return DEFAULT_SENSOR_RAYS;
+    }
+
+    // controllers that only look at distances can skip colors
+    fn is_depth_only(&self) -> bool {
+        return false;
+    }
+
+    // keyboard input of the user, passed to the controller of the selected agent
+    fn key_press(&mut self, _key: char) {}
+
+    fn key_release(&mut self, _key: char) {}
+
+    // how to create this controller again as it is now, for snapshots of the simulation;
+    // none if it can't be described, the agent is restored without a controller then
+    fn description(&self) -> Option<ControllerDescription> {
+        return None;
+    }
+}
+
+// controllers that can be given to agents in world files
+#[derive(Clone, Deserialize, Serialize)]
+pub enum ControllerDescription {
+    Keyboard,
+    Scripted(Vec<TimedAction>),
+    RandomWalk {
+        seed: u64,
+        #[serde(default)]
+        position: u64, // numbers of the random stream already used, 0 for a new walk
+    },
+    WallFollower {
+        side: Side,
+        distance: f32,
+    },
+}
+
+impl ControllerDescription {
+    pub fn create(&self) -> Box<dyn Controller> {
+        match self {
+            ControllerDescription::Keyboard => Box::new(KeyboardController::new()),
+            ControllerDescription::Scripted(script) => Box::new(ScriptedController::new(script.clone())),
+            ControllerDescription::RandomWalk { seed, position } => Box::new(RandomWalkController::continued(*seed, *position)),
+            ControllerDescription::WallFollower { side, distance } => Box::new(WallFollowerController::new(*side, *distance)),
+        }
+    }
+}
+
+// actions from the keys held down, repeated every tick until the key is released,
+// so that several held keys combine (e.g. forward and rotate right)
+pub struct KeyboardController {
+    held: Vec<char>,
+    tapped: Vec<char>, // pressed since the previous tick, maybe already released
+}
+
+impl KeyboardController {
+    pub fn new() -> KeyboardController {
+        return KeyboardController {
+            held: Vec::new(),
+            tapped: Vec::new(),
+        };
+    }
+
+    pub fn action_for_key(key: char) -> Option<Action> {
+        match key {
+            'w' => Some(Action::Move(Direction::Forward)),
+            's' => Some(Action::Move(Direction::Backward)),
+            'd' => Some(Action::Move(Direction::Right)),
+            'a' => Some(Action::Move(Direction::Left)),
+            'e' => Some(Action::Rotate(1.0)), // rotate right
+            'q' => Some(Action::Rotate(-1.0)), // rotate left
+            _ => None,
+        }
+    }
+}
+
+impl Controller for KeyboardController {
+    fn act(&mut self, _senses: &Senses) -> Vec<Action> {
+        let mut actions: Vec<Action> = Vec::new();
+
+        for i in 0..self.tapped.len() {
+            if let Some(action) = KeyboardController::action_for_key(self.tapped[i]) {
+                actions.push(action);
+            }
+        }
+
+        for i in 0..self.held.len() {
+            if self.tapped.contains(&self.held[i]) {
+                continue;
+            }
+
+            if let Some(action) = KeyboardController::action_for_key(self.held[i]) {
+                actions.push(action);
+            }
+        }
+
+        self.tapped.clear();
+
+        return actions;
+    }
+
+    fn ray_count(&self) -> i32 {
+        // the user has their own eyes
+        return 0;
+    }
+
+    fn key_press(&mut self, key: char) {
+        // key repeat of the OS sends presses again and again, they change nothing
+        if !self.held.contains(&key) {
+            self.held.push(key);
+            self.tapped.push(key);
+        }
+    }
+
+    fn key_release(&mut self, key: char) {
+        self.held.retain(|held_key| *held_key != key);
+    }
+
+    fn description(&self) -> Option<ControllerDescription> {
+        // held keys are not kept, they are released by the time the snapshot is restored
+        return Some(ControllerDescription::Keyboard);
+    }
+}
+
+// replays a list of timed actions, the agent field of timed actions is ignored
+pub struct ScriptedController {
+    script: Vec<TimedAction>, // sorted by tick
+    next_scripted: usize,
+}
+
+impl ScriptedController {
+    pub fn new(mut script: Vec<TimedAction>) -> ScriptedController {
+        // stable sort keeps the order of actions scheduled for the same tick
+        script.sort_by_key(|timed_action| timed_action.tick);
+
+        return ScriptedController {
+            script,
+            next_scripted: 0,
+        };
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.next_scripted >= self.script.len();
+    }
+}
+
+impl Controller for ScriptedController {
+    fn act(&mut self, senses: &Senses) -> Vec<Action> {
+        let mut actions: Vec<Action> = Vec::new();
+
+        while self.next_scripted < self.script.len() && self.script[self.next_scripted].tick <= senses.tick {
+            actions.push(self.script[self.next_scripted].action);
+            self.next_scripted += 1;
+        }
+
+        return actions;
+    }
+
+    fn ray_count(&self) -> i32 {
+        return 0;
+    }
+
+    fn description(&self) -> Option<ControllerDescription> {
+        // what is still to be done
+        return Some(ControllerDescription::Scripted(self.script[self.next_scripted..].to_vec()));
+    }
+}
+
+// walks forward, now and then turns by a random angle, turns away when bumping into something;
+// the same seed gives the same walk
+pub struct RandomWalkController {
+    seed: u64,
+    rng: ChaCha8Rng,
+    turn_chance: f32, // per tick
+    max_turn_deg: f32,
+}
+
+impl RandomWalkController {
+    pub fn new(seed: u64) -> RandomWalkController {
+        return RandomWalkController {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            turn_chance: 0.05,
+            max_turn_deg: 30.0,
+        };
+    }
+
+    pub fn continued(seed: u64, position: u64) -> RandomWalkController {
+        // the walk with given seed, from where it was after using position numbers
+        let mut controller: RandomWalkController = RandomWalkController::new(seed);
+        controller.rng.set_word_pos(position as u128);
+
+        return controller;
+    }
+}
+
+impl Controller for RandomWalkController {
+    fn act(&mut self, senses: &Senses) -> Vec<Action> {
+        if senses.collisions.front {
+            let sign: f32 = if self.rng.gen_bool(0.5) {1.0} else {-1.0};
+
+            return vec![Action::Turn(sign * self.rng.gen_range(90.0..180.0))];
+        }
+
+        let mut actions: Vec<Action> = Vec::new();
+
+        if self.rng.gen::<f32>() < self.turn_chance {
+            actions.push(Action::Turn(self.rng.gen_range(-self.max_turn_deg..self.max_turn_deg)));
+        }
+
+        actions.push(Action::Move(Direction::Forward));
+
+        return actions;
+    }
+
+    fn ray_count(&self) -> i32 {
+        return 0;
+    }
+
+    fn description(&self) -> Option<ControllerDescription> {
+        return Some(ControllerDescription::RandomWalk {
+            seed: self.seed,
+            position: self.rng.get_word_pos() as u64,
+        });
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+// keeps a wall on one side at about given distance, turns away from walls ahead
+pub struct WallFollowerController {
+    side: Side,
+    distance: f32,
+    turn_step_deg: f32,
+}
+
+impl WallFollowerController {
+    pub fn new(side: Side, distance: f32) -> WallFollowerController {
+        return WallFollowerController {
+            side,
+            distance,
+            turn_step_deg: 5.0,
+        };
+    }
+}
+
+impl Controller for WallFollowerController {
+    fn act(&mut self, senses: &Senses) -> Vec<Action> {
+        if senses.distances.is_empty() {
+            return vec![Action::Move(Direction::Forward)];
+        }
+
+        // turning toward the wall side: negative degrees for left, positive for right
+        let toward: f32 = match self.side {
+            Side::Left => -1.0,
+            Side::Right => 1.0,
+        };
+
+        let side_distance: f32 = match self.side {
+            Side::Left => senses.distances[0],
+            Side::Right => senses.distances[senses.distances.len() - 1],
+        };
+
+        if senses.front_distance() < self.distance * 2.0 || senses.collisions.front {
+            // corner ahead -- turn away from the wall without moving
+            return vec![Action::Turn(-toward * self.turn_step_deg * 2.0)];
+        }
+
+        let mut actions: Vec<Action> = Vec::new();
+
+        // side rays look ahead at an angle, so compare with a proportionally longer distance
+        if side_distance > self.distance * 3.0 {
+            actions.push(Action::Turn(toward * self.turn_step_deg));
+        } else if side_distance < self.distance * 1.5 {
+            actions.push(Action::Turn(-toward * self.turn_step_deg / 2.0));
+        }
+
+        actions.push(Action::Move(Direction::Forward));
+
+        return actions;
+    }
+
+    fn ray_count(&self) -> i32 {
+        return 15;
+    }
+
+    fn is_depth_only(&self) -> bool {
+        return true;
+    }
+
+    fn description(&self) -> Option<ControllerDescription> {
+        return Some(ControllerDescription::WallFollower {
+            side: self.side,
+            distance: self.distance,
+        });
+    }
+}
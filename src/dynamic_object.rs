@@ -0,0 +1,263 @@
+// dynamic objects -- shapes that live in the world next to static walls,
+// but have position, angle and velocity, and are stepped every tick:
+// patrolling objects and doors follow waypoints, free objects drift, slow down and stop at walls,
+// pushable objects are moved by the agent
+//
+// for collisions a dynamic object is approximated by its bounding circle around the anchor,
+// free objects are swept along their way, so they can't pass through walls
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collision::{self, SlideResult},
+    common_structs::{AlignedBox, Angle, Coord},
+    error::{Error, Result},
+    shape::{Shape, ShapeDescription},
+    vector_2d::Vector2D,
+    wall_grid::WallGrid,
+};
+
+#[derive(Clone, Deserialize, Serialize)]
+pub enum Motion {
+    Free {
+        damping: f32, // fraction of velocity lost per second
+    },
+    Patrol {
+        waypoints: Vec<Coord>,
+        speed: f32, // world units per second
+        #[serde(default)]
+        next_waypoint: usize,
+    },
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DynamicObjectDescription {
+    pub shape: ShapeDescription, // vertices are relative to the anchor, anchor is the starting position
+    #[serde(default)]
+    pub velocity: Coord,
+    #[serde(default)]
+    pub angle_deg: f32,
+    #[serde(default)]
+    pub angular_velocity_deg: f32,
+    pub motion: Motion,
+    #[serde(default)]
+    pub pushable: bool,
+}
+
+// what a moving obstacle belongs to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ObstacleOwner {
+    Object(usize),
+    Agent(usize),
+}
+
+// one side of a dynamic object (or of an agent), in world coordinates
+#[derive(Clone, Copy)]
+pub struct ObstacleSegment {
+    pub segment: Vector2D,
+    pub owner: ObstacleOwner,
+    pub is_pushable: bool,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DynamicObject {
+    pub id: usize,
+    pub shape: Shape, // shape.anchor is the position of the object
+    pub angle: Angle,
+    pub velocity: Coord, // world units per second
+    pub angular_velocity: f32, // degrees per second
+    pub motion: Motion,
+    pub is_pushable: bool,
+    radius: f32,
+}
+
+impl DynamicObject {
+    pub fn from_descr(id: usize, descr: &DynamicObjectDescription) -> Result<DynamicObject> {
+        if let Motion::Patrol { waypoints, speed, .. } = &descr.motion {
+            if waypoints.is_empty() {
+                return Err(Error::InvalidMotion { reason: "patrol needs at least one waypoint".to_string() });
+            }
+
+            if !speed.is_finite() || *speed <= 0.0 {
+                return Err(Error::InvalidMotion { reason: format!("patrol speed {} should be a positive number", speed) });
+            }
+        }
+
+        let mut shape: Shape = Shape::from_descr(&descr.shape)?;
+        let angle: Angle = Angle::new_deg(descr.angle_deg);
+
+        shape.rotate(angle);
+
+        let radius: f32 = shape.radius;
+
+        return Ok(DynamicObject {
+            id,
+            shape,
+            angle,
+            velocity: descr.velocity,
+            angular_velocity: descr.angular_velocity_deg,
+            motion: descr.motion.clone(),
+            is_pushable: descr.pushable,
+            radius,
+        });
+    }
+
+    pub fn position(&self) -> Coord {
+        return self.shape.anchor;
+    }
+
+    pub fn radius(&self) -> f32 {
+        return self.radius;
+    }
+
+    pub fn shift(&mut self, shift: Coord) {
+        self.shape.shift(shift);
+    }
+
+    pub fn step(&mut self, dt: f32, walls: &WallGrid) {
+        let mut displacement: Coord = Coord::new(0.0, 0.0);
+
+        match &mut self.motion {
+            Motion::Free { damping } => {
+                displacement = self.velocity.new_scaled(dt);
+                self.velocity = self.velocity.new_scaled(f32::max(0.0, 1.0 - *damping * dt));
+            }
+            Motion::Patrol { waypoints, speed, next_waypoint } => {
+                if !waypoints.is_empty() {
+                    let target: Coord = waypoints[*next_waypoint % waypoints.len()];
+                    let to_target: Coord = Coord::new(
+                        target.x() - self.shape.anchor.x(),
+                        target.y() - self.shape.anchor.y(),
+                    );
+                    let distance: f32 = f32::sqrt(to_target.x() * to_target.x() + to_target.y() * to_target.y());
+
+                    if distance <= *speed * dt {
+                        displacement = to_target;
+                        *next_waypoint = (*next_waypoint + 1) % waypoints.len();
+                    } else {
+                        displacement = to_target.new_scaled(*speed * dt / distance);
+                    }
+
+                    self.velocity = displacement.new_scaled(1.0 / dt);
+                }
+            }
+        }
+
+        if self.angular_velocity != 0.0 {
+            let turn: Angle = Angle::new_deg(self.angular_velocity * dt);
+
+            self.shape.rotate(turn);
+            self.angle.turn(turn);
+        }
+
+        // patrolling objects are on rails, the rest is stopped by walls
+        if let Motion::Free { .. } = self.motion {
+            self.slide(displacement, walls);
+            self.resolve_walls(walls);
+        } else {
+            self.shift(displacement);
+        }
+    }
+
+    pub fn slide(&mut self, displacement: Coord, walls: &WallGrid) {
+        // swept move of the bounding circle, stopping at walls and sliding along them
+        let start: Coord = self.position();
+
+        // sliding can turn the move sideways, but never makes it longer
+        let reach: f32 = f32::sqrt(displacement.x() * displacement.x() + displacement.y() * displacement.y());
+        let swept_area: AlignedBox = AlignedBox::around_point(start, reach + self.radius);
+
+        let mut nearby: Vec<Vector2D> = Vec::new();
+
+        for &i in walls.query_box(&swept_area).iter() {
+            nearby.push(*walls.wall(i));
+        }
+
+        let slide: SlideResult = collision::slide_circle(start, self.radius, displacement, &nearby);
+
+        self.shift(Coord::new(slide.center.x() - start.x(), slide.center.y() - start.y()));
+
+        for normal in slide.normals.iter() {
+            let into_wall: f32 = self.velocity.x() * normal.x() + self.velocity.y() * normal.y();
+
+            if into_wall < 0.0 {
+                self.velocity = self.velocity.new_offset(normal.new_scaled(-into_wall));
+            }
+        }
+    }
+
+    pub fn resolve_walls(&mut self, walls: &WallGrid) {
+        // push the object out of walls and drop the part of velocity going into them
+        let push: Coord = walls.circle_penetration(self.position(), self.radius);
+        let push_length: f32 = f32::sqrt(push.x() * push.x() + push.y() * push.y());
+
+        if push_length > 0.0 {
+            self.shift(push);
+
+            let normal: Coord = push.new_scaled(1.0 / push_length);
+            let into_wall: f32 = self.velocity.x() * normal.x() + self.velocity.y() * normal.y();
+
+            if into_wall < 0.0 {
+                self.velocity = self.velocity.new_offset(normal.new_scaled(-into_wall));
+            }
+        }
+    }
+
+    pub fn segments(&self) -> Vec<ObstacleSegment> {
+        let mut segments: Vec<ObstacleSegment> = Vec::with_capacity(self.shape.elements.len());
+
+        for i in 0..self.shape.elements.len() {
+            segments.push(ObstacleSegment {
+                segment: self.shape.elements[i].new_shifted(self.shape.anchor),
+                owner: ObstacleOwner::Object(self.id),
+                is_pushable: self.is_pushable,
+            });
+        }
+
+        return segments;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common_structs::RGBAColor, linear_texture::LinearTexture};
+
+    fn patrol(waypoints: Vec<Coord>, speed: f32) -> DynamicObjectDescription {
+        let square: Vec<Coord> = vec![
+            Coord::new(-0.5, -0.5),
+            Coord::new(0.5, -0.5),
+            Coord::new(0.5, 0.5),
+            Coord::new(-0.5, 0.5),
+        ];
+
+        return DynamicObjectDescription {
+            shape: ShapeDescription {
+                name: String::from("box"),
+                anchor: Coord::new(0.0, 0.0),
+                vertices: square,
+                texture: LinearTexture::new_plain(RGBAColor::new()),
+            },
+            velocity: Coord::new(0.0, 0.0),
+            angle_deg: 0.0,
+            angular_velocity_deg: 0.0,
+            motion: Motion::Patrol { waypoints, speed, next_waypoint: 0 },
+            pushable: false,
+        };
+    }
+
+    #[test]
+    fn patrol_needs_waypoints_and_a_positive_speed() {
+        let waypoints: Vec<Coord> = vec![Coord::new(0.0, 0.0), Coord::new(5.0, 0.0)];
+
+        assert!(DynamicObject::from_descr(0, &patrol(waypoints.clone(), 2.0)).is_ok());
+
+        for speed in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let result: Result<DynamicObject> = DynamicObject::from_descr(0, &patrol(waypoints.clone(), speed));
+            assert!(matches!(result, Err(Error::InvalidMotion { .. })), "speed {}", speed);
+        }
+
+        let result: Result<DynamicObject> = DynamicObject::from_descr(0, &patrol(Vec::new(), 2.0));
+        assert!(matches!(result, Err(Error::InvalidMotion { .. })));
+    }
+}
@@ -0,0 +1,382 @@
+// editing static shapes of a world while it runs:
+// placing vertices of a new shape, dragging vertices of existing ones, deleting shapes
+// and choosing their textures, optionally snapped to a grid
+//
+// frontends turn mouse and keys into these calls; every change goes into the world at once
+// (walls and the wall index of every agent are made again), and the shapes can be saved
+// back into a world file through Shape::to_descr
+
+use std::path::Path;
+
+use crate::{
+    common_structs::{Coord, Palette, RGBAColor},
+    error::Result,
+    linear_texture::{LinearTexture, TextureBodyType, TextureEdgeType},
+    loading::save_ron_file,
+    rgba_canvas::RGBACanvas,
+    shape::{Shape, ShapeDescription, WorldSetup},
+    vector_2d::Vector2D,
+    view::TopViewCamera,
+    world::World,
+};
+
+// grid steps to snap to, in world units, 0 for no snapping
+pub const GRID_STEPS: [f32; 4] = [0.0, 0.25, 0.5, 1.0];
+
+// grid points closer than this on the screen are not drawn
+const MIN_GRID_PIXELS: f32 = 8.0;
+
+pub struct WorldEditor {
+    pub new_vertices: Vec<Coord>, // of the shape being placed, in world coordinates
+    pub selected_shape: Option<usize>,
+    dragged: Option<(usize, usize)>, // shape and its vertex
+    texture_index: usize,
+    grid_index: usize,
+}
+
+impl WorldEditor {
+    pub fn new() -> WorldEditor {
+        return WorldEditor {
+            new_vertices: Vec::new(),
+            selected_shape: None,
+            dragged: None,
+            texture_index: 0,
+            grid_index: 0,
+        };
+    }
+
+    pub fn textures() -> Vec<LinearTexture> {
+        // the textures to choose from, the first one is the one of walls in the example world
+        return vec![
+            LinearTexture::new(
+                RGBAColor::new_rgb(32, 32, 32),
+                RGBAColor::new_p(Palette::White),
+                0.2,
+                TextureEdgeType::Lin,
+                RGBAColor::new_rgb(127, 127, 127),
+                0.5,
+                0.0,
+                TextureBodyType::Step,
+                0.1,
+            ),
+            LinearTexture::new(
+                RGBAColor::new_rgb(150, 60, 40),
+                RGBAColor::new_rgb(90, 30, 20),
+                0.1,
+                TextureEdgeType::Lin,
+                RGBAColor::new_rgb(200, 180, 170),
+                0.4,
+                0.0,
+                TextureBodyType::Step,
+                0.1,
+            ),
+            LinearTexture::new(
+                RGBAColor::new_rgb(30, 120, 40),
+                RGBAColor::new_rgb(20, 70, 25),
+                0.15,
+                TextureEdgeType::Quad,
+                RGBAColor::new_rgb(60, 180, 60),
+                0.3,
+                0.0,
+                TextureBodyType::Sin,
+                0.5,
+            ),
+            LinearTexture::new(
+                RGBAColor::new_rgb(220, 200, 40),
+                RGBAColor::new_rgb(220, 200, 40),
+                0.0,
+                TextureEdgeType::None,
+                RGBAColor::new_rgb(30, 30, 30),
+                0.5,
+                0.0,
+                TextureBodyType::Step,
+                0.5,
+            ),
+            LinearTexture::new_plain(RGBAColor::new_rgb(40, 80, 200)),
+        ];
+    }
+
+    pub fn texture(&self) -> LinearTexture {
+        return WorldEditor::textures()[self.texture_index];
+    }
+
+    pub fn next_texture(&mut self, world: &mut World) -> bool {
+        // the texture of new shapes, also given to the selected shape; true if the world changed
+        self.texture_index = (self.texture_index + 1) % WorldEditor::textures().len();
+
+        if let Some(shape_index) = self.selected_shape {
+            let mut descr: ShapeDescription = world.shapes[shape_index].to_descr();
+            descr.texture = self.texture();
+
+            return self.replace(world, shape_index, &descr);
+        }
+
+        return false;
+    }
+
+    pub fn grid_step(&self) -> f32 {
+        return GRID_STEPS[self.grid_index];
+    }
+
+    pub fn next_grid_step(&mut self) {
+        self.grid_index = (self.grid_index + 1) % GRID_STEPS.len();
+    }
+
+    pub fn snap(&self, point: Coord) -> Coord {
+        let step: f32 = self.grid_step();
+
+        if step > 0.0 {
+            return Coord::new((point.x() / step).round() * step, (point.y() / step).round() * step);
+        } else {
+            return point;
+        }
+    }
+
+    pub fn vertex_at(&self, world: &World, point: Coord, tolerance: f32) -> Option<(usize, usize)> {
+        // the closest vertex of any shape within tolerance (world units) from the point
+        let mut closest: Option<(usize, usize)> = None;
+        let mut closest_distance: f32 = tolerance;
+
+        for j in 0..world.shapes.len() {
+            let anchor: Coord = world.shapes[j].anchor;
+
+            for i in 0..world.shapes[j].elements.len() {
+                let vertex: Coord = world.shapes[j].elements[i].base.new_offset(anchor);
+                let distance: f32 = distance(vertex, point);
+
+                if distance <= closest_distance {
+                    closest = Some((j, i));
+                    closest_distance = distance;
+                }
+            }
+        }
+
+        return closest;
+    }
+
+    pub fn shape_at(&self, world: &World, point: Coord, tolerance: f32) -> Option<usize> {
+        // the shape with a wall closest to the point, within tolerance
+        let mut closest: Option<usize> = None;
+        let mut closest_distance: f32 = tolerance;
+
+        for i in 0..world.walls().len() {
+            let distance: f32 = distance(world.walls()[i].closest_point(point), point);
+
+            if distance <= closest_distance {
+                closest = world.shape_of_wall(i);
+                closest_distance = distance;
+            }
+        }
+
+        return closest;
+    }
+
+    pub fn press(&mut self, world: &World, point: Coord, tolerance: f32) {
+        // on a vertex: starts dragging it, anywhere else: next vertex of the new shape
+        if self.new_vertices.is_empty() {
+            if let Some(vertex) = self.vertex_at(world, point, tolerance) {
+                self.dragged = Some(vertex);
+                self.selected_shape = Some(vertex.0);
+                return;
+            }
+        }
+
+        let snapped: Coord = self.snap(point);
+
+        if let Some(last) = self.new_vertices.last() {
+            if last.x() == snapped.x() && last.y() == snapped.y() {
+                return;
+            }
+        }
+
+        self.new_vertices.push(snapped);
+    }
+
+    pub fn drag(&mut self, world: &mut World, point: Coord) -> bool {
+        // moves the dragged vertex, true if the world changed
+        // (moves that would put the vertex onto its neighbour are not done)
+        match self.dragged {
+            Some((shape_index, vertex_index)) => {
+                let mut descr: ShapeDescription = world.shapes[shape_index].to_descr();
+                let snapped: Coord = self.snap(point);
+
+                descr.vertices[vertex_index] = Coord::new(snapped.x() - descr.anchor.x(), snapped.y() - descr.anchor.y());
+
+                return self.replace(world, shape_index, &descr);
+            }
+            None => {
+                return false;
+            }
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.dragged = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        return self.dragged.is_some();
+    }
+
+    pub fn select(&mut self, world: &World, point: Coord, tolerance: f32) {
+        // picks the shape under the point, and its texture if it is one of the editor's
+        self.selected_shape = self.shape_at(world, point, tolerance);
+
+        if let Some(shape_index) = self.selected_shape {
+            let texture: LinearTexture = world.shapes[shape_index].to_descr().texture;
+            let textures: Vec<LinearTexture> = WorldEditor::textures();
+
+            for i in 0..textures.len() {
+                if textures[i] == texture {
+                    self.texture_index = i;
+                }
+            }
+        }
+    }
+
+    pub fn undo_vertex(&mut self) {
+        self.new_vertices.pop();
+    }
+
+    pub fn finish_shape(&mut self, world: &mut World) -> Result<bool> {
+        // closes the new shape and adds it to the world, true if there was one to add
+        if self.new_vertices.len() < 2 {
+            return Ok(false);
+        }
+
+        // vertices are kept relative to the first one, which becomes the anchor
+        let anchor: Coord = self.new_vertices[0];
+        let descr: ShapeDescription = ShapeDescription {
+            name: format!("Shape {}", world.shapes.len() + 1),
+            anchor,
+            vertices: self.new_vertices
+                .iter()
+                .map(|vertex| Coord::new(vertex.x() - anchor.x(), vertex.y() - anchor.y()))
+                .collect(),
+            texture: self.texture(),
+        };
+
+        let shape: Shape = Shape::from_descr(&descr)?;
+
+        self.selected_shape = Some(world.add_shape(shape));
+        self.new_vertices.clear();
+
+        return Ok(true);
+    }
+
+    pub fn delete_selected(&mut self, world: &mut World) -> bool {
+        match self.selected_shape.take() {
+            Some(shape_index) => {
+                self.dragged = None;
+                return world.remove_shape(shape_index).is_some();
+            }
+            None => {
+                return false;
+            }
+        }
+    }
+
+    pub fn world_setup(world: &World, setup: &WorldSetup) -> WorldSetup {
+        // the setup with the shapes of the world as they are now
+        let mut edited: WorldSetup = setup.clone();
+
+        edited.world_shapes = world.shapes.iter().map(|shape| shape.to_descr()).collect();
+
+        return edited;
+    }
+
+    pub fn save(world: &World, setup: &WorldSetup, path: &Path) -> Result<()> {
+        return save_ron_file(path, &WorldEditor::world_setup(world, setup));
+    }
+
+    pub fn draw(&self, world: &World, canvas: &mut RGBACanvas, camera: &TopViewCamera) {
+        // on top of the top view: the grid, vertices of all shapes (those of the selected one bigger),
+        // and the new shape being placed
+        let width: i32 = canvas.width;
+        let height: i32 = canvas.height;
+        let step: f32 = self.grid_step();
+
+        if step > 0.0 && step * camera.scale >= MIN_GRID_PIXELS {
+            // grid points in the part of the world seen, whichever way the view is turned
+            let corners: [Coord; 4] = [
+                camera.point(Coord::new(0.0, 0.0), width, height),
+                camera.point(Coord::new(width as f32, 0.0), width, height),
+                camera.point(Coord::new(0.0, height as f32), width, height),
+                camera.point(Coord::new(width as f32, height as f32), width, height),
+            ];
+            let min: Coord = Coord::new(
+                corners.iter().map(|corner| corner.x()).fold(f32::MAX, f32::min),
+                corners.iter().map(|corner| corner.y()).fold(f32::MAX, f32::min),
+            );
+            let max: Coord = Coord::new(
+                corners.iter().map(|corner| corner.x()).fold(f32::MIN, f32::max),
+                corners.iter().map(|corner| corner.y()).fold(f32::MIN, f32::max),
+            );
+            let grid_color: RGBAColor = RGBAColor::new_rgb(70, 70, 70);
+
+            let mut y: f32 = (min.y() / step).floor() * step;
+
+            while y <= max.y() {
+                let mut x: f32 = (min.x() / step).floor() * step;
+
+                while x <= max.x() {
+                    let pixel: Coord = camera.pixel(Coord::new(x, y), width, height);
+                    canvas.put_pixel(pixel.get_x_i(), pixel.get_y_i(), grid_color);
+
+                    x += step;
+                }
+
+                y += step;
+            }
+        }
+
+        for j in 0..world.shapes.len() {
+            let is_selected: bool = self.selected_shape == Some(j);
+            let color: RGBAColor = if is_selected {RGBAColor::new_p(Palette::Yellow)} else {RGBAColor::new_p(Palette::LightGrey)};
+            let size: i32 = if is_selected {3} else {2};
+
+            for i in 0..world.shapes[j].elements.len() {
+                let vertex: Coord = world.shapes[j].elements[i].base.new_offset(world.shapes[j].anchor);
+                let pixel: Coord = camera.pixel(vertex, width, height);
+                let vertex_color: RGBAColor = if self.dragged == Some((j, i)) {RGBAColor::new_p(Palette::Red)} else {color};
+
+                canvas.put_square(pixel.get_x_i(), pixel.get_y_i(), size, vertex_color);
+            }
+        }
+
+        let offset: Coord = camera.pixel_offset(width, height);
+
+        for i in 0..self.new_vertices.len() {
+            if i > 0 {
+                let previous: Coord = self.new_vertices[i - 1];
+                let side: Vector2D = Vector2D::new(
+                    previous,
+                    Coord::new(self.new_vertices[i].x() - previous.x(), self.new_vertices[i].y() - previous.y()),
+                    self.texture(),
+                );
+
+                camera.view_vector(&side).draw(canvas, offset, camera.scale, 2.0);
+            }
+
+            let pixel: Coord = camera.pixel(self.new_vertices[i], width, height);
+            canvas.put_square(pixel.get_x_i(), pixel.get_y_i(), 2, RGBAColor::new_p(Palette::Cyan));
+        }
+    }
+
+    fn replace(&mut self, world: &mut World, shape_index: usize, descr: &ShapeDescription) -> bool {
+        // broken shapes (e.g. two vertices at the same point) leave the world as it was
+        match Shape::from_descr(descr) {
+            Ok(shape) => {
+                world.replace_shape(shape_index, shape);
+                return true;
+            }
+            Err(_) => {
+                return false;
+            }
+        }
+    }
+}
+
+fn distance(a: Coord, b: Coord) -> f32 {
+    return f32::sqrt((a.x() - b.x()) * (a.x() - b.x()) + (a.y() - b.y()) * (a.y() - b.y()));
+}
@@ -5,6 +5,7 @@ use crate::{
         Coord,
         Angle,
         RGBAColor},
+    error::{Error, Result},
     rgba_canvas::RGBACanvas,
 };
 
@@ -19,11 +20,15 @@ pub struct Ellipse {
 }
 
 impl Ellipse {
-    pub fn new(center: Coord, hor_axis: f32, ver_axis: f32, color: RGBAColor) -> Ellipse {
-        if hor_axis <= 0.0 { panic!("Horisontal axis for Ellipse should be positive number!"); }
-        if ver_axis <= 0.0 { panic!("Vertical axis for Ellipse should be positive number!"); }
+    pub fn new(center: Coord, hor_axis: f32, ver_axis: f32, color: RGBAColor) -> Result<Ellipse> {
+        if !(hor_axis > 0.0 && ver_axis > 0.0) {
+            return Err(Error::DegenerateShape {
+                name: String::from("Ellipse"),
+                reason: format!("both axes should be positive numbers, got {} and {}", hor_axis, ver_axis),
+            });
+        }
 
-        return Ellipse{center, pivot: center, hor_axis, ver_axis, angle: Angle::new(), color};
+        return Ok(Ellipse{center, pivot: center, hor_axis, ver_axis, angle: Angle::new(), color});
     }
 
     pub fn draw_ellipse_raster(&self, canvas: &mut RGBACanvas, is_filled: bool, edge_width: f32) {
@@ -0,0 +1,101 @@
+// errors of the whole crate:
+// bad geometry, motions, agents, canvas sizes and recording options, and problems with reading world files and images or writing recordings
+
+use std::{fmt, path::PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidCanvasSize {
+        width: f32,
+        height: f32,
+    },
+    DegenerateShape {
+        name: String,
+        reason: String,
+    },
+    TooFewVertices {
+        name: String,
+        count: usize,
+        required: usize,
+    },
+    InvalidMotion {
+        reason: String,
+    },
+    InvalidAgent {
+        agent: usize, // the first agent is 0, other_agents follow
+        reason: String,
+    },
+    InvalidRecording {
+        reason: String,
+    },
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Write {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    RonParse {
+        path: PathBuf,
+        line: usize,
+        col: usize,
+        message: String,
+    },
+    InvalidImage {
+        path: Option<PathBuf>, // none when decoded from bytes in memory
+        reason: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidCanvasSize { width, height } => {
+                write!(f, "invalid canvas size {}x{}, both dimensions should be at least 1", width, height)
+            }
+            Error::DegenerateShape { name, reason } => {
+                write!(f, "shape \"{}\" is degenerate: {}", name, reason)
+            }
+            Error::TooFewVertices { name, count, required } => {
+                write!(f, "shape \"{}\" has {} vertices, at least {} are needed", name, count, required)
+            }
+            Error::InvalidMotion { reason } => {
+                write!(f, "invalid motion: {}", reason)
+            }
+            Error::InvalidAgent { agent, reason } => {
+                write!(f, "agent {}: {}", agent, reason)
+            }
+            Error::InvalidRecording { reason } => {
+                write!(f, "can't record: {}", reason)
+            }
+            Error::Io { path, error } => {
+                write!(f, "couldn't read {}: {}", path.display(), error)
+            }
+            Error::Write { path, error } => {
+                write!(f, "couldn't write {}: {}", path.display(), error)
+            }
+            Error::RonParse { path, line, col, message } => {
+                write!(f, "{}:{}:{}: {}", path.display(), line, col, message)
+            }
+            Error::InvalidImage { path: Some(path), reason } => {
+                write!(f, "couldn't decode image {}: {}", path.display(), reason)
+            }
+            Error::InvalidImage { path: None, reason } => {
+                write!(f, "couldn't decode image: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            Error::Write { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,802 @@
+// FLTK frontend: window, event loop and blitting of rendered canvases
+
+use fltk::{
+    app::{self, App, MouseButton, MouseWheel},
+    enums::{self, ColorDepth, FrameType, Event, Cursor},
+    image::RgbImage,
+    prelude::*,
+    *,
+};
+
+use std::path::{Path, PathBuf};
+
+use moving_agent::{
+    agent::{Action, Agent},
+    common_structs::{Angle, Coord},
+    controller::{Controller, KeyboardController},
+    editor::WorldEditor,
+    image_io,
+    recording::{Recorder, RecordingOptions},
+    replay::ActionLog,
+    rgba_canvas::RGBACanvas,
+    shape::WorldSetup,
+    backdrop::TopViewBackdrop,
+    simulation::{Simulation, DELTA_T},
+    snapshot::Snapshot,
+    view::{self, SplitLayout, TopViewCamera, ViewMode},
+};
+
+use crate::cli::ViewOptions;
+
+const WIND_LABEL: &str = "Moving Agent";
+// const MENU_HEIGHT: i32 = 32;
+const MENU_HEIGHT: i32 = 0;
+// how close to a vertex or a wall a click has to be to hit it in the editor
+const PICK_PIXELS: f32 = 6.0;
+// change of the top view scale by one step of the mouse wheel
+const ZOOM_STEP: f32 = 1.2;
+
+#[derive(Clone)]
+enum Message {
+    Quit,
+    MouseDown(i32, i32, MouseButton),
+    MouseDrag(i32, i32),
+    MouseMove(i32),
+    MouseReleased(i32, i32, MouseButton),
+    Zoom(i32, i32, i32), // at the mouse, steps in (positive) or out
+    Tick,
+    KeyPress(char),
+    KeyRelease(char),
+    WindowResize,
+    ToggleFullScreen,
+}
+
+pub fn run(mut simulation: Simulation, options: &ViewOptions, mut action_log: Option<ActionLog>, setup: Option<WorldSetup>) {
+    // setup is the world file the simulation was made from, the editor saves its shapes into a copy of it
+    let mut view_mode: ViewMode = options.view_mode;
+    // the top view follows the controlled agent until it is dragged away, and can turn with it
+    let mut camera: TopViewCamera = TopViewCamera::new(simulation.agent().center, options.scale);
+    camera.render_mode = options.top_view_mode;
+    let mut backdrop: TopViewBackdrop = TopViewBackdrop::new();
+    let mut follows_agent: bool = true;
+    let mut rotates_with_agent: bool = false;
+    let mut pan_from: Option<(i32, i32)> = None; // last mouse position while the view is dragged
+    let mut shows_view_rays: bool = false; // in the top view, the split view always shows them
+    let wind_width: i32 = options.width;
+    let wind_height: i32 = options.height;
+    let mut is_full_screen: bool = false;
+
+    let application: App = app::App::default();
+
+    let (s, r) = app::channel();
+
+    let mut mouse_x: i32 = -1;
+    let mut mouse_dx: i32 = 0;
+
+    // in a replay agents only repeat the log, keyboard and mouse don't move them
+    let is_replay: bool = options.replay.is_some();
+
+    // the controlled agent listens to the keyboard, its own controller waits here until
+    // the keyboard is handed to another agent
+    let mut displaced_controller: Option<Box<dyn Controller>> = if is_replay {
+        None
+    } else {
+        simulation.set_controller(simulation.controlled_agent, Some(Box::new(KeyboardController::new())))
+    };
+
+    let mut wind = window::Window::new(0, 0, wind_width, wind_height, WIND_LABEL);
+
+    let mut menu = menu::SysMenuBar::default().with_size(wind.width(), MENU_HEIGHT);
+    menu.set_frame(enums::FrameType::FlatBox);
+    menu.set_color(enums::Color::Light2);
+
+    menu.add_emit(
+        "&File/Quit\t",
+        enums::Shortcut::Ctrl | 'q',
+        menu::MenuFlag::Normal,
+        s.clone(),
+        Message::Quit,
+    );
+
+    menu.add_emit(
+        "&View/FullScreen\t",
+        enums::Shortcut::Ctrl | 'z',
+        menu::MenuFlag::Normal,
+        s.clone(),
+        Message::ToggleFullScreen,
+    );
+
+    let mut top_view_frame = frame::Frame::default()
+        .with_pos(
+            0,
+            MENU_HEIGHT,
+        )
+        .with_size(wind_width, wind_height - MENU_HEIGHT);
+
+    wind.make_resizable(true);
+    wind.end();
+    wind.show();
+
+    // world editor, in the top view while 'm' is on
+    let mut editor: Option<WorldEditor> = None;
+
+    // 'l' goes back to the snapshot saved last with 'o', or to the one the window started from
+    let mut last_snapshot: Option<PathBuf> = options.restore.clone();
+
+    // frames keep the size the window had when recording started
+    let mut recorder: Option<Recorder> = None;
+
+    if let Some(directory) = &options.record_directory {
+        start_recording(&mut recorder, options, directory.clone(), &top_view_frame, &simulation);
+    }
+
+    // intercept keyboard events on the window
+    let key_interceptor_sender =s.clone();
+    let mut chars_vec: Vec<char> = Vec::new();
+    wind.handle(move |_, event| match event {
+        Event::Resize => {
+            // println!("Resize event: {:?}", app::event());
+
+            key_interceptor_sender.send(Message::WindowResize);
+            // key_interceptor_sender.send(Message::Tick);
+
+            false
+        }
+        Event::KeyDown => {
+            match app::event_key().to_char() {
+                Some(char) => {
+                    // println!("Key pressed: {:?}", char);
+                    key_interceptor_sender.send(Message::KeyPress(char));
+                    chars_vec.push(char);
+                }
+                None => {
+                    // nothing
+                }
+            };
+            false
+        }
+        Event::KeyUp => {
+            match app::event_key().to_char() {
+                Some(char) => {
+                    // println!("Key released: {:?}", char);
+                    key_interceptor_sender.send(Message::KeyRelease(char));
+                }
+                None => {
+                    // nothing
+                }
+            };
+            false
+        }
+        Event::Move => {
+            let current_x = app::event_x();
+
+            key_interceptor_sender.send(Message::MouseMove(current_x));
+
+            true
+        }
+
+        _ => false,
+    });
+
+    let callback_sender = s.clone();
+    
+    let callback = move |handle| {
+        callback_sender.send(Message::Tick);
+        
+        app::repeat_timeout3(DELTA_T, handle);
+    };
+
+    app::add_timeout3(DELTA_T, callback);
+    
+
+    let top_view_frame_handle_sender = s.clone();
+    top_view_frame.handle(move |_, event: Event| {
+        match event {
+            Event::Push => {
+                let x = app::event_x();
+                let y = app::event_y() - MENU_HEIGHT;
+                let button = app::event_mouse_button();
+                top_view_frame_handle_sender.send(Message::MouseDown(x, y, button));
+                true
+            }
+            Event::Drag => {
+                let x = app::event_x();
+                let y = app::event_y() - MENU_HEIGHT;
+                if x >= 0 && x < wind_width && y >= 0 && y < wind_height - MENU_HEIGHT {
+                    top_view_frame_handle_sender.send(Message::MouseDrag(x, y));
+                }
+                true
+            }
+            Event::Released => {
+                let x = app::event_x();
+                let y = app::event_y() - MENU_HEIGHT;
+                let button = app::event_mouse_button();
+                top_view_frame_handle_sender.send(Message::MouseReleased(x, y, button));
+                true
+            }
+            Event::MouseWheel => {
+                let x = app::event_x();
+                let y = app::event_y() - MENU_HEIGHT;
+
+                // fltk calls the wheel turned away from the user "Down"
+                match app::event_dy() {
+                    MouseWheel::Down => top_view_frame_handle_sender.send(Message::Zoom(x, y, 1)),
+                    MouseWheel::Up => top_view_frame_handle_sender.send(Message::Zoom(x, y, -1)),
+                    _ => {}
+                }
+                true
+            }
+            _ => false,
+        }
+    });
+
+    while application.wait() {
+        if let Some(msg) = r.recv() {
+            match msg {
+                Message::Quit => {
+                    println!("quitting the app...");
+                    fltk::app::quit();
+                }
+                Message::Tick => {
+                    simulation.step(&[]);
+
+                    if let Some(active_recorder) = recorder.as_mut() {
+                        if let Err(error) = active_recorder.record(&simulation) {
+                            eprintln!("recording stopped: {}", error);
+                            recorder = None;
+                        }
+                    }
+
+                    if view_mode.shows_top_view() {
+                        if follows_agent {
+                            camera.center = simulation.agent().center;
+                        }
+
+                        if rotates_with_agent {
+                            camera.angle = TopViewCamera::behind_agent(simulation.agent(), camera.scale).angle;
+                        }
+
+                        camera.render_mode = camera.render_mode.seen_by(simulation.controlled_agent);
+                    }
+
+                    match view_mode {
+                        ViewMode::FirstPerson
+                        | ViewMode::Depth
+                        | ViewMode::Parallel(_)
+                        | ViewMode::CrossEye(_)
+                        | ViewMode::Anaglyph(_) => draw_fisrt_person_view(
+                            simulation.agent_mut(),
+                            view_mode,
+                            &mut top_view_frame,
+                        ),
+                        ViewMode::Top => {
+                            draw_top_view(&mut simulation, editor.as_ref(), &camera, shows_view_rays, &mut backdrop, &mut top_view_frame);
+                        }
+                        ViewMode::Split(layout) => {
+                            draw_split_view(&mut simulation, &camera, &layout, &mut backdrop, &mut top_view_frame);
+                        }
+                    }
+                }
+                Message::MouseDown(x, y, button) => {
+                    match editor.as_mut() {
+                        Some(world_editor) if view_mode == ViewMode::Top && button != MouseButton::Middle => {
+                            // left button places or grabs vertices, right button selects a shape
+                            let point: Coord = frame_to_world(&camera, &top_view_frame, x, y);
+
+                            if button == MouseButton::Right {
+                                world_editor.select(&simulation.world, point, PICK_PIXELS / camera.scale);
+                            } else {
+                                world_editor.press(&simulation.world, point, PICK_PIXELS / camera.scale);
+                            }
+
+                            simulation.world.is_updated = true;
+                        }
+                        _ => {
+                            // dragging moves the top view (with the middle button while editing)
+                            let (area_x, area_y, area_width, area_height) = top_view_area(view_mode, &top_view_frame);
+
+                            if x >= area_x && x < area_x + area_width && y >= area_y && y < area_y + area_height {
+                                pan_from = Some((x, y));
+                            }
+                        }
+                    }
+                }
+                Message::MouseDrag(x, y) => {
+                    match (editor.as_mut(), pan_from) {
+                        (Some(world_editor), _) if world_editor.is_dragging() => {
+                            let point: Coord = frame_to_world(&camera, &top_view_frame, x, y);
+
+                            if world_editor.drag(&mut simulation.world, point) {
+                                stop_action_log(&mut action_log);
+                            }
+                        }
+                        (_, Some((from_x, from_y))) if view_mode.shows_top_view() => {
+                            camera.pan((x - from_x) as f32, (y - from_y) as f32);
+                            pan_from = Some((x, y));
+
+                            if follows_agent {
+                                follows_agent = false;
+                                println!("the view doesn't follow the agent any more, 'u' follows it again");
+                            }
+
+                            simulation.world.is_updated = true;
+                        }
+                        _ => {}
+                    }
+                }
+                Message::MouseReleased(_, _, _) => {
+                    pan_from = None;
+
+                    if let Some(world_editor) = editor.as_mut() {
+                        world_editor.release();
+                        simulation.world.is_updated = true;
+                    }
+                }
+                Message::Zoom(x, y, steps) => {
+                    // around the mouse, or around the agent while the view follows it
+                    let (area_x, area_y, area_width, area_height) = top_view_area(view_mode, &top_view_frame);
+                    let is_over_top_view: bool = x >= area_x && x < area_x + area_width && y >= area_y && y < area_y + area_height;
+
+                    if view_mode.shows_top_view() && is_over_top_view {
+                        let pixel: Coord = if follows_agent {
+                            camera.pixel(camera.center, area_width, area_height)
+                        } else {
+                            Coord::new_i(x - area_x, y - area_y)
+                        };
+
+                        camera.zoom_at(pixel, ZOOM_STEP.powi(steps), area_width, area_height);
+                        simulation.world.is_updated = true;
+                    }
+                }
+                Message::MouseMove(current_x) => {
+                    if mouse_x != -1 {
+                        mouse_dx = current_x - mouse_x;
+                    }
+                    mouse_x = current_x;
+
+                    if mouse_dx != 0 && !is_replay && editor.is_none() {
+                        simulation.apply_action(Action::Turn((mouse_dx as f32) / 3.0));
+                    }
+                    
+                }
+                Message::KeyPress(key_char) if editor.is_some() && is_editor_key(key_char) => {
+                    let world_editor: &mut WorldEditor = editor.as_mut().unwrap();
+
+                    if edit_with_key(world_editor, key_char, &mut simulation, setup.as_ref()) {
+                        stop_action_log(&mut action_log);
+                    }
+
+                    simulation.world.is_updated = true;
+                }
+                Message::KeyPress(key_char) => {
+                    match key_char {
+                        'm' => {
+                            // edit mode, always in the top view
+                            if editor.is_some() {
+                                editor = None;
+                                println!("edit mode off");
+                            } else {
+                                editor = Some(WorldEditor::new());
+                                view_mode = ViewMode::Top;
+                                println!(
+                                    "edit mode: left click places vertices or drags them, 'c' closes the shape, 'z' takes the last vertex back, \
+                                    right click selects a shape, 'x' deletes it, 't' changes the texture, 'g' the grid, 'f' saves the world"
+                                );
+                            }
+
+                            simulation.world.is_updated = true;
+                        }
+                        'u' => {
+                            follows_agent = !follows_agent;
+                            simulation.world.is_updated = true;
+                        }
+                        'y' => {
+                            // the agent looks up in the view
+                            rotates_with_agent = !rotates_with_agent;
+
+                            if !rotates_with_agent {
+                                camera.angle = Angle::new();
+                            }
+
+                            simulation.world.is_updated = true;
+                        }
+                        'h' => {
+                            // the whole world in the window
+                            let (_, _, area_width, area_height) = top_view_area(view_mode, &top_view_frame);
+
+                            follows_agent = false;
+                            camera.fit(&simulation.world.outline_points(), area_width, area_height);
+                            simulation.world.is_updated = true;
+                        }
+                        '=' | '+' | '-' => {
+                            let steps: i32 = if key_char == '-' {-1} else {1};
+                            let (_, _, area_width, area_height) = top_view_area(view_mode, &top_view_frame);
+                            let middle: Coord = Coord::new_i(area_width / 2, area_height / 2);
+
+                            camera.zoom_at(middle, ZOOM_STEP.powi(steps), area_width, area_height);
+                            simulation.world.is_updated = true;
+                        }
+                        'b' => {
+                            camera.render_mode = camera.render_mode.next();
+                            println!("top view: {}", camera.render_mode.name());
+                            simulation.world.is_updated = true;
+                        }
+                        'k' => {
+                            shows_view_rays = !shows_view_rays;
+                            simulation.world.is_updated = true;
+                        }
+                        'v' => {
+                            view_mode = options.configured_view(view_mode.next());
+                            simulation.world.is_updated = true;
+                            simulation.agent_mut().is_updated = true;
+                        }
+                        'n' => {
+                            // take control of the next agent
+                            let next: usize = (simulation.controlled_agent + 1) % simulation.agent_count();
+                            select_agent(&mut simulation, next, is_replay, &mut displaced_controller);
+                        }
+                        '1'..='9' => {
+                            // take control of the agent by its number
+                            select_agent(&mut simulation, key_char as usize - '1' as usize, is_replay, &mut displaced_controller);
+                        }
+                        'p' => {
+                            save_screenshot(&simulation, view_mode, &camera, top_view_frame.width(), top_view_frame.height());
+                        }
+                        'o' => {
+                            if let Some(path) = save_snapshot(&mut simulation, is_replay, &mut displaced_controller) {
+                                last_snapshot = Some(path);
+                            }
+                        }
+                        'l' => {
+                            if let Some(path) = &last_snapshot {
+                                if restore_snapshot(&mut simulation, path, is_replay, &mut displaced_controller) {
+                                    stop_action_log(&mut action_log);
+
+                                    // shapes the editor pointed at may be gone
+                                    if editor.is_some() {
+                                        editor = Some(WorldEditor::new());
+                                    }
+                                }
+                            } else {
+                                println!("no snapshot to go back to, 'o' saves one");
+                            }
+                        }
+                        'r' => {
+                            if recorder.is_some() {
+                                stop_recording(&mut recorder);
+                            } else {
+                                // the views being looked at, unless the command line says which
+                                let mut current: ViewOptions = options.clone();
+                                current.view_mode = view_mode;
+
+                                let directory: PathBuf = options.record_directory.clone().unwrap_or(next_free_path("recording-", ""));
+                                start_recording(&mut recorder, &current, directory, &top_view_frame, &simulation);
+                            }
+                        }
+                        _ => {
+                            // movement keys are turned into actions by the keyboard controller
+                            simulation.key_press(key_char);
+                        }
+                    }
+                }
+                Message::KeyRelease(key_char) => {
+                    simulation.key_release(key_char);
+                }
+                Message::WindowResize => {
+                    simulation.world.is_updated = true;
+                    simulation.agent_mut().is_updated = true;
+                }
+                Message::ToggleFullScreen => {
+                    is_full_screen = !is_full_screen;
+
+                    wind.fullscreen(is_full_screen);
+
+                    if is_full_screen {
+                        wind.set_cursor(Cursor::None);
+                    } else {
+                        wind.set_cursor(Cursor::Default);
+                    }
+                    
+                }
+            };
+        }
+    }
+
+    stop_recording(&mut recorder);
+
+    if let (Some(action_log), Some(log_path)) = (action_log.as_mut(), &options.log_actions) {
+        action_log.finish(&mut simulation);
+
+        match action_log.save(log_path) {
+            Ok(()) => println!("logged {} actions of {} ticks into {}", action_log.actions.len(), action_log.ticks, log_path.display()),
+            Err(error) => eprintln!("couldn't save the action log: {}", error),
+        }
+    }
+
+    application.run().unwrap();
+}
+
+fn start_recording(recorder: &mut Option<Recorder>, options: &ViewOptions, directory: PathBuf, frame: &frame::Frame, simulation: &Simulation) {
+    let recording_options: RecordingOptions = options.recording_options(directory, frame.width(), frame.height());
+
+    match Recorder::start(recording_options) {
+        Ok(mut started) => {
+            println!("recording into {}", started.options().directory.display());
+
+            match started.record(simulation) {
+                Ok(()) => *recorder = Some(started),
+                Err(error) => eprintln!("couldn't record: {}", error),
+            }
+        }
+        Err(error) => eprintln!("couldn't start recording: {}", error),
+    }
+}
+
+fn stop_recording(recorder: &mut Option<Recorder>) {
+    if let Some(finished) = recorder.take() {
+        let directory: PathBuf = finished.options().directory.clone();
+        let frame_count: u64 = finished.frame_count();
+
+        match finished.finish() {
+            Ok(()) => println!("recorded {} frames into {}", frame_count, directory.display()),
+            Err(error) => eprintln!("couldn't finish recording: {}", error),
+        }
+    }
+}
+
+fn next_free_path(prefix: &str, suffix: &str) -> PathBuf {
+    // prefix-001suffix, or the first number after it that isn't taken yet
+    let mut number: u32 = 1;
+    let mut path: PathBuf = PathBuf::from(format!("{}{:03}{}", prefix, number, suffix));
+
+    while path.exists() {
+        number += 1;
+        path = PathBuf::from(format!("{}{:03}{}", prefix, number, suffix));
+    }
+
+    return path;
+}
+
+
+fn stop_action_log(action_log: &mut Option<ActionLog>) {
+    // after the world was changed by other means than actions, a log couldn't repeat the run
+    if action_log.take().is_some() {
+        println!("action log stopped, the run doesn't go on from the logged world any more");
+    }
+}
+
+fn top_view_area(view_mode: ViewMode, frame: &frame::Frame) -> (i32, i32, i32, i32) {
+    // x, y, width and height of the top view in the frame
+    match view_mode {
+        ViewMode::Split(layout) => return layout.top_view_area(frame.width(), frame.height()),
+        _ => return (0, 0, frame.width(), frame.height()),
+    }
+}
+
+fn frame_to_world(camera: &TopViewCamera, frame: &frame::Frame, x: i32, y: i32) -> Coord {
+    return camera.point(Coord::new_i(x, y), frame.width(), frame.height());
+}
+
+fn is_editor_key(key: char) -> bool {
+    return matches!(key, 'c' | 'z' | 'x' | 't' | 'g' | 'f');
+}
+
+fn edit_with_key(editor: &mut WorldEditor, key: char, simulation: &mut Simulation, setup: Option<&WorldSetup>) -> bool {
+    // true if the world has been changed
+    match key {
+        'c' => match editor.finish_shape(&mut simulation.world) {
+            Ok(is_added) => return is_added,
+            Err(error) => eprintln!("couldn't add the shape: {}", error),
+        },
+        'z' => editor.undo_vertex(),
+        'x' => return editor.delete_selected(&mut simulation.world),
+        't' => return editor.next_texture(&mut simulation.world),
+        'g' => {
+            editor.next_grid_step();
+
+            match editor.grid_step() {
+                step if step > 0.0 => println!("snapping to a grid of {}", step),
+                _ => println!("not snapping to a grid"),
+            }
+        }
+        'f' => match setup {
+            Some(setup) => {
+                // into a new file, the original world file stays as it was
+                let path: PathBuf = next_free_path("world-", ".ron");
+
+                match WorldEditor::save(&simulation.world, setup, &path) {
+                    Ok(()) => println!("world saved to {}", path.display()),
+                    Err(error) => eprintln!("couldn't save the world: {}", error),
+                }
+            }
+            None => println!("the simulation wasn't started from a world file, 'o' saves it as a snapshot"),
+        },
+        _ => {}
+    }
+
+    return false;
+}
+
+fn save_snapshot(simulation: &mut Simulation, is_replay: bool, displaced_controller: &mut Option<Box<dyn Controller>>) -> Option<PathBuf> {
+    // saves into the first free snapshot-NNN.ron;
+    // the controlled agent's own controller is saved, not the keyboard lent to it
+    let controlled_agent: usize = simulation.controlled_agent;
+
+    let snapshot: Snapshot = if is_replay {
+        simulation.snapshot()
+    } else {
+        let keyboard: Option<Box<dyn Controller>> = simulation.set_controller(controlled_agent, displaced_controller.take());
+        let snapshot: Snapshot = simulation.snapshot();
+        *displaced_controller = simulation.set_controller(controlled_agent, keyboard);
+
+        snapshot
+    };
+
+    let path: PathBuf = next_free_path("snapshot-", ".ron");
+
+    match snapshot.save(&path) {
+        Ok(()) => {
+            println!("saved tick {} into {}", snapshot.tick, path.display());
+            return Some(path);
+        }
+        Err(error) => {
+            eprintln!("couldn't save a snapshot: {}", error);
+            return None;
+        }
+    }
+}
+
+fn restore_snapshot(simulation: &mut Simulation, path: &Path, is_replay: bool, displaced_controller: &mut Option<Box<dyn Controller>>) -> bool {
+    // the keyboard goes to the agent that was controlled when the snapshot was saved
+    match Snapshot::load(path) {
+        Ok(snapshot) => {
+            simulation.restore(snapshot);
+
+            if !is_replay {
+                *displaced_controller = simulation.set_controller(
+                    simulation.controlled_agent,
+                    Some(Box::new(KeyboardController::new())),
+                );
+            }
+
+            println!("went back to tick {} from {}", simulation.tick, path.display());
+            return true;
+        }
+        Err(error) => {
+            eprintln!("couldn't restore a snapshot: {}", error);
+            return false;
+        }
+    }
+}
+
+fn select_agent(simulation: &mut Simulation, agent_id: usize, is_replay: bool, displaced_controller: &mut Option<Box<dyn Controller>>) {
+    // in a replay the view only follows another agent, its controller stays in place
+    if is_replay {
+        if simulation.select_agent(agent_id) {
+            simulation.world.is_updated = true;
+        }
+    } else {
+        hand_keyboard_to(simulation, agent_id, displaced_controller);
+    }
+}
+
+fn hand_keyboard_to(simulation: &mut Simulation, agent_id: usize, displaced_controller: &mut Option<Box<dyn Controller>>) {
+    // the previously controlled agent gets its own controller back,
+    // the newly selected one gives its controller away for the keyboard
+    if agent_id >= simulation.agent_count() || agent_id == simulation.controlled_agent {
+        return;
+    }
+
+    let keyboard: Option<Box<dyn Controller>> = simulation.set_controller(simulation.controlled_agent, displaced_controller.take());
+    *displaced_controller = simulation.set_controller(agent_id, keyboard);
+
+    simulation.select_agent(agent_id);
+    simulation.world.is_updated = true;
+}
+
+fn save_screenshot(simulation: &Simulation, view_mode: ViewMode, camera: &TopViewCamera, width: i32, height: i32) {
+    // the current view, rendered once more at the size of the window, into the first free screenshot-NNN.png
+    let canvas: RGBACanvas = match view::render_view_with_camera(simulation, view_mode, camera, width, height) {
+        Ok(canvas) => canvas,
+        Err(error) => {
+            eprintln!("couldn't take a screenshot: {}", error);
+            return;
+        }
+    };
+
+    let path: PathBuf = next_free_path("screenshot-", ".png");
+
+    match image_io::write_png(&canvas, &path) {
+        Ok(()) => println!("screenshot saved to {}", path.display()),
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+fn draw_top_view(
+    simulation: &mut Simulation,
+    editor: Option<&WorldEditor>,
+    camera: &TopViewCamera,
+    shows_view_rays: bool,
+    backdrop: &mut TopViewBackdrop,
+    top_view_frame: &mut frame::Frame,
+) {
+    if simulation.world.is_updated {
+        let mut rendered_scene: RGBACanvas = match backdrop.render(
+            &simulation.world,
+            camera,
+            top_view_frame.width(),
+            top_view_frame.height(),
+        ) {
+            Ok(canvas) => canvas,
+            Err(_) => return, // e.g. zero-sized frame of a minimized window
+        };
+
+        // the overlays are drawn as the backdrop was, on whole pixels
+        let camera: &TopViewCamera = &camera.pixel_aligned();
+
+        if shows_view_rays {
+            view::draw_view_rays(simulation.agent(), &mut rendered_scene, camera);
+        }
+
+        if let Some(editor) = editor {
+            editor.draw(&simulation.world, &mut rendered_scene, camera);
+        }
+
+        show_canvas(&rendered_scene, top_view_frame);
+
+        simulation.world.is_updated = false;
+    }
+}
+
+fn draw_split_view(
+    simulation: &mut Simulation,
+    camera: &TopViewCamera,
+    layout: &SplitLayout,
+    backdrop: &mut TopViewBackdrop,
+    frame: &mut frame::Frame,
+) {
+    // both views are drawn again when either of them has changed, so they always show the same tick
+    if simulation.world.is_updated || simulation.agent().is_updated {
+        let rendered_view: RGBACanvas = match view::render_split_view(simulation, camera, layout, Some(backdrop), frame.width(), frame.height()) {
+            Ok(canvas) => canvas,
+            Err(_) => return,
+        };
+
+        show_canvas(&rendered_view, frame);
+
+        simulation.world.is_updated = false;
+        simulation.agent_mut().is_updated = false;
+    }
+}
+
+fn draw_fisrt_person_view(agent: &mut Agent, view_mode: ViewMode, first_person_view_frame: &mut frame::Frame) {
+    if agent.is_updated {
+        let rendered_view: moving_agent::Result<RGBACanvas> = match view_mode {
+            ViewMode::Depth => agent.render_depth_view(first_person_view_frame.width(), first_person_view_frame.height()),
+            // the plain first-person view for the other modes
+            _ => view::render_stereo_view(agent, view_mode, first_person_view_frame.width(), first_person_view_frame.height()),
+        };
+
+        let agent_view: RGBACanvas = match rendered_view {
+            Ok(canvas) => canvas,
+            Err(_) => return,
+        };
+
+        show_canvas(&agent_view, first_person_view_frame);
+
+        agent.is_updated = false;
+    }
+}
+
+fn show_canvas(canvas: &RGBACanvas, frame: &mut frame::Frame) {
+    let image: RgbImage = unsafe { RgbImage::from_data(
+        &canvas.data,
+        canvas.width,
+        canvas.height,
+        ColorDepth::Rgba8,
+    )
+    .unwrap() };
+
+    frame.set_image(Some(image));
+    frame.redraw();
+}
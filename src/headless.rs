@@ -0,0 +1,224 @@
+// running the simulation without any window:
+// actions come either from a script (list of timed actions, e.g. loaded from RON)
+// or are pushed programmatically, the state of every agent is collected every tick
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::{Action, Agent, WallHit},
+    common_structs::{Coord, RGBAColor},
+    error::Result,
+    loading::load_ron_file,
+    recording::Recorder,
+    shape::WorldSetup,
+    simulation::Simulation,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct TimedAction {
+    pub tick: u64,
+    #[serde(default)]
+    pub agent: usize, // id of the agent doing the action, the first one if not given
+    pub action: Action,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct AgentState {
+    pub tick: u64,
+    pub agent_id: usize,
+    pub center: Coord,
+    pub angle_deg: f32,
+}
+
+pub struct HeadlessRunner {
+    pub simulation: Simulation,
+    script: Vec<TimedAction>, // sorted by tick
+    next_scripted: usize,
+}
+
+impl HeadlessRunner {
+    pub fn new(setup: &WorldSetup) -> Result<HeadlessRunner> {
+        return Ok(HeadlessRunner {
+            simulation: Simulation::new(setup)?,
+            script: Vec::new(),
+            next_scripted: 0,
+        });
+    }
+
+    pub fn from_simulation(simulation: Simulation) -> HeadlessRunner {
+        return HeadlessRunner {
+            simulation,
+            script: Vec::new(),
+            next_scripted: 0,
+        };
+    }
+
+    pub fn with_script(setup: &WorldSetup, script: Vec<TimedAction>) -> Result<HeadlessRunner> {
+        let mut runner: HeadlessRunner = HeadlessRunner::new(setup)?;
+        runner.set_script(script);
+
+        return Ok(runner);
+    }
+
+    pub fn set_script(&mut self, mut script: Vec<TimedAction>) {
+        // stable sort keeps the order of actions scheduled for the same tick;
+        // ticks are absolute, so a restored simulation skips the actions its ticks so far have done
+        script.sort_by_key(|timed_action| timed_action.tick);
+
+        let tick: u64 = self.simulation.tick;
+
+        self.next_scripted = script.partition_point(|timed_action| timed_action.tick < tick);
+        self.script = script;
+    }
+
+    pub fn load_script(path: &Path) -> Result<Vec<TimedAction>> {
+        return load_ron_file(path);
+    }
+
+    pub fn is_script_finished(&self) -> bool {
+        return self.next_scripted >= self.script.len();
+    }
+
+    pub fn step(&mut self) -> Vec<AgentState> {
+        // one tick driven by the script only
+        return self.step_with(&[]);
+    }
+
+    pub fn step_with(&mut self, extra_actions: &[(usize, Action)]) -> Vec<AgentState> {
+        // one tick: scripted actions due on this tick, then the programmatic ones
+        // returns states of all agents after the tick
+
+        let mut actions: Vec<(usize, Action)> = Vec::new();
+
+        while self.next_scripted < self.script.len() && self.script[self.next_scripted].tick <= self.simulation.tick {
+            actions.push((self.script[self.next_scripted].agent, self.script[self.next_scripted].action));
+            self.next_scripted += 1;
+        }
+
+        actions.extend_from_slice(extra_actions);
+
+        self.simulation.step_agents(&actions);
+
+        return self.agent_states();
+    }
+
+    pub fn run(&mut self, ticks: u64) -> Vec<AgentState> {
+        let mut states: Vec<AgentState> = Vec::with_capacity(ticks as usize * self.simulation.agent_count());
+
+        for _ in 0..ticks {
+            states.append(&mut self.step());
+        }
+
+        return states;
+    }
+
+    pub fn run_recorded(&mut self, ticks: u64, recorder: &mut Recorder) -> Result<Vec<AgentState>> {
+        // same as run, with a frame of the starting state and of every tick
+        let mut states: Vec<AgentState> = Vec::with_capacity(ticks as usize * self.simulation.agent_count());
+
+        recorder.record(&self.simulation)?;
+
+        for _ in 0..ticks {
+            states.append(&mut self.step());
+            recorder.record(&self.simulation)?;
+        }
+
+        return Ok(states);
+    }
+
+    pub fn agent_state(&self, agent_id: usize) -> AgentState {
+        let agent: &Agent = &self.simulation.world.agents[agent_id];
+
+        return AgentState {
+            tick: self.simulation.tick,
+            agent_id,
+            center: agent.center,
+            angle_deg: agent.angle().get_deg(),
+        };
+    }
+
+    pub fn agent_states(&self) -> Vec<AgentState> {
+        let mut states: Vec<AgentState> = Vec::with_capacity(self.simulation.agent_count());
+
+        for i in 0..self.simulation.agent_count() {
+            states.push(self.agent_state(i));
+        }
+
+        return states;
+    }
+
+    pub fn get_view(&self, size: i32) -> Vec<RGBAColor> {
+        return self.simulation.agent().get_view(size);
+    }
+
+    pub fn get_agent_view(&self, agent_id: usize, size: i32) -> Vec<RGBAColor> {
+        return self.simulation.world.agents[agent_id].get_view(size);
+    }
+
+    pub fn get_depth_view(&self, agent_id: usize, size: i32) -> Vec<f32> {
+        return self.simulation.world.agents[agent_id].get_depth_view(size);
+    }
+
+    pub fn get_view_hits(&self, agent_id: usize, size: i32) -> Vec<Option<WallHit>> {
+        // everything known about each ray: distance, hit point, normal, what was hit, texture position and color
+        return self.simulation.world.agents[agent_id].cast_view_rays(size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{agent::Direction, snapshot::Snapshot};
+
+    fn test_script() -> Vec<TimedAction> {
+        // both agents moving and turning before and after the split
+        let mut script: Vec<TimedAction> = Vec::new();
+
+        for tick in 0..60 {
+            script.push(TimedAction { tick, agent: 0, action: Action::Move(Direction::Forward) });
+
+            if tick % 10 == 5 {
+                script.push(TimedAction { tick, agent: 0, action: Action::Turn(15.0) });
+                script.push(TimedAction { tick, agent: 1, action: Action::Turn(-15.0) });
+            }
+        }
+
+        return script;
+    }
+
+    #[test]
+    fn run_split_by_a_snapshot_matches_uninterrupted_run() {
+        let setup: WorldSetup = WorldSetup::from_file(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/world-shapes.ron")).unwrap();
+
+        let mut uninterrupted: HeadlessRunner = HeadlessRunner::with_script(&setup, test_script()).unwrap();
+        uninterrupted.run(60);
+
+        // the first half, saved and loaded as with --save-snapshot and --restore, then the same script again
+        let mut first_half: HeadlessRunner = HeadlessRunner::with_script(&setup, test_script()).unwrap();
+        first_half.run(30);
+
+        let snapshot_path: PathBuf = std::env::temp_dir().join(format!("moving_agent_split_run_{}.ron", std::process::id()));
+        first_half.simulation.snapshot().save(&snapshot_path).unwrap();
+        let snapshot: Snapshot = Snapshot::load(&snapshot_path).unwrap();
+        let _ = std::fs::remove_file(&snapshot_path);
+
+        let mut second_half: HeadlessRunner = HeadlessRunner::from_simulation(Simulation::from_snapshot(snapshot));
+        second_half.set_script(test_script());
+        second_half.run(30);
+
+        let expected: Vec<AgentState> = uninterrupted.agent_states();
+        let states: Vec<AgentState> = second_half.agent_states();
+
+        assert_eq!(states.len(), expected.len());
+
+        for i in 0..states.len() {
+            assert_eq!(states[i].tick, expected[i].tick);
+            assert_eq!((states[i].center.x(), states[i].center.y()), (expected[i].center.x(), expected[i].center.y()));
+            assert_eq!(states[i].angle_deg, expected[i].angle_deg);
+        }
+    }
+}
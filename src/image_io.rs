@@ -0,0 +1,1659 @@
+// writing RGBACanvas to image files and reading it back
+//
+// PNG is written without any external crates: 8-bit RGBA, no filtering,
+// zlib stream made of uncompressed (stored) deflate blocks;
+// PPM is binary (P6) RGB, alpha is dropped
+//
+// reading takes any PNG (all color types and bit depths, interlaced or not, with a small inflate below)
+// and PPM/PGM, ascii or binary, everything is turned into 8-bit RGBA
+//
+// animated GIF is written frame by frame: each frame has its own palette, the exact colors
+// if there are at most 256 of them, otherwise 256 colors found by median cut over the colors of the frame
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, Result},
+    rgba_canvas::RGBACanvas,
+};
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+const MAX_STORED_BLOCK: usize = 65535;
+
+pub fn write_image(canvas: &RGBACanvas, path: &Path) -> Result<()> {
+    // format by file extension, PNG unless it is .ppm
+    let is_ppm: bool = match path.extension() {
+        Some(extension) => extension.eq_ignore_ascii_case("ppm"),
+        None => false,
+    };
+
+    if is_ppm {
+        return write_ppm(canvas, path);
+    } else {
+        return write_png(canvas, path);
+    }
+}
+
+pub fn read_image(path: &Path) -> Result<RGBACanvas> {
+    // format by content, not by file extension
+    let bytes: Vec<u8> = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) => return Err(Error::Io { path: path.to_path_buf(), error }),
+    };
+
+    let decoded: Result<RGBACanvas> = if bytes.starts_with(&PNG_SIGNATURE) {
+        decode_png(&bytes)
+    } else if bytes.first() == Some(&b'P') {
+        decode_ppm(&bytes)
+    } else {
+        Err(invalid("neither PNG nor PPM"))
+    };
+
+    return decoded.map_err(|error| match error {
+        Error::InvalidImage { path: _, reason } => Error::InvalidImage { path: Some(path.to_path_buf()), reason },
+        other => other,
+    });
+}
+
+pub fn max_difference(canvas: &RGBACanvas, other: &RGBACanvas) -> Option<u8> {
+    // largest difference of any channel of any pixel, e.g. for comparing a render with a saved one;
+    // none if sizes differ
+    if canvas.width != other.width || canvas.height != other.height {
+        return None;
+    }
+
+    let mut difference: u8 = 0;
+
+    for i in 0..canvas.data.len() {
+        difference = u8::max(difference, canvas.data[i].abs_diff(other.data[i]));
+    }
+
+    return Some(difference);
+}
+
+pub fn write_ppm(canvas: &RGBACanvas, path: &Path) -> Result<()> {
+    return fs::write(path, encode_ppm(canvas)).map_err(|error| write_error(path, error));
+}
+
+pub fn encode_ppm(canvas: &RGBACanvas) -> Vec<u8> {
+    let header: String = format!("P6\n{} {}\n255\n", canvas.width, canvas.height);
+    let pixel_count: usize = canvas.width as usize * canvas.height as usize;
+
+    let mut ppm: Vec<u8> = Vec::with_capacity(header.len() + pixel_count * 3);
+    ppm.extend_from_slice(header.as_bytes());
+
+    for i in 0..pixel_count {
+        ppm.extend_from_slice(&canvas.data[i * 4..i * 4 + 3]);
+    }
+
+    return ppm;
+}
+
+pub fn write_png(canvas: &RGBACanvas, path: &Path) -> Result<()> {
+    return fs::write(path, encode_png(canvas)).map_err(|error| write_error(path, error));
+}
+
+pub fn encode_png(canvas: &RGBACanvas) -> Vec<u8> {
+    let mut png: Vec<u8> = Vec::new();
+    png.extend_from_slice(&PNG_SIGNATURE);
+
+    let mut header: Vec<u8> = Vec::with_capacity(13);
+    header.extend_from_slice(&(canvas.width as u32).to_be_bytes());
+    header.extend_from_slice(&(canvas.height as u32).to_be_bytes());
+    header.push(8); // bit depth
+    header.push(6); // color type: RGBA
+    header.push(0); // compression: deflate
+    header.push(0); // filter method
+    header.push(0); // no interlace
+    write_chunk(&mut png, b"IHDR", &header);
+
+    // every scanline starts with its filter type, 0 == None
+    let row_length: usize = canvas.width as usize * 4;
+    let mut raw: Vec<u8> = Vec::with_capacity((row_length + 1) * canvas.height as usize);
+
+    for j in 0..(canvas.height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(&canvas.data[j * row_length..(j + 1) * row_length]);
+    }
+
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    return png;
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let crc_start: usize = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+
+    let crc: u32 = crc32(&png[crc_start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream: Vec<u8> = Vec::with_capacity(data.len() + data.len() / MAX_STORED_BLOCK * 5 + 16);
+
+    // deflate, 32K window, no dictionary, fastest -- header checksum makes it divisible by 31
+    stream.push(0x78);
+    stream.push(0x01);
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+
+    if blocks.peek().is_none() {
+        // a single empty final block
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let is_final: u8 = if blocks.peek().is_none() {1} else {0};
+        let length: u16 = block.len() as u16;
+
+        stream.push(is_final); // BFINAL, BTYPE = 00 (stored)
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+
+    return stream;
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffff_ffff;
+
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            if crc & 1 == 1 {
+                crc = (crc >> 1) ^ 0xedb8_8320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+
+    return !crc;
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    return (b << 16) | a;
+}
+
+fn write_error(path: &Path, error: io::Error) -> Error {
+    return Error::Write { path: path.to_path_buf(), error };
+}
+
+fn invalid(reason: &str) -> Error {
+    return Error::InvalidImage { path: None, reason: reason.to_string() };
+}
+
+fn new_canvas(width: u32, height: u32) -> Result<RGBACanvas> {
+    check_canvas_size(width, height)?;
+
+    return RGBACanvas::new(width as i32, height as i32);
+}
+
+fn check_canvas_size(width: u32, height: u32) -> Result<()> {
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 || (width as u64) * (height as u64) > (1 << 28) {
+        return Err(invalid(&format!("unsupported image size {}x{}", width, height)));
+    }
+
+    return Ok(());
+}
+
+/////////////////////////////////////////////////////////
+// animated GIF
+
+const GIF_MAX_CODES: u16 = 4096;
+const GIF_PALETTE_SIZE: usize = 256;
+
+pub struct GifWriter {
+    file: BufWriter<File>,
+    path: PathBuf, // for errors
+    width: i32,
+    height: i32,
+    frame_time: f64, // between frames, in seconds
+    frames: u64, // written so far
+    delay_written_cs: u64, // sum of the delays written so far, in hundredths of a second
+}
+
+impl GifWriter {
+    pub fn create(path: &Path, width: i32, height: i32, frame_time: f64) -> Result<GifWriter> {
+        if width <= 0 || height <= 0 || width > u16::MAX as i32 || height > u16::MAX as i32 {
+            return Err(write_error(path, io::Error::new(io::ErrorKind::InvalidInput, format!("GIF can't be {}x{}", width, height))));
+        }
+
+        let file: File = File::create(path).map_err(|error| write_error(path, error))?;
+
+        let mut gif: GifWriter = GifWriter {
+            file: BufWriter::new(file),
+            path: path.to_path_buf(),
+            width,
+            height,
+            frame_time,
+            frames: 0,
+            delay_written_cs: 0,
+        };
+
+        gif.write_header().map_err(|error| write_error(path, error))?;
+
+        return Ok(gif);
+    }
+
+    pub fn add_frame(&mut self, canvas: &RGBACanvas) -> Result<()> {
+        // all frames are of the size the GIF was created with, alpha is ignored
+        if canvas.width != self.width || canvas.height != self.height {
+            return Err(write_error(&self.path, io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("frame is {}x{}, the GIF is {}x{}", canvas.width, canvas.height, self.width, self.height),
+            )));
+        }
+
+        let (palette, indices) = palettize(canvas);
+
+        return self.write_frame(&palette, &indices).map_err(|error| write_error(&self.path, error));
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        let trailer: io::Result<()> = self.file.write_all(&[0x3b]).and_then(|_| self.file.flush());
+
+        return trailer.map_err(|error| write_error(&self.path, error));
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        self.file.write_all(b"GIF89a")?;
+        self.file.write_all(&(self.width as u16).to_le_bytes())?;
+        self.file.write_all(&(self.height as u16).to_le_bytes())?;
+        self.file.write_all(&[0, 0, 0])?; // no global palette, background, aspect ratio
+
+        // loop forever
+        self.file.write_all(&[0x21, 0xff, 11])?;
+        self.file.write_all(b"NETSCAPE2.0")?;
+        self.file.write_all(&[3, 1, 0, 0, 0])?;
+
+        return Ok(());
+    }
+
+    fn next_delay_cs(&mut self) -> u16 {
+        // GIF delays are whole hundredths of a second, so each frame gets the rounded end time
+        // of the frame minus the delays already written, and the rounding error doesn't pile up
+        // (1.67 cs frames get 2, 1, 2, 2, 1, 2, ...)
+        self.frames += 1;
+
+        let end_cs: u64 = f64::round(self.frames as f64 * self.frame_time * 100.0) as u64;
+        let delay_cs: u64 = end_cs.saturating_sub(self.delay_written_cs).min(u16::MAX as u64);
+
+        self.delay_written_cs += delay_cs;
+
+        return delay_cs as u16;
+    }
+
+    fn write_frame(&mut self, palette: &[[u8; 3]], indices: &[u8]) -> io::Result<()> {
+        let delay_cs: u16 = self.next_delay_cs();
+
+        // graphic control: frame delay, each frame is drawn over the previous one
+        self.file.write_all(&[0x21, 0xf9, 4, 0x04])?;
+        self.file.write_all(&delay_cs.to_le_bytes())?;
+        self.file.write_all(&[0, 0])?;
+
+        // image descriptor with a local palette of 256 colors, unused entries are black
+        self.file.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.file.write_all(&(self.width as u16).to_le_bytes())?;
+        self.file.write_all(&(self.height as u16).to_le_bytes())?;
+        self.file.write_all(&[0x87])?;
+
+        for i in 0..GIF_PALETTE_SIZE {
+            self.file.write_all(&palette.get(i).unwrap_or(&[0, 0, 0])[..])?;
+        }
+
+        self.file.write_all(&[8])?; // minimal code size
+
+        let compressed: Vec<u8> = lzw_compress(indices);
+
+        for block in compressed.chunks(255) {
+            self.file.write_all(&[block.len() as u8])?;
+            self.file.write_all(block)?;
+        }
+
+        self.file.write_all(&[0])?;
+
+        return Ok(());
+    }
+}
+
+fn palettize(canvas: &RGBACanvas) -> (Vec<[u8; 3]>, Vec<u8>) {
+    // palette and palette index of every pixel
+    let pixel_count: usize = canvas.width as usize * canvas.height as usize;
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut known: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut indices: Vec<u8> = Vec::with_capacity(pixel_count);
+
+    for i in 0..pixel_count {
+        let color: [u8; 3] = [canvas.data[i * 4], canvas.data[i * 4 + 1], canvas.data[i * 4 + 2]];
+
+        match known.get(&color) {
+            Some(&index) => indices.push(index),
+            None => {
+                if palette.len() == GIF_PALETTE_SIZE {
+                    return median_cut_palettize(canvas);
+                }
+
+                known.insert(color, palette.len() as u8);
+                indices.push(palette.len() as u8);
+                palette.push(color);
+            }
+        }
+    }
+
+    return (palette, indices);
+}
+
+// colors start..end of the frame, sorted or not, and the channel along which they are the widest apart
+#[derive(Clone, Copy)]
+struct ColorBox {
+    start: usize,
+    end: usize,
+    channel: usize,
+    width: u8,
+}
+
+impl ColorBox {
+    fn new(colors: &[([u8; 3], u32)], start: usize, end: usize) -> ColorBox {
+        let mut color_box: ColorBox = ColorBox { start, end, channel: 0, width: 0 };
+
+        for k in 0..3 {
+            let low: u8 = colors[start..end].iter().map(|(color, _)| color[k]).min().unwrap();
+            let high: u8 = colors[start..end].iter().map(|(color, _)| color[k]).max().unwrap();
+
+            if high - low > color_box.width {
+                color_box.channel = k;
+                color_box.width = high - low;
+            }
+        }
+
+        return color_box;
+    }
+}
+
+fn median_cut_palettize(canvas: &RGBACanvas) -> (Vec<[u8; 3]>, Vec<u8>) {
+    // the colors of the frame are split into boxes, each time the box that is the widest along some channel
+    // at the median of its pixels along that channel; a box gets the average of its pixels
+    let pixel_count: usize = canvas.width as usize * canvas.height as usize;
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+
+    for i in 0..pixel_count {
+        *counts.entry([canvas.data[i * 4], canvas.data[i * 4 + 1], canvas.data[i * 4 + 2]]).or_insert(0) += 1;
+    }
+
+    let mut colors: Vec<([u8; 3], u32)> = counts.into_iter().collect();
+    colors.sort_unstable(); // the same palette for the same frame
+
+    let mut boxes: Vec<ColorBox> = vec![ColorBox::new(&colors, 0, colors.len())];
+
+    while boxes.len() < GIF_PALETTE_SIZE {
+        let mut widest_box: usize = 0;
+
+        for i in 1..boxes.len() {
+            if boxes[i].width > boxes[widest_box].width {
+                widest_box = i;
+            }
+        }
+
+        let ColorBox { start, end, channel, width } = boxes[widest_box];
+
+        if width == 0 {
+            break; // every box is a single color
+        }
+
+        let part: &mut [([u8; 3], u32)] = &mut colors[start..end];
+        part.sort_unstable_by_key(|(color, _)| color[channel]);
+
+        // split after half of the pixels, leaving at least one color on each side
+        let half: u64 = part.iter().map(|&(_, count)| count as u64).sum::<u64>() / 2;
+        let mut split: usize = 1;
+        let mut below: u64 = part[0].1 as u64;
+
+        while split < part.len() - 1 && below + (part[split].1 as u64) <= half {
+            below += part[split].1 as u64;
+            split += 1;
+        }
+
+        boxes[widest_box] = ColorBox::new(&colors, start, start + split);
+        boxes.push(ColorBox::new(&colors, start + split, end));
+    }
+
+    let mut palette: Vec<[u8; 3]> = Vec::with_capacity(boxes.len());
+    let mut known: HashMap<[u8; 3], u8> = HashMap::with_capacity(colors.len());
+
+    for i in 0..boxes.len() {
+        let ColorBox { start, end, .. } = boxes[i];
+        let mut sums: [u64; 3] = [0; 3];
+        let mut total: u64 = 0;
+
+        for j in start..end {
+            let (color, count) = colors[j];
+
+            for k in 0..3 {
+                sums[k] += color[k] as u64 * count as u64;
+            }
+
+            total += count as u64;
+            known.insert(color, i as u8);
+        }
+
+        palette.push([
+            ((sums[0] + total / 2) / total) as u8,
+            ((sums[1] + total / 2) / total) as u8,
+            ((sums[2] + total / 2) / total) as u8,
+        ]);
+    }
+
+    let mut indices: Vec<u8> = Vec::with_capacity(pixel_count);
+
+    for i in 0..pixel_count {
+        indices.push(known[&[canvas.data[i * 4], canvas.data[i * 4 + 1], canvas.data[i * 4 + 2]]]);
+    }
+
+    return (palette, indices);
+}
+
+fn lzw_compress(indices: &[u8]) -> Vec<u8> {
+    // variable-length codes from 9 to 12 bits, packed starting from the lowest bit;
+    // the table is cleared when it is full
+    let clear_code: u16 = 256;
+    let end_code: u16 = 257;
+
+    let mut output: CodeWriter = CodeWriter { bytes: Vec::new(), buffer: 0, buffered_bits: 0 };
+    let mut code_size: u32 = 9;
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code: u16 = end_code + 1;
+
+    output.write(clear_code, code_size);
+
+    if let Some((&first, rest)) = indices.split_first() {
+        let mut prefix: u16 = first as u16;
+
+        for &index in rest {
+            if let Some(&code) = table.get(&(prefix, index)) {
+                prefix = code;
+                continue;
+            }
+
+            output.write(prefix, code_size);
+
+            if next_code < GIF_MAX_CODES - 1 {
+                table.insert((prefix, index), next_code);
+                next_code += 1;
+
+                // the decoder learns codes one step later, so it switches to longer codes one code later too
+                if next_code as u32 > (1 << code_size) && code_size < 12 {
+                    code_size += 1;
+                }
+            } else {
+                output.write(clear_code, code_size);
+                table.clear();
+                next_code = end_code + 1;
+                code_size = 9;
+            }
+
+            prefix = index as u16;
+        }
+
+        output.write(prefix, code_size);
+    }
+
+    output.write(end_code, code_size);
+
+    return output.finish();
+}
+
+struct CodeWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    buffered_bits: u32,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, code_size: u32) {
+        self.buffer |= (code as u32) << self.buffered_bits;
+        self.buffered_bits += code_size;
+
+        while self.buffered_bits >= 8 {
+            self.bytes.push((self.buffer & 0xff) as u8);
+            self.buffer >>= 8;
+            self.buffered_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        // the rest of the last byte
+        if self.buffered_bits > 0 {
+            self.bytes.push((self.buffer & 0xff) as u8);
+        }
+
+        return self.bytes;
+    }
+}
+
+/////////////////////////////////////////////////////////
+// PPM / PGM
+
+pub fn decode_ppm(bytes: &[u8]) -> Result<RGBACanvas> {
+    // P2 / P5 are grey (PGM), P3 / P6 are color; P2 and P3 have their samples written as text
+    let mut position: usize = 0;
+
+    let magic: Vec<u8> = next_token(bytes, &mut position).ok_or(invalid("empty file"))?;
+    let (channels, is_binary): (usize, bool) = match magic.as_slice() {
+        b"P2" => (1, false),
+        b"P3" => (3, false),
+        b"P5" => (1, true),
+        b"P6" => (3, true),
+        _ => return Err(invalid("not a PPM/PGM file (P2, P3, P5 or P6)")),
+    };
+
+    let width: u32 = next_number(bytes, &mut position)?;
+    let height: u32 = next_number(bytes, &mut position)?;
+    let max_value: u32 = next_number(bytes, &mut position)?;
+
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid(&format!("max value {} is out of 1..65535", max_value)));
+    }
+
+    // the data has to be in the file before anything is allocated for it,
+    // so that a few bytes of header can't ask for a huge canvas
+    let sample_size: u64 = if max_value < 256 {1} else {2};
+    let header_samples: u64 = (width as u64).saturating_mul(height as u64).saturating_mul(channels as u64);
+    let needed_bytes: u64 = if is_binary {
+        // exactly one whitespace between the max value and the data
+        header_samples.saturating_mul(sample_size).saturating_add(1)
+    } else {
+        // at least a digit and a separator for every sample but the last
+        header_samples.saturating_mul(2).saturating_sub(1)
+    };
+
+    if ((bytes.len() - position) as u64) < needed_bytes {
+        return Err(invalid("image data is cut short"));
+    }
+
+    let mut canvas: RGBACanvas = new_canvas(width, height)?;
+    let pixel_count: usize = width as usize * height as usize;
+    let sample_count: usize = pixel_count * channels;
+    let mut samples: Vec<u32> = Vec::with_capacity(sample_count);
+
+    if is_binary {
+        position += 1;
+
+        for i in 0..sample_count {
+            let at: usize = position + i * sample_size as usize;
+            let sample: u32 = if sample_size == 1 {
+                bytes[at] as u32
+            } else {
+                (bytes[at] as u32) << 8 | bytes[at + 1] as u32
+            };
+
+            samples.push(sample);
+        }
+    } else {
+        for _ in 0..sample_count {
+            samples.push(next_number(bytes, &mut position)?);
+        }
+    }
+
+    for i in 0..pixel_count {
+        for k in 0..3 {
+            let sample: u32 = u32::min(samples[i * channels + k % channels], max_value);
+            canvas.data[i * 4 + k] = ((sample * 255 + max_value / 2) / max_value) as u8;
+        }
+
+        canvas.data[i * 4 + 3] = 255;
+    }
+
+    return Ok(canvas);
+}
+
+fn next_token(bytes: &[u8], position: &mut usize) -> Option<Vec<u8>> {
+    // skips whitespace and comments (from # to the end of line)
+    while *position < bytes.len() {
+        if bytes[*position] == b'#' {
+            while *position < bytes.len() && bytes[*position] != b'\n' {
+                *position += 1;
+            }
+        } else if bytes[*position].is_ascii_whitespace() {
+            *position += 1;
+        } else {
+            break;
+        }
+    }
+
+    let start: usize = *position;
+
+    while *position < bytes.len() && !bytes[*position].is_ascii_whitespace() && bytes[*position] != b'#' {
+        *position += 1;
+    }
+
+    if *position == start {
+        return None;
+    }
+
+    return Some(bytes[start..*position].to_vec());
+}
+
+fn next_number(bytes: &[u8], position: &mut usize) -> Result<u32> {
+    let token: Vec<u8> = next_token(bytes, position).ok_or(invalid("header or data is cut short"))?;
+
+    return String::from_utf8_lossy(&token).parse::<u32>().map_err(|_| invalid("expected a number"));
+}
+
+/////////////////////////////////////////////////////////
+// PNG
+
+// pixel positions of the 7 passes of Adam7 interlacing: first x, first y, step x, step y
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+struct PngHeader {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    is_interlaced: bool,
+}
+
+impl PngHeader {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 => 1, // grey
+            2 => 3, // RGB
+            3 => 1, // palette index
+            4 => 2, // grey and alpha
+            _ => 4, // RGBA
+        }
+    }
+
+    fn passes(&self) -> Vec<(usize, usize, usize, usize)> {
+        return if self.is_interlaced {ADAM7.to_vec()} else {vec![(0, 0, 1, 1)]};
+    }
+
+    fn data_length(&self) -> usize {
+        // filtered rows of all passes, each with its filter type byte
+        let mut length: usize = 0;
+
+        for &(x_0, y_0, d_x, d_y) in self.passes().iter() {
+            let pass_width: usize = if self.width > x_0 {(self.width - x_0 + d_x - 1) / d_x} else {0};
+            let pass_height: usize = if self.height > y_0 {(self.height - y_0 + d_y - 1) / d_y} else {0};
+
+            if pass_width > 0 && pass_height > 0 {
+                length += ((pass_width * self.bits_per_pixel() + 7) / 8 + 1) * pass_height;
+            }
+        }
+
+        return length;
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        return self.channels() * self.bit_depth as usize;
+    }
+}
+
+pub fn decode_png(bytes: &[u8]) -> Result<RGBACanvas> {
+    if !bytes.starts_with(&PNG_SIGNATURE) {
+        return Err(invalid("not a PNG file"));
+    }
+
+    let mut header: Option<PngHeader> = None;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut transparency: Vec<u8> = Vec::new(); // tRNS: alphas of palette entries, or the transparent color
+    let mut compressed: Vec<u8> = Vec::new();
+    let mut position: usize = PNG_SIGNATURE.len();
+    let mut is_ended: bool = false;
+
+    while !is_ended {
+        if bytes.len() < position + 12 {
+            return Err(invalid("file is cut short"));
+        }
+
+        let length: usize = u32::from_be_bytes([bytes[position], bytes[position + 1], bytes[position + 2], bytes[position + 3]]) as usize;
+
+        if bytes.len() < position + 12 + length {
+            return Err(invalid("chunk is cut short"));
+        }
+
+        let chunk_type: &[u8] = &bytes[position + 4..position + 8];
+        let data: &[u8] = &bytes[position + 8..position + 8 + length];
+        let crc_at: usize = position + 8 + length;
+        let crc: u32 = u32::from_be_bytes([bytes[crc_at], bytes[crc_at + 1], bytes[crc_at + 2], bytes[crc_at + 3]]);
+
+        if crc32(&bytes[position + 4..crc_at]) != crc {
+            return Err(invalid(&format!("bad checksum of {} chunk", String::from_utf8_lossy(chunk_type))));
+        }
+
+        match chunk_type {
+            b"IHDR" => header = Some(read_png_header(data)?),
+            b"PLTE" => {
+                for rgb in data.chunks_exact(3) {
+                    palette.push([rgb[0], rgb[1], rgb[2], 255]);
+                }
+            }
+            b"tRNS" => transparency = data.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => is_ended = true,
+            _ => {
+                // ancillary chunks (gamma, text, ...) are not needed
+            }
+        }
+
+        position = crc_at + 4;
+    }
+
+    let header: PngHeader = header.ok_or(invalid("IHDR chunk is missing"))?;
+
+    if header.color_type == 3 {
+        if palette.is_empty() {
+            return Err(invalid("PLTE chunk is missing"));
+        }
+
+        for i in 0..usize::min(transparency.len(), palette.len()) {
+            palette[i][3] = transparency[i];
+        }
+    }
+
+    // the size is checked before decompressing, which then stops at as much data as the image needs
+    check_canvas_size(header.width as u32, header.height as u32)?;
+
+    let raw: Vec<u8> = zlib_decompress(&compressed, header.data_length())?;
+    let mut canvas: RGBACanvas = new_canvas(header.width as u32, header.height as u32)?;
+
+    let passes: Vec<(usize, usize, usize, usize)> = header.passes();
+    let mut offset: usize = 0;
+
+    for &(x_0, y_0, d_x, d_y) in passes.iter() {
+        let pass_width: usize = if header.width > x_0 {(header.width - x_0 + d_x - 1) / d_x} else {0};
+        let pass_height: usize = if header.height > y_0 {(header.height - y_0 + d_y - 1) / d_y} else {0};
+
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        let row_length: usize = (pass_width * header.bits_per_pixel() + 7) / 8;
+        let pass_length: usize = (row_length + 1) * pass_height;
+
+        if raw.len() < offset + pass_length {
+            return Err(invalid("image data is cut short"));
+        }
+
+        let pixels: Vec<u8> = unfilter(&raw[offset..offset + pass_length], row_length, pass_height, header.bits_per_pixel())?;
+        offset += pass_length;
+
+        for j in 0..pass_height {
+            let row: &[u8] = &pixels[j * row_length..(j + 1) * row_length];
+
+            for i in 0..pass_width {
+                let color: [u8; 4] = png_pixel(&header, row, i, &palette, &transparency);
+                let at: usize = ((y_0 + j * d_y) * header.width + x_0 + i * d_x) * 4;
+
+                canvas.data[at..at + 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    return Ok(canvas);
+}
+
+fn read_png_header(data: &[u8]) -> Result<PngHeader> {
+    if data.len() != 13 {
+        return Err(invalid("IHDR chunk has wrong length"));
+    }
+
+    let width: u32 = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let height: u32 = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let bit_depth: u8 = data[8];
+    let color_type: u8 = data[9];
+
+    let allowed_depths: &[u8] = match color_type {
+        0 => &[1, 2, 4, 8, 16],
+        3 => &[1, 2, 4, 8],
+        2 | 4 | 6 => &[8, 16],
+        _ => return Err(invalid(&format!("unknown color type {}", color_type))),
+    };
+
+    if !allowed_depths.contains(&bit_depth) {
+        return Err(invalid(&format!("bit depth {} is not allowed for color type {}", bit_depth, color_type)));
+    }
+
+    if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+        return Err(invalid("unknown compression, filter or interlace method"));
+    }
+
+    // checks the size before anything is allocated for it
+    new_canvas(width, height)?;
+
+    return Ok(PngHeader {
+        width: width as usize,
+        height: height as usize,
+        bit_depth,
+        color_type,
+        is_interlaced: data[12] == 1,
+    });
+}
+
+fn unfilter(filtered: &[u8], row_length: usize, rows: usize, bits_per_pixel: usize) -> Result<Vec<u8>> {
+    // every row starts with its filter type, filters predict a byte from the bytes to the left,
+    // above and above-left (a whole pixel away, or a byte for pixels smaller than a byte)
+    let step: usize = usize::max(1, bits_per_pixel / 8);
+    let mut pixels: Vec<u8> = vec![0; row_length * rows];
+
+    for j in 0..rows {
+        let filter: u8 = filtered[j * (row_length + 1)];
+        let source: &[u8] = &filtered[j * (row_length + 1) + 1..(j + 1) * (row_length + 1)];
+
+        for i in 0..row_length {
+            let left: u8 = if i >= step {pixels[j * row_length + i - step]} else {0};
+            let up: u8 = if j > 0 {pixels[(j - 1) * row_length + i]} else {0};
+            let up_left: u8 = if i >= step && j > 0 {pixels[(j - 1) * row_length + i - step]} else {0};
+
+            let prediction: u8 = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(invalid(&format!("unknown filter type {}", filter))),
+            };
+
+            pixels[j * row_length + i] = source[i].wrapping_add(prediction);
+        }
+    }
+
+    return Ok(pixels);
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate: i16 = left as i16 + up as i16 - up_left as i16;
+    let to_left: i16 = (estimate - left as i16).abs();
+    let to_up: i16 = (estimate - up as i16).abs();
+    let to_up_left: i16 = (estimate - up_left as i16).abs();
+
+    if to_left <= to_up && to_left <= to_up_left {
+        return left;
+    } else if to_up <= to_up_left {
+        return up;
+    } else {
+        return up_left;
+    }
+}
+
+fn png_pixel(header: &PngHeader, row: &[u8], index: usize, palette: &[[u8; 4]], transparency: &[u8]) -> [u8; 4] {
+    let channels: usize = header.channels();
+    let mut samples: [u16; 4] = [0; 4];
+
+    for k in 0..channels {
+        samples[k] = png_sample(row, index * channels + k, header.bit_depth);
+    }
+
+    // samples scaled to 8 bits
+    let max_sample: u32 = (1 << header.bit_depth) - 1;
+    let to_8_bit = |sample: u16| -> u8 { return ((sample as u32 * 255 + max_sample / 2) / max_sample) as u8; };
+
+    // a single fully transparent color can be given for grey and RGB images
+    let is_key_color: bool = match header.color_type {
+        0 => transparency.len() >= 2 && samples[0] == u16::from_be_bytes([transparency[0], transparency[1]]),
+        2 => transparency.len() >= 6 && (0..3).all(|k| samples[k] == u16::from_be_bytes([transparency[k * 2], transparency[k * 2 + 1]])),
+        _ => false,
+    };
+    let key_alpha: u8 = if is_key_color {0} else {255};
+
+    match header.color_type {
+        0 => {
+            let grey: u8 = to_8_bit(samples[0]);
+            return [grey, grey, grey, key_alpha];
+        }
+        2 => return [to_8_bit(samples[0]), to_8_bit(samples[1]), to_8_bit(samples[2]), key_alpha],
+        3 => {
+            // indices out of the palette are black
+            return *palette.get(samples[0] as usize).unwrap_or(&[0, 0, 0, 255]);
+        }
+        4 => {
+            let grey: u8 = to_8_bit(samples[0]);
+            return [grey, grey, grey, to_8_bit(samples[1])];
+        }
+        _ => return [to_8_bit(samples[0]), to_8_bit(samples[1]), to_8_bit(samples[2]), to_8_bit(samples[3])],
+    }
+}
+
+fn png_sample(row: &[u8], index: usize, bit_depth: u8) -> u16 {
+    // samples smaller than a byte are packed from the highest bits down
+    match bit_depth {
+        16 => return u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => return row[index] as u16,
+        _ => {
+            let bits: usize = bit_depth as usize;
+            let per_byte: usize = 8 / bits;
+            let shift: usize = 8 - bits * (index % per_byte + 1);
+
+            return ((row[index / per_byte] >> shift) & ((1 << bits) - 1) as u8) as u16;
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////
+// zlib / deflate decompression
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// order in which lengths of the code length code are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+const MAX_CODE_BITS: usize = 15;
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize, // next byte
+    buffer: u32,
+    buffered_bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        return BitReader { data, position: 0, buffer: 0, buffered_bits: 0 };
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        // deflate packs bits starting from the lowest bit of each byte
+        while self.buffered_bits < count {
+            if self.position >= self.data.len() {
+                return Err(invalid("compressed data is cut short"));
+            }
+
+            self.buffer |= (self.data[self.position] as u32) << self.buffered_bits;
+            self.position += 1;
+            self.buffered_bits += 8;
+        }
+
+        let value: u32 = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.buffered_bits -= count;
+
+        return Ok(value);
+    }
+
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.buffered_bits = 0;
+    }
+}
+
+// canonical Huffman code: how many codes there are of each length, and symbols ordered by their codes
+struct Huffman {
+    counts: [u16; MAX_CODE_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman> {
+        let mut counts: [u16; MAX_CODE_BITS + 1] = [0; MAX_CODE_BITS + 1];
+
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        counts[0] = 0;
+
+        // more codes of some length than are left for it, incomplete codes are fine
+        let mut codes_left: i32 = 1;
+
+        for length in 1..=MAX_CODE_BITS {
+            codes_left = codes_left * 2 - counts[length] as i32;
+
+            if codes_left < 0 {
+                return Err(invalid("bad Huffman code lengths"));
+            }
+        }
+
+        let mut offsets: [u16; MAX_CODE_BITS + 2] = [0; MAX_CODE_BITS + 2];
+
+        for length in 1..=MAX_CODE_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols: Vec<u16> = vec![0; lengths.len()];
+
+        for symbol in 0..lengths.len() {
+            if lengths[symbol] != 0 {
+                let length: usize = lengths[symbol] as usize;
+                symbols[offsets[length] as usize] = symbol as u16;
+                offsets[length] += 1;
+            }
+        }
+
+        return Ok(Huffman { counts, symbols });
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        // codes are read bit by bit, the first code of each length follows the last one of the previous length
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for length in 1..=MAX_CODE_BITS {
+            code |= reader.bits(1)? as i32;
+
+            let count: i32 = self.counts[length] as i32;
+
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        return Err(invalid("bad Huffman code"));
+    }
+}
+
+fn zlib_decompress(stream: &[u8], max_length: usize) -> Result<Vec<u8>> {
+    // data longer than max_length is an error, found as soon as it gets that long
+    if stream.len() < 6 {
+        return Err(invalid("compressed data is cut short"));
+    }
+
+    let method: u8 = stream[0] & 0x0f;
+    let has_dictionary: bool = stream[1] & 0x20 != 0;
+
+    if method != 8 || ((stream[0] as u16) << 8 | stream[1] as u16) % 31 != 0 || has_dictionary {
+        return Err(invalid("bad zlib header"));
+    }
+
+    let (data, used) = inflate(&stream[2..], max_length)?;
+
+    let checksum_at: usize = 2 + used;
+
+    if stream.len() < checksum_at + 4 {
+        return Err(invalid("zlib checksum is missing"));
+    }
+
+    let checksum: u32 = u32::from_be_bytes([stream[checksum_at], stream[checksum_at + 1], stream[checksum_at + 2], stream[checksum_at + 3]]);
+
+    if adler32(&data) != checksum {
+        return Err(invalid("bad zlib checksum"));
+    }
+
+    return Ok(data);
+}
+
+fn inflate(compressed: &[u8], max_length: usize) -> Result<(Vec<u8>, usize)> {
+    // decompressed data, and how many bytes of input it took
+    let mut reader: BitReader = BitReader::new(compressed);
+    let mut output: Vec<u8> = Vec::new();
+    let mut is_final: bool = false;
+
+    while !is_final {
+        is_final = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => inflate_stored(&mut reader, &mut output, max_length)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                inflate_block(&mut reader, &mut output, &literals, &distances, max_length)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances, max_length)?;
+            }
+            _ => return Err(invalid("bad deflate block type")),
+        }
+    }
+
+    return Ok((output, reader.position));
+}
+
+fn inflate_stored(reader: &mut BitReader, output: &mut Vec<u8>, max_length: usize) -> Result<()> {
+    reader.align_to_byte();
+
+    let at: usize = reader.position;
+
+    if reader.data.len() < at + 4 {
+        return Err(invalid("compressed data is cut short"));
+    }
+
+    let length: usize = u16::from_le_bytes([reader.data[at], reader.data[at + 1]]) as usize;
+    let check: u16 = u16::from_le_bytes([reader.data[at + 2], reader.data[at + 3]]);
+
+    if !check != length as u16 {
+        return Err(invalid("bad stored block length"));
+    }
+
+    if reader.data.len() < at + 4 + length {
+        return Err(invalid("compressed data is cut short"));
+    }
+
+    if output.len() + length > max_length {
+        return Err(too_much_data());
+    }
+
+    output.extend_from_slice(&reader.data[at + 4..at + 4 + length]);
+    reader.position = at + 4 + length;
+
+    return Ok(());
+}
+
+fn too_much_data() -> Error {
+    return invalid("decompressed data is longer than the image needs");
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman)> {
+    let mut lengths: [u8; 288] = [8; 288];
+
+    for symbol in 144..256 {
+        lengths[symbol] = 9;
+    }
+
+    for symbol in 256..280 {
+        lengths[symbol] = 7;
+    }
+
+    return Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?));
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let literal_count: usize = reader.bits(5)? as usize + 257;
+    let distance_count: usize = reader.bits(5)? as usize + 1;
+    let code_length_count: usize = reader.bits(4)? as usize + 4;
+
+    if literal_count > 286 || distance_count > 30 {
+        return Err(invalid("bad deflate code counts"));
+    }
+
+    let mut code_length_lengths: [u8; 19] = [0; 19];
+
+    for i in 0..code_length_count {
+        code_length_lengths[CODE_LENGTH_ORDER[i]] = reader.bits(3)? as u8;
+    }
+
+    let code_length_code: Huffman = Huffman::new(&code_length_lengths)?;
+    let mut lengths: Vec<u8> = Vec::with_capacity(literal_count + distance_count);
+
+    while lengths.len() < literal_count + distance_count {
+        let symbol: u16 = code_length_code.decode(reader)?;
+
+        let (repeated, times): (u8, u32) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(&previous) => (previous, 3 + reader.bits(2)?),
+                None => return Err(invalid("repeated code length without a previous one")),
+            },
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+
+        for _ in 0..times {
+            lengths.push(repeated);
+        }
+    }
+
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid("too many code lengths"));
+    }
+
+    if lengths[256] == 0 {
+        return Err(invalid("end of block has no code"));
+    }
+
+    return Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?));
+}
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman, max_length: usize) -> Result<()> {
+    loop {
+        let symbol: u16 = literals.decode(reader)?;
+
+        if symbol < 256 {
+            if output.len() >= max_length {
+                return Err(too_much_data());
+            }
+
+            output.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            // a copy of earlier output: length, then distance back
+            let length_code: usize = symbol as usize - 257;
+
+            if length_code >= LENGTH_BASE.len() {
+                return Err(invalid("bad length code"));
+            }
+
+            let length: usize = LENGTH_BASE[length_code] as usize + reader.bits(LENGTH_EXTRA[length_code] as u32)? as usize;
+            let distance_code: usize = distances.decode(reader)? as usize;
+
+            if distance_code >= DISTANCE_BASE.len() {
+                return Err(invalid("bad distance code"));
+            }
+
+            let distance: usize = DISTANCE_BASE[distance_code] as usize + reader.bits(DISTANCE_EXTRA[distance_code] as u32)? as usize;
+
+            if distance > output.len() {
+                return Err(invalid("distance goes before the start of data"));
+            }
+
+            if output.len() + length > max_length {
+                return Err(too_much_data());
+            }
+
+            // byte by byte, the copy may overlap what it is copying
+            let start: usize = output.len() - distance;
+
+            for i in 0..length {
+                output.push(output[start + i]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_canvas(width: i32, height: i32, is_opaque: bool) -> RGBACanvas {
+        // every channel of every pixel different enough to catch swapped or shifted bytes
+        let mut canvas: RGBACanvas = RGBACanvas::new(width, height).unwrap();
+
+        for i in 0..canvas.data.len() {
+            canvas.data[i] = ((i * 37 + i / 7) % 256) as u8;
+
+            if is_opaque && i % 4 == 3 {
+                canvas.data[i] = 255;
+            }
+        }
+
+        return canvas;
+    }
+
+    fn encode_p3(canvas: &RGBACanvas) -> Vec<u8> {
+        // text samples, with a comment and uneven spacing as other programs write them
+        let mut text: String = format!("P3\n# test image\n{} {}\n255\n", canvas.width, canvas.height);
+
+        for i in 0..(canvas.width * canvas.height) as usize {
+            text.push_str(&format!("{} {}  {}\n", canvas.data[i * 4], canvas.data[i * 4 + 1], canvas.data[i * 4 + 2]));
+        }
+
+        return text.into_bytes();
+    }
+
+    fn png_with_data(width: u32, height: u32, raw: &[u8]) -> Vec<u8> {
+        // RGBA PNG with whatever filtered data is given
+        return png_with_idat(width, height, &zlib_stored(raw));
+    }
+
+    fn png_with_idat(width: u32, height: u32, idat: &[u8]) -> Vec<u8> {
+        // RGBA PNG with whatever compressed data is given, in a single IDAT chunk
+        let mut png: Vec<u8> = PNG_SIGNATURE.to_vec();
+        let mut header: Vec<u8> = Vec::new();
+
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", idat);
+        write_chunk(&mut png, b"IEND", &[]);
+
+        return png;
+    }
+
+    fn png_with_deflate(fields: &[(u32, u32)]) -> Vec<u8> {
+        // 2x2 PNG with a zlib stream around a deflate stream of (value, bit count) fields, packed from the lowest bit up;
+        // the checksum is left empty, the decoder should give up before it
+        let mut stream: Vec<u8> = vec![0x78, 0x01];
+        let mut bit: usize = 0;
+
+        for &(value, count) in fields {
+            for k in 0..count {
+                if bit % 8 == 0 {
+                    stream.push(0);
+                }
+
+                *stream.last_mut().unwrap() |= (((value >> k) & 1) as u8) << (bit % 8);
+                bit += 1;
+            }
+        }
+
+        stream.extend_from_slice(&[0; 4]);
+
+        return png_with_idat(2, 2, &stream);
+    }
+
+    fn huffman_code(code: u32, length: u32) -> (u32, u32) {
+        // Huffman codes are packed from their highest bit
+        return (code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn assert_invalid(bytes: &[u8], what: &str) {
+        assert!(matches!(decode_png(bytes), Err(Error::InvalidImage { .. })), "{}", what);
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let canvas: RGBACanvas = test_canvas(13, 7, false);
+        let decoded: RGBACanvas = decode_png(&encode_png(&canvas)).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (13, 7));
+        assert_eq!(decoded.data, canvas.data);
+    }
+
+    #[test]
+    fn p6_round_trip() {
+        let canvas: RGBACanvas = test_canvas(9, 11, true);
+        let decoded: RGBACanvas = decode_ppm(&encode_ppm(&canvas)).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (9, 11));
+        assert_eq!(decoded.data, canvas.data);
+    }
+
+    #[test]
+    fn p3_round_trip() {
+        let canvas: RGBACanvas = test_canvas(5, 3, true);
+        let decoded: RGBACanvas = decode_ppm(&encode_p3(&canvas)).unwrap();
+
+        assert_eq!(max_difference(&decoded, &canvas), Some(0));
+    }
+
+    #[test]
+    fn truncated_images_are_errors() {
+        let canvas: RGBACanvas = test_canvas(6, 4, true);
+        let png: Vec<u8> = encode_png(&canvas);
+        let p6: Vec<u8> = encode_ppm(&canvas);
+        let p3: Vec<u8> = encode_p3(&canvas);
+
+        for length in [0, 5, 8, 20, 40, png.len() / 2, png.len() - 13, png.len() - 1] {
+            assert!(decode_png(&png[..length]).is_err(), "PNG cut at {} bytes", length);
+        }
+
+        for length in [0, 2, 10, p6.len() / 2, p6.len() - 1] {
+            assert!(decode_ppm(&p6[..length]).is_err(), "P6 cut at {} bytes", length);
+        }
+
+        // the last sample cut off entirely
+        let last_line: usize = p3[..p3.len() - 1].iter().rposition(|&byte| byte == b'\n').unwrap();
+        let without_last_samples: &[u8] = &p3[..last_line + 1];
+        assert!(decode_ppm(without_last_samples).is_err());
+    }
+
+    #[test]
+    fn oversized_images_are_errors() {
+        // a short header can't make the decoder allocate the canvas it asks for
+        assert!(decode_ppm(b"P3 16384 16384 255\n0 0 0").is_err());
+        assert!(decode_ppm(b"P6 16384 16384 255\n\0\0\0").is_err());
+        assert!(decode_ppm(b"P6 100000 100000 255\n").is_err());
+
+        // more data than a 1x1 image needs
+        assert!(decode_png(&png_with_data(1, 1, &[0; 1000])).is_err());
+        assert!(decode_png(&png_with_data(1, 1, &[0, 1, 2, 3, 4])).is_ok());
+        assert!(decode_png(&png_with_data(1 << 15, 1 << 15, &[0; 100])).is_err());
+    }
+
+    #[test]
+    fn corrupt_pngs_are_errors() {
+        // final dynamic block, 257 literal and 1 distance codes, all 19 code length codes one bit long
+        let mut over_subscribed: Vec<(u32, u32)> = vec![(1, 1), (2, 2), (0, 5), (0, 5), (15, 4)];
+        over_subscribed.extend_from_slice(&[(1, 3); 19]);
+        assert_invalid(&png_with_deflate(&over_subscribed), "over-subscribed code length code");
+
+        // final fixed block: a copy of 3 bytes from 1 back, with nothing before it
+        assert_invalid(&png_with_deflate(&[(1, 1), (1, 2), huffman_code(1, 7), huffman_code(0, 5)]), "copy from an empty output");
+
+        // 'a', then a copy from 2 back
+        assert_invalid(
+            &png_with_deflate(&[(1, 1), (1, 2), huffman_code(0x30 + b'a' as u32, 8), huffman_code(1, 7), huffman_code(1, 5)]),
+            "copy from before the start",
+        );
+
+        // distance code 30 has no distance
+        assert_invalid(
+            &png_with_deflate(&[(1, 1), (1, 2), huffman_code(0x30 + b'a' as u32, 8), huffman_code(1, 7), huffman_code(30, 5)]),
+            "distance code 30",
+        );
+
+        // IDAT chunks that are fine by themselves, with the compressed data cut short
+        let raw: Vec<u8> = vec![0; 2 * (1 + 2 * 4)];
+        let idat: Vec<u8> = zlib_stored(&raw);
+
+        for length in [0, 2, 6, idat.len() / 2, idat.len() - 4, idat.len() - 1] {
+            assert_invalid(&png_with_idat(2, 2, &idat[..length]), &format!("IDAT cut at {} bytes", length));
+        }
+
+        // a changed byte in the CRC of IHDR, and in IDAT data under its CRC
+        let png: Vec<u8> = encode_png(&test_canvas(3, 2, false));
+        let ihdr_crc_at: usize = 8 + 8 + 13;
+        let idat_data_at: usize = ihdr_crc_at + 4 + 8;
+
+        for at in [ihdr_crc_at, ihdr_crc_at + 3, idat_data_at + 3] {
+            let mut corrupt: Vec<u8> = png.clone();
+            corrupt[at] ^= 0x10;
+            assert_invalid(&corrupt, &format!("byte {} changed", at));
+        }
+    }
+
+    fn lzw_decompress(bytes: &[u8]) -> Vec<u8> {
+        // GIF decoder side: codes from the lowest bit up, one bit longer when the table fills the current size
+        let mut output: Vec<u8> = Vec::new();
+        let mut table: Vec<Vec<u8>> = (0..258).map(|index| vec![index as u8]).collect();
+        let mut previous: Option<Vec<u8>> = None;
+        let mut code_size: usize = 9;
+        let mut bit: usize = 0;
+
+        while bit + code_size <= bytes.len() * 8 {
+            let mut code: usize = 0;
+
+            for k in 0..code_size {
+                code |= ((bytes[(bit + k) / 8] >> ((bit + k) % 8)) as usize & 1) << k;
+            }
+
+            bit += code_size;
+
+            if code == 256 {
+                table.truncate(258);
+                code_size = 9;
+                previous = None;
+                continue;
+            } else if code == 257 {
+                return output;
+            }
+
+            let entry: Vec<u8> = match &previous {
+                Some(previous) if code == table.len() => [previous.clone(), vec![previous[0]]].concat(),
+                _ => table[code].clone(),
+            };
+
+            if let Some(previous) = &previous {
+                if table.len() < GIF_MAX_CODES as usize {
+                    table.push([previous.clone(), vec![entry[0]]].concat());
+                }
+            }
+
+            if table.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+
+            output.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+
+        panic!("no end code");
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        // long enough for 12 bit codes and a few table clears, repetitive enough for long codes
+        let mut indices: Vec<u8> = Vec::new();
+
+        for i in 0..60000usize {
+            indices.push(if (i / 300) % 2 == 0 {(i % 7) as u8} else {((i * i * 31 + i / 3) % 251) as u8});
+        }
+
+        assert_eq!(lzw_decompress(&lzw_compress(&indices)), indices);
+        assert_eq!(lzw_decompress(&lzw_compress(&[])), Vec::<u8>::new());
+        assert_eq!(lzw_decompress(&lzw_compress(&[5])), vec![5]);
+    }
+
+    fn gif_frames(bytes: &[u8]) -> Vec<Vec<[u8; 3]>> {
+        // colors of every pixel of every frame, for GIFs as GifWriter writes them: local palettes, 8 bit codes
+        let mut frames: Vec<Vec<[u8; 3]>> = Vec::new();
+        let mut position: usize = 13;
+
+        let sub_blocks = |position: &mut usize| -> Vec<u8> {
+            let mut data: Vec<u8> = Vec::new();
+
+            while bytes[*position] != 0 {
+                let length: usize = bytes[*position] as usize;
+                data.extend_from_slice(&bytes[*position + 1..*position + 1 + length]);
+                *position += 1 + length;
+            }
+
+            *position += 1;
+
+            return data;
+        };
+
+        loop {
+            match bytes[position] {
+                0x21 => {
+                    position += 2;
+                    sub_blocks(&mut position);
+                }
+                0x2c => {
+                    let palette_size: usize = 2 << (bytes[position + 9] & 0x07);
+                    let palette: &[u8] = &bytes[position + 10..position + 10 + palette_size * 3];
+
+                    position += 10 + palette_size * 3 + 1;
+
+                    let indices: Vec<u8> = lzw_decompress(&sub_blocks(&mut position));
+                    frames.push(indices.iter().map(|&index| {
+                        let at: usize = index as usize * 3;
+                        return [palette[at], palette[at + 1], palette[at + 2]];
+                    }).collect());
+                }
+                _ => return frames,
+            }
+        }
+    }
+
+    #[test]
+    fn gif_frames_with_many_colors_stay_close() {
+        // 4096 colors in a gradient, a frame with few colors keeps them exactly
+        let mut gradient: RGBACanvas = RGBACanvas::new(64, 64).unwrap();
+        let few_colors: RGBACanvas = test_canvas(5, 5, true);
+
+        for y in 0..64 {
+            for x in 0..64 {
+                let at: usize = (y * 64 + x) * 4;
+                gradient.data[at..at + 4].copy_from_slice(&[(x * 4) as u8, (y * 4) as u8, 128, 255]);
+            }
+        }
+
+        let path: PathBuf = std::env::temp_dir().join(format!("moving_agent_colors_{}.gif", std::process::id()));
+
+        let mut gif: GifWriter = GifWriter::create(&path, 64, 64, 0.1).unwrap();
+        gif.add_frame(&gradient).unwrap();
+        gif.finish().unwrap();
+
+        let frames: Vec<Vec<[u8; 3]>> = gif_frames(&fs::read(&path).unwrap());
+        assert_eq!(frames.len(), 1);
+
+        let mut max_error: i32 = 0;
+        let mut total_error: i32 = 0;
+
+        for i in 0..64 * 64 {
+            for k in 0..3 {
+                let error: i32 = (frames[0][i][k] as i32 - gradient.data[i * 4 + k] as i32).abs();
+                max_error = i32::max(max_error, error);
+                total_error += error;
+            }
+        }
+
+        // 256 boxes of 4x4 colors 4 apart, off by 6 at most and by 10 over the three channels of a pixel on average
+        assert!(max_error <= 8, "max error {}", max_error);
+        assert!(total_error <= 64 * 64 * 10, "total error {}", total_error);
+
+        let mut gif: GifWriter = GifWriter::create(&path, 5, 5, 0.1).unwrap();
+        gif.add_frame(&few_colors).unwrap();
+        gif.finish().unwrap();
+
+        let frames: Vec<Vec<[u8; 3]>> = gif_frames(&fs::read(&path).unwrap());
+
+        for i in 0..25 {
+            assert_eq!(frames[0][i][..], few_colors.data[i * 4..i * 4 + 3]);
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn gif_delays_keep_up_with_the_frame_time() {
+        let path: PathBuf = std::env::temp_dir().join(format!("moving_agent_delays_{}.gif", std::process::id()));
+        let mut gif: GifWriter = GifWriter::create(&path, 4, 4, 0.0166667).unwrap();
+
+        let delays: Vec<u16> = (0..6).map(|_| gif.next_delay_cs()).collect();
+
+        // 1.67 cs frames, rounding each of them to 2 would play 20% slow
+        assert_eq!(delays, vec![2, 1, 2, 2, 1, 2]);
+
+        for _ in 6..600 {
+            gif.next_delay_cs();
+        }
+
+        assert_eq!(gif.delay_written_cs, 1000);
+
+        gif.finish().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn inflate_stored_block() {
+        // final block, no compression: length 5, its complement, then the bytes as they are
+        let compressed: [u8; 10] = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+
+        assert_eq!(inflate(&compressed, 100).unwrap(), (b"hello".to_vec(), 10));
+        assert!(inflate(&compressed, 4).is_err());
+        assert!(inflate(&compressed[..8], 100).is_err());
+    }
+
+    #[test]
+    fn inflate_fixed_huffman_block() {
+        // "hello hello hello!" with the fixed codes, the repeats are copies of earlier output
+        let compressed: [u8; 11] = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x8a, 0x00];
+
+        let (data, used): (Vec<u8>, usize) = inflate(&compressed, 100).unwrap();
+
+        assert_eq!(data, b"hello hello hello!".to_vec());
+        assert_eq!(used, compressed.len());
+        assert!(inflate(&compressed, 17).is_err());
+    }
+}
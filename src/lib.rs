@@ -0,0 +1,48 @@
+// moving_agent as a library:
+// world building (shapes, walls, textures), agent simulation and software rendering
+// into RGBA canvases. Frontends (the FLTK window in main.rs, or anything else)
+// are built on top of this. Nothing here depends on a GUI toolkit.
+
+pub mod error;
+pub mod common_structs;
+pub mod vector_2d;
+pub mod linear_texture;
+pub mod rgba_canvas;
+pub mod ellipse;
+pub mod shape;
+pub mod agent;
+pub mod controller;
+pub mod wall_grid;
+pub mod collision;
+pub mod dynamic_object;
+pub mod world;
+pub mod simulation;
+pub mod view;
+pub mod backdrop;
+pub mod loading;
+pub mod image_io;
+pub mod recording;
+pub mod headless;
+pub mod replay;
+pub mod snapshot;
+pub mod editor;
+
+pub use error::{Error, Result};
+pub use agent::{Action, Agent, CollisionFlags, CollisionModel, Direction};
+pub use controller::{Controller, ControllerDescription, Senses};
+pub use common_structs::{Angle, Coord, Dot, Marker, Palette, RGBAColor};
+pub use linear_texture::LinearTexture;
+pub use rgba_canvas::RGBACanvas;
+pub use shape::{Shape, ShapeDescription, WorldSetup};
+pub use vector_2d::Vector2D;
+pub use wall_grid::WallGrid;
+pub use dynamic_object::{DynamicObject, DynamicObjectDescription, Motion};
+pub use world::World;
+pub use simulation::{Simulation, DELTA_T};
+pub use view::{TopViewCamera, ViewMode};
+pub use backdrop::TopViewBackdrop;
+pub use recording::{Recorder, RecordingFormat, RecordingOptions};
+pub use headless::{HeadlessRunner, TimedAction, AgentState};
+pub use replay::ActionLog;
+pub use snapshot::Snapshot;
+pub use editor::WorldEditor;
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::common_structs::RGBAColor;
 
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub enum TextureBodyType {
   Sin,
   Lin,
@@ -10,7 +10,7 @@ pub enum TextureBodyType {
   None,
 }
 
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub enum TextureEdgeType {
   Step,
   Lin,
@@ -18,7 +18,7 @@ pub enum TextureEdgeType {
   None,
 }
 
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub struct LinearTexture {
   main_color: RGBAColor,
   edge_color: RGBAColor,
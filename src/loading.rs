@@ -0,0 +1,55 @@
+// reading RON files (world setups, scripts) without panicking:
+// I/O problems and parse errors are reported back to the caller,
+// parse errors together with line and column in the file;
+// writing goes the other way (action logs), in the same readable form
+
+use std::{fs, io, path::Path};
+
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{Error, Result};
+
+pub fn load_ron_file<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let contents: String = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) => {
+            return Err(Error::Io { path: path.to_path_buf(), error });
+        }
+    };
+
+    match ron::from_str(&contents) {
+        Ok(value) => {
+            return Ok(value);
+        }
+        Err(spanned_error) => {
+            return Err(Error::RonParse {
+                path: path.to_path_buf(),
+                line: spanned_error.position.line,
+                col: spanned_error.position.col,
+                message: spanned_error.code.to_string(),
+            });
+        }
+    }
+}
+
+pub fn save_ron_file<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let contents: String = match ron::ser::to_string_pretty(value, PrettyConfig::new()) {
+        Ok(contents) => contents,
+        Err(error) => {
+            return Err(Error::Write {
+                path: path.to_path_buf(),
+                error: io::Error::new(io::ErrorKind::InvalidData, error.to_string()),
+            });
+        }
+    };
+
+    match fs::write(path, contents) {
+        Ok(()) => {
+            return Ok(());
+        }
+        Err(error) => {
+            return Err(Error::Write { path: path.to_path_buf(), error });
+        }
+    }
+}
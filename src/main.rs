@@ -17,419 +17,298 @@ Moving Agent features to add (with no particular order):
   
   1) pan and zoom for top-view +
   2) move rendering (raster creation) of top-view and first-person view into separate threads
-  3) add smooth transitions for agent movements
+  3) add smooth transitions for agent movements +
   4) add agent collisions with lines and polygons in world +
   5) make prettier agent +
-  7) create dot display modes i.e. display only polygon vertices:
-   -- 2d "wireframe",
-   -- 2d "wireframe with transparent surfaces"
-   -- 2d "occluded wireframe"
-  8) add stereo modes (parallel-eye/cross-eye and anaglyph)
-  9) world segmentation (this is to decrease computational load for collisions and renderings)
+  7) create dot display modes i.e. display only polygon vertices: +
+   -- 2d "wireframe", +
+   -- 2d "wireframe with transparent surfaces" +
+   -- 2d "occluded wireframe" +
+  8) add stereo modes (parallel-eye/cross-eye and anaglyph) +
+  9) world segmentation (this is to decrease computational load for collisions and renderings) +
   11) investigate openGL api
   
    */
 
-use std::{path::Path, fs::File, io::Read};
-
-use agent::{Agent, Direction};
-use common_structs::{
-    Coord,
-    Angle,
-    RGBAColor, Palette,
-};
-use fltk::{
-    app::{self, App, MouseButton, MouseWheel},
-    enums::{self, ColorDepth, FrameType, Event, Cursor},
-    image::RgbImage,
-    prelude::*,
-    *,
+use std::{path::Path, process};
+
+use moving_agent::{
+    dynamic_object::DynamicObject,
+    headless::{AgentState, HeadlessRunner, TimedAction},
+    image_io,
+    recording::Recorder,
+    replay::ActionLog,
+    rgba_canvas::RGBACanvas,
+    shape::{Shape, WorldSetup},
+    simulation::Simulation,
+    snapshot::Snapshot,
+    view,
 };
 
+use cli::{CliCommand, ViewOptions};
 
-use rgba_canvas::RGBACanvas;
-use shape::{Shape, WorldSetup};
-use world::World;
-
-use crate::shape::ShapeDescription;
-
-mod common_structs;
-mod vector_2d;
-mod linear_texture;
-mod rgba_canvas;
-mod ellipse;
-mod shape;
-mod agent;
-mod world;
-
-const WIND_LABEL: &str = "Moving Agent";
-const WIND_WIDTH: i32 = 1000;
-const WIND_HEIGHT: i32 = 720;
-// const MENU_HEIGHT: i32 = 32;
-const MENU_HEIGHT: i32 = 0;
-
-const DELTA_T: f64 = 0.0166667;
-
-#[derive(Clone)]
-enum Message {
-    Quit,
-    MouseDown(i32, i32, MouseButton),
-    MouseDrag(i32, i32),
-    MouseMove(i32),
-    MouseReleased(i32, i32, MouseButton),
-    Tick,
-    KeyPress(char),
-    WindowResize,
-    ToggleFullScreen,
-}
-
-#[derive(Debug)]
-enum ViewMode {
-    Top,
-    FirstPerson,
-}
+mod cli;
+#[cfg(feature = "gui")]
+mod gui;
 
 fn main() {
-    let mut view_mode: ViewMode = ViewMode::Top;
-    let mut is_full_screen: bool = false;
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    let initialization_data: WorldSetup = get_init_data_from_file().unwrap();
+    let command: CliCommand = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, cli::USAGE);
+            process::exit(2);
+        }
+    };
 
-    let mut world: World = world::World::new();
-    add_shapes_to_world(&mut world, initialization_data.world_shapes);
+    match command {
+        CliCommand::Help => {
+            println!("{}", cli::USAGE);
+        }
+        CliCommand::Run { world, options } => {
+            if let Some(snapshot_path) = &options.restore {
+                // a restored simulation is taken as it was saved, without command line changes
+                run_gui(Simulation::from_snapshot(load_snapshot(snapshot_path)), &options, None, None);
+                return;
+            }
 
-    let mut agent: Agent = Agent::new(
-        initialization_data.initial_coord,
-        Angle::new_deg(initialization_data.initial_angle_deg),
-        Angle::new_deg(initialization_data.agents_field_of_view_deg),
-    );
+            // a replay starts from the logged world as it is, without command line changes
+            let (initialization_data, mut simulation): (WorldSetup, Simulation) = match &options.replay {
+                Some(log_path) => {
+                    let replayed: ActionLog = load_action_log(log_path);
+                    let simulation: Simulation = exit_on_error(replayed.replay_simulation());
 
-    agent.update_visible_walls(world.walls.clone());
+                    (replayed.setup, simulation)
+                }
+                None => {
+                    let mut initialization_data: WorldSetup = load_setup(&world);
+                    options.apply_to_setup(&mut initialization_data);
+                    let simulation: Simulation = create_simulation(&initialization_data);
 
-    let application: App = app::App::default();
+                    (initialization_data, simulation)
+                }
+            };
 
-    let (s, r) = app::channel();
+            let action_log: Option<ActionLog> = options.log_actions
+                .as_ref()
+                .map(|_| ActionLog::start(&initialization_data, &mut simulation));
 
-    let mut mouse_x: i32 = -1;
-    let mut mouse_dx: i32 = 0;
+            run_gui(simulation, &options, action_log, Some(initialization_data));
+        }
+        CliCommand::Validate { world } => {
+            validate(&world);
+        }
+        CliCommand::Render { world, out, options } => {
+            let mut initialization_data: WorldSetup = load_setup(&world);
+            options.apply_to_setup(&mut initialization_data);
 
-    let mut wind = window::Window::new(0, 0, WIND_WIDTH, WIND_HEIGHT, WIND_LABEL);
+            render(&create_simulation(&initialization_data), &options, &out);
+        }
+        CliCommand::Headless { world, script, ticks, options } => {
+            match (&world, &options.restore) {
+                (_, Some(snapshot_path)) => {
+                    let runner: HeadlessRunner = HeadlessRunner::from_simulation(Simulation::from_snapshot(load_snapshot(snapshot_path)));
 
-    let mut menu = menu::SysMenuBar::default().with_size(wind.width(), MENU_HEIGHT);
-    menu.set_frame(enums::FrameType::FlatBox);
-    menu.set_color(enums::Color::Light2);
+                    run_headless(runner, None, script.as_deref(), ticks, &options);
+                }
+                (Some(world), None) => {
+                    let mut initialization_data: WorldSetup = load_setup(world);
+                    options.apply_to_setup(&mut initialization_data);
 
-    menu.add_emit(
-        "&File/Quit\t",
-        enums::Shortcut::Ctrl | 'q',
-        menu::MenuFlag::Normal,
-        s.clone(),
-        Message::Quit,
-    );
+                    let runner: HeadlessRunner = exit_on_error(HeadlessRunner::new(&initialization_data));
 
-    menu.add_emit(
-        "&View/FullScreen\t",
-        enums::Shortcut::Ctrl | 'z',
-        menu::MenuFlag::Normal,
-        s.clone(),
-        Message::ToggleFullScreen,
-    );
+                    run_headless(runner, Some(&initialization_data), script.as_deref(), ticks, &options);
+                }
+                (None, None) => unreachable!("headless without a world is only parsed together with --restore"),
+            }
+        }
+        CliCommand::Replay { log, ticks, options } => {
+            replay(&load_action_log(&log), ticks, &options);
+        }
+    }
+}
 
-    let mut top_view_frame = frame::Frame::default()
-        .with_pos(
-            0,
-            MENU_HEIGHT,
-        )
-        .with_size(WIND_WIDTH, WIND_HEIGHT - MENU_HEIGHT);
+fn load_setup(path: &Path) -> WorldSetup {
+    return exit_on_error(WorldSetup::from_file(path));
+}
 
-    wind.make_resizable(true);
-    wind.end();
-    wind.show();
+fn load_snapshot(path: &Path) -> Snapshot {
+    return exit_on_error(Snapshot::load(path));
+}
 
-    // intercept keyboard events on the window
-    let key_interceptor_sender =s.clone();
-    let mut chars_vec: Vec<char> = Vec::new();
-    wind.handle(move |_, event| match event {
-        Event::Resize => {
-            // println!("Resize event: {:?}", app::event());
+fn load_action_log(path: &Path) -> ActionLog {
+    return exit_on_error(ActionLog::load(path));
+}
 
-            key_interceptor_sender.send(Message::WindowResize);
-            // key_interceptor_sender.send(Message::Tick);
+fn create_simulation(initialization_data: &WorldSetup) -> Simulation {
+    return exit_on_error(Simulation::new(initialization_data));
+}
 
-            false
-        }
-        Event::KeyDown => {
-            match app::event_key().to_char() {
-                Some(char) => {
-                    // println!("Key pressed: {:?}", char);
-                    key_interceptor_sender.send(Message::KeyPress(char));
-                    chars_vec.push(char);
-                }
-                None => {
-                    // nothing
-                }
-            };
-            false
+fn exit_on_error<T>(result: moving_agent::Result<T>) -> T {
+    match result {
+        Ok(value) => {
+            return value;
         }
-        Event::KeyUp => {
-            match app::event_key().to_char() {
-                Some(char) => {
-                    // key_interceptor_sender.send(Message::KeyPress(char));
-                    // println!("Key released: {:?}", char);
-                }
-                None => {
-                    // nothing
-                }
-            };
-            false
-        }
-        Event::Move => {
-            let current_x = app::event_x();
-
-            key_interceptor_sender.send(Message::MouseMove(current_x));
-
-            true
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
         }
+    }
+}
 
-        _ => false,
-    });
+#[cfg(feature = "gui")]
+fn run_gui(simulation: Simulation, options: &ViewOptions, action_log: Option<ActionLog>, setup: Option<WorldSetup>) {
+    gui::run(simulation, options, action_log, setup);
+}
 
-    let callback_sender = s.clone();
-    
-    let callback = move |handle| {
-        callback_sender.send(Message::Tick);
-        
-        app::repeat_timeout3(DELTA_T, handle);
-    };
+#[cfg(not(feature = "gui"))]
+fn run_gui(_simulation: Simulation, _options: &ViewOptions, _action_log: Option<ActionLog>, _setup: Option<WorldSetup>) {
+    eprintln!("moving_agent was built without the \"gui\" feature, use validate, render or headless commands");
+    process::exit(1);
+}
 
-    app::add_timeout3(DELTA_T, callback);
-    
-
-    let top_view_frame_handle_sender = s.clone();
-    top_view_frame.handle(move |_, event: Event| {
-        match event {
-            Event::Push => {
-                let x = app::event_x();
-                let y = app::event_y() - MENU_HEIGHT;
-                let button = app::event_mouse_button();
-                top_view_frame_handle_sender.send(Message::MouseDown(x, y, button));
-                true
-            }
-            Event::Drag => {
-                let x = app::event_x();
-                let y = app::event_y() - MENU_HEIGHT;
-                if x >= 0 && x < WIND_WIDTH && y >= 0 && y < WIND_HEIGHT - MENU_HEIGHT {
-                    top_view_frame_handle_sender.send(Message::MouseDrag(x, y));
-                }
-                true
-            }
-            Event::Released => {
-                let x = app::event_x();
-                let y = app::event_y() - MENU_HEIGHT;
-                let button = app::event_mouse_button();
-                top_view_frame_handle_sender.send(Message::MouseReleased(x, y, button));
-                true
+fn validate(path: &Path) {
+    let setup: WorldSetup = load_setup(path);
+    let mut problems: usize = 0;
+    let mut walls: usize = 0;
+
+    // report every broken shape, object and agent, not only the first one
+    for descr in setup.world_shapes.iter() {
+        match Shape::from_descr(descr) {
+            Ok(shape) => walls += shape.elements.len(),
+            Err(error) => {
+                eprintln!("{}: {}", path.display(), error);
+                problems += 1;
             }
-            _ => false,
         }
-    });
-
-    while application.wait() {
-        if let Some(msg) = r.recv() {
-            match msg {
-                Message::Quit => {
-                    println!("quitting the app...");
-                    fltk::app::quit();
-                }
-                Message::Tick => {
-                    match view_mode {
-                        ViewMode::FirstPerson => draw_fisrt_person_view(
-                            &mut agent,
-                            &mut top_view_frame,
-                        ),
-                        ViewMode::Top => draw_top_view(&mut world, &agent, &mut top_view_frame),
-                    }
-                }
-                Message::MouseDown(x, y, button) => {
-                    println!("The image was clicked at coordinates x={}, y={}", x, y);
-                }
-                Message::MouseMove(current_x) => {
-                    if mouse_x != -1 {
-                        mouse_dx = current_x - mouse_x;
-                    }
-                    mouse_x = current_x;
-
-                    if mouse_dx != 0 {
-                        agent.turn_sideways((mouse_dx as f32) / 3.0);
-
-                        world.is_updated = true;
-                        agent.is_updated = true;
-                    }
-                    
-                }
-                Message::KeyPress(key_char) => {
-                    match key_char {
-                        'w' => {
-                            agent.agent_move(Direction::Forward);
-
-                            world.is_updated = true;
-                            agent.is_updated = true;
-                        }
-                        's' => {
-                            agent.agent_move(Direction::Backward);
-
-                            world.is_updated = true;
-                            agent.is_updated = true;
-                        }
-                        'd' => {
-                            agent.agent_move(Direction::Right);
-
-                            world.is_updated = true;
-                            agent.is_updated = true;
-                        }
-                        'a' => {
-                            agent.agent_move(Direction::Left);
-
-                            world.is_updated = true;
-                            agent.is_updated = true;
-                        }
-                        'e' => {
-                            // rotate right
-                            agent.turn_sideways(5.0);
-                            world.is_updated = true;
-                            agent.is_updated = true;
-                        }
-                        'q' => {
-                            // rotate left
-                            agent.turn_sideways(-5.0);
-                            world.is_updated = true;
-                            agent.is_updated = true;
-                        }
-                        'v' => {
-                            match view_mode {
-                                ViewMode::Top => view_mode = ViewMode::FirstPerson,
-                                ViewMode::FirstPerson => view_mode = ViewMode::Top,
-                            }
-                            world.is_updated = true;
-                            agent.is_updated = true;
-                        }
-                        _ => {}
-                    }
-                }
-                Message::WindowResize => {
-                    world.is_updated = true;
-                    agent.is_updated = true;
-                }
-                Message::ToggleFullScreen => {
-                    is_full_screen = !is_full_screen;
-
-                    wind.fullscreen(is_full_screen);
+    }
 
-                    if is_full_screen {
-                        wind.set_cursor(Cursor::None);
-                    } else {
-                        wind.set_cursor(Cursor::Default);
-                    }
-                    
-                }
-                _ => {
-                    // println!("yet undefined event");
-                }
-            };
+    for i in 0..setup.dynamic_objects.len() {
+        if let Err(error) = DynamicObject::from_descr(i, &setup.dynamic_objects[i]) {
+            eprintln!("{}: dynamic object {}: {}", path.display(), i, error);
+            problems += 1;
         }
     }
 
-    application.run().unwrap();
-}
+    if let Err(error) = setup.validate() {
+        eprintln!("{}: {}", path.display(), error);
+        problems += 1;
+    }
 
+    if problems > 0 {
+        process::exit(1);
+    }
 
-fn draw_top_view(world: &mut World, agent: &Agent, top_view_frame: &mut frame::Frame) {
-    if world.is_updated {
-        let rendered_scene: RGBACanvas = world.render_top_view(
-            &agent.shape,
-            agent.center,
-            50.0,
-            top_view_frame.width(),
-            top_view_frame.height(),
-        );
-
-        let image = unsafe { RgbImage::from_data(
-            &rendered_scene.data,
-            rendered_scene.width,
-            rendered_scene.height,
-            ColorDepth::Rgba8,
-        )
-        .unwrap() };
-
-        top_view_frame.set_image(Some(image));
-        top_view_frame.redraw();
-
-        world.is_updated = false;
+    println!(
+        "{}: ok, {} shapes, {} walls, {} dynamic objects, {} agents",
+        path.display(),
+        setup.world_shapes.len(),
+        walls,
+        setup.dynamic_objects.len(),
+        setup.other_agents.len() + 1,
+    );
+}
+
+fn render(simulation: &Simulation, options: &ViewOptions, out: &Path) {
+    let canvas: RGBACanvas = exit_on_error(view::render_view(
+        simulation,
+        options.view_mode,
+        options.scale,
+        options.top_view_mode,
+        options.width,
+        options.height,
+    ));
+
+    if let Err(error) = image_io::write_image(&canvas, out) {
+        eprintln!("{}", error);
+        process::exit(1);
     }
 }
 
-fn draw_fisrt_person_view(agent: &mut Agent, first_person_view_frame: &mut frame::Frame) {
-    if agent.is_updated {
-        let agent_line_view: Vec<RGBAColor> = agent.get_view(first_person_view_frame.width());
-        let mut agent_view: RGBACanvas = RGBACanvas::new(
-            first_person_view_frame.width(),
-            first_person_view_frame.height(),
-        );
-
-        for j in 0..first_person_view_frame.height() {
-            for i in 0..first_person_view_frame.width() {
-                agent_view.put_pixel_simple(i, j, agent_line_view[i as usize]);
-            }
-        }
-    
-        let image: RgbImage = unsafe { RgbImage::from_data(
-            &agent_view.data,
-            agent_view.width,
-            agent_view.height,
-            ColorDepth::Rgba8,
-        )
-        .unwrap() };
-    
-        first_person_view_frame.set_image(Some(image));
-        first_person_view_frame.redraw();
-
-        agent.is_updated = false;
+fn run_headless(
+    mut runner: HeadlessRunner,
+    initialization_data: Option<&WorldSetup>, // none for a restored simulation
+    script_path: Option<&Path>,
+    ticks: Option<u64>,
+    options: &ViewOptions,
+) {
+    let script: Vec<TimedAction> = match script_path {
+        Some(script_path) => exit_on_error(HeadlessRunner::load_script(script_path)),
+        None => Vec::new(),
+    };
+
+    // by default run until the last scripted action has been applied
+    let start_tick: u64 = runner.simulation.tick;
+    let ticks: u64 = ticks.unwrap_or(
+        script.iter().map(|timed_action| (timed_action.tick + 1).saturating_sub(start_tick)).max().unwrap_or(0),
+    );
+
+    runner.set_script(script);
+
+    let mut action_log: Option<ActionLog> = match (initialization_data, &options.log_actions) {
+        (Some(initialization_data), Some(_)) => Some(ActionLog::start(initialization_data, &mut runner.simulation)),
+        _ => None,
+    };
+
+    let states: Vec<AgentState> = run_maybe_recorded(&mut runner, ticks, options);
+
+    if let (Some(action_log), Some(log_path)) = (action_log.as_mut(), &options.log_actions) {
+        action_log.finish(&mut runner.simulation);
+        exit_on_error(action_log.save(log_path));
+    }
+
+    if let Some(snapshot_path) = &options.save_snapshot {
+        exit_on_error(runner.simulation.snapshot().save(snapshot_path));
     }
+
+    print_states(&states);
 }
 
-fn add_shapes_to_world(world: &mut World, shape_descriptions: Vec<ShapeDescription>) {
-    let mut shapes: Vec<Shape> = Vec::new();
+fn replay(action_log: &ActionLog, ticks: Option<u64>, options: &ViewOptions) {
+    // repeats the logged run, then tells whether the agents ended where they did in the log
+    let ticks: u64 = ticks.unwrap_or(action_log.ticks);
+
+    let mut runner: HeadlessRunner = exit_on_error(action_log.replay_runner());
+
+    let states: Vec<AgentState> = run_maybe_recorded(&mut runner, ticks, options);
 
-    for i in 0..shape_descriptions.len() {
-        shapes.push(Shape::from_coord_list(
-            String::from(&shape_descriptions[i].name),
-            shape_descriptions[i].vertices.clone(),
-            shape_descriptions[i].texture,
-        ).unwrap());
+    print_states(&states);
 
-        shapes[i].shift(shape_descriptions[i].anchor);
+    if ticks != action_log.ticks {
+        eprintln!("replayed {} of {} logged ticks, final positions not checked", ticks, action_log.ticks);
+        return;
     }
 
-    world.add_shapes_as_walls(&shapes);
+    match action_log.first_difference(&runner.simulation) {
+        None => eprintln!("replay matches the log after {} ticks", ticks),
+        Some(agent_id) => {
+            eprintln!("replay differs from the log: agent {} doesn't end where it did", agent_id);
+            process::exit(1);
+        }
+    }
 }
 
-fn get_init_data_from_file() -> Option<WorldSetup> {
-    // Create a path to the desired file
-    let path = Path::new("data/world-shapes.ron");
-    let display = path.display();
+fn run_maybe_recorded(runner: &mut HeadlessRunner, ticks: u64, options: &ViewOptions) -> Vec<AgentState> {
+    match &options.record_directory {
+        Some(directory) => {
+            let mut recorder: Recorder = exit_on_error(Recorder::start(
+                options.recording_options(directory.clone(), options.width, options.height),
+            ));
+            let states: Vec<AgentState> = exit_on_error(runner.run_recorded(ticks, &mut recorder));
 
-    // Open the path in read-only mode, returns `io::Result<File>`
-    let mut file = match File::open(&path) {
-        Err(why) => panic!("couldn't open {}: {}", display, why),
-        Ok(file) => file,
-    };
+            exit_on_error(recorder.finish());
+
+            return states;
+        }
+        None => {
+            return runner.run(ticks);
+        }
+    }
+}
 
-    // Read the file contents into a string, returns `io::Result<usize>`
-    let mut s = String::new();
-    match file.read_to_string(&mut s) {
-        Err(why) => panic!("couldn't read {}: {}", display, why),
-        Ok(_) => {
-            return Some(ron::from_str(&s).unwrap());
-        },
+fn print_states(states: &[AgentState]) {
+    for state in states.iter() {
+        println!("{} {} {:.4} {:.4} {:.2}", state.tick, state.agent_id, state.center.x(), state.center.y(), state.angle_deg);
     }
 }
@@ -0,0 +1,193 @@
+// recording of a running simulation for demos and offline analysis:
+// every recorded tick the chosen views are rendered and written as numbered images
+// (<view>-000001.png, ...) or appended to one animated GIF per view (<view>.gif),
+// and frames.csv gets the time and the pose of every agent for each frame
+//
+// the same recorder is used by the window and by headless runs, it only needs the simulation
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, Result},
+    image_io::{self, GifWriter},
+    rgba_canvas::RGBACanvas,
+    simulation::{Simulation, DELTA_T},
+    view::{self, TopViewRenderMode, ViewMode, DEFAULT_TOP_VIEW_SCALE},
+};
+
+const SIDECAR_FILE: &str = "frames.csv";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordingFormat {
+    Png,
+    Ppm,
+    Gif,
+}
+
+impl RecordingFormat {
+    pub fn from_name(name: &str) -> Option<RecordingFormat> {
+        match name {
+            "png" => Some(RecordingFormat::Png),
+            "ppm" => Some(RecordingFormat::Ppm),
+            "gif" => Some(RecordingFormat::Gif),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RecordingOptions {
+    pub directory: PathBuf, // created if it doesn't exist
+    pub views: Vec<ViewMode>,
+    pub format: RecordingFormat,
+    pub width: i32,
+    pub height: i32,
+    pub scale: f32, // of the top view, kept within MIN_TOP_VIEW_SCALE..MAX_TOP_VIEW_SCALE
+    pub top_view_mode: TopViewRenderMode,
+    pub every: u64, // ticks between frames, 1 records every tick
+}
+
+impl RecordingOptions {
+    pub fn new(directory: PathBuf) -> RecordingOptions {
+        return RecordingOptions {
+            directory,
+            views: vec![ViewMode::Top],
+            format: RecordingFormat::Png,
+            width: 640,
+            height: 480,
+            scale: DEFAULT_TOP_VIEW_SCALE,
+            top_view_mode: TopViewRenderMode::Wireframe,
+            every: 1,
+        };
+    }
+}
+
+pub struct Recorder {
+    options: RecordingOptions,
+    gifs: Vec<GifWriter>, // one per view, for the GIF format
+    sidecar: BufWriter<File>,
+    frame: u64, // number of the next frame
+    last_tick: Option<u64>, // the same tick is never recorded twice
+}
+
+impl Recorder {
+    pub fn start(mut options: RecordingOptions) -> Result<Recorder> {
+        if options.every == 0 {
+            return Err(Error::InvalidRecording { reason: "frames should be recorded at least every 1 tick".to_string() });
+        }
+
+        // the same zoom limits as in the window
+        options.scale = view::clamp_top_view_scale(options.scale);
+
+        let directory: &Path = &options.directory;
+
+        fs::create_dir_all(directory).map_err(|error| write_error(directory, error))?;
+
+        let sidecar_path: PathBuf = directory.join(SIDECAR_FILE);
+        let mut sidecar: BufWriter<File> = BufWriter::new(File::create(&sidecar_path).map_err(|error| write_error(&sidecar_path, error))?);
+
+        writeln!(sidecar, "frame,tick,time_s,agent,x,y,angle_deg").map_err(|error| write_error(&sidecar_path, error))?;
+
+        let mut gifs: Vec<GifWriter> = Vec::new();
+
+        if options.format == RecordingFormat::Gif {
+            let frame_time: f64 = DELTA_T * options.every as f64;
+
+            for i in 0..options.views.len() {
+                let path: PathBuf = directory.join(format!("{}.gif", options.views[i].name()));
+                let gif: GifWriter = GifWriter::create(&path, options.width, options.height, frame_time)?;
+
+                gifs.push(gif);
+            }
+        }
+
+        return Ok(Recorder {
+            options,
+            gifs,
+            sidecar,
+            frame: 0,
+            last_tick: None,
+        });
+    }
+
+    pub fn options(&self) -> &RecordingOptions {
+        return &self.options;
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        return self.frame;
+    }
+
+    pub fn record(&mut self, simulation: &Simulation) -> Result<()> {
+        // called after every tick (and before the first one, for the starting state),
+        // ticks between frames are skipped
+        if simulation.tick % self.options.every != 0 || self.last_tick == Some(simulation.tick) {
+            return Ok(());
+        }
+
+        self.last_tick = Some(simulation.tick);
+
+        for i in 0..self.options.views.len() {
+            let view_mode: ViewMode = self.options.views[i];
+            let canvas: RGBACanvas = view::render_view(
+                simulation,
+                view_mode,
+                self.options.scale,
+                self.options.top_view_mode,
+                self.options.width,
+                self.options.height,
+            )?;
+
+            match self.options.format {
+                RecordingFormat::Gif => {
+                    self.gifs[i].add_frame(&canvas)?;
+                }
+                RecordingFormat::Png | RecordingFormat::Ppm => {
+                    let extension: &str = if self.options.format == RecordingFormat::Png {"png"} else {"ppm"};
+                    let path: PathBuf = self.options.directory.join(format!("{}-{:06}.{}", view_mode.name(), self.frame, extension));
+                    image_io::write_image(&canvas, &path)?;
+                }
+            }
+        }
+
+        let sidecar_path: PathBuf = self.options.directory.join(SIDECAR_FILE);
+
+        for agent in simulation.world.agents.iter() {
+            writeln!(
+                self.sidecar,
+                "{},{},{:.4},{},{:.4},{:.4},{:.2}",
+                self.frame,
+                simulation.tick,
+                simulation.elapsed_time(),
+                agent.id,
+                agent.center.x(),
+                agent.center.y(),
+                agent.angle().get_deg(),
+            ).map_err(|error| write_error(&sidecar_path, error))?;
+        }
+
+        self.frame += 1;
+
+        return Ok(());
+    }
+
+    pub fn finish(self) -> Result<()> {
+        // GIFs are only complete after this
+        for gif in self.gifs.into_iter() {
+            gif.finish()?;
+        }
+
+        let sidecar_path: PathBuf = self.options.directory.join(SIDECAR_FILE);
+        let mut sidecar: BufWriter<File> = self.sidecar;
+
+        return sidecar.flush().map_err(|error| write_error(&sidecar_path, error));
+    }
+}
+
+fn write_error(path: &Path, error: io::Error) -> Error {
+    return Error::Write { path: path.to_path_buf(), error };
+}
@@ -0,0 +1,207 @@
+// action logs: everything the agents did in a run, tick by tick, and playing it back
+//
+// a log holds the world setup the run started from and every action applied to
+// every agent (given by the user, a script or a controller) with its tick;
+// in the replay each agent gets a scripted controller with its own actions,
+// so the same Agent and World code repeats the run, and controllers (with their
+// random generators) are not needed any more
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controller::ScriptedController,
+    error::Result,
+    headless::{AgentState, HeadlessRunner, TimedAction},
+    loading::{load_ron_file, save_ron_file},
+    shape::WorldSetup,
+    simulation::Simulation,
+};
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ActionLog {
+    pub setup: WorldSetup,
+    pub ticks: u64, // length of the logged run
+    pub actions: Vec<TimedAction>, // in the order they were applied
+    #[serde(default)]
+    pub final_states: Vec<AgentState>, // where the agents ended, to check a replay against
+}
+
+impl ActionLog {
+    pub fn start(setup: &WorldSetup, simulation: &mut Simulation) -> ActionLog {
+        // the simulation should be fresh from the setup, still at tick 0
+        simulation.start_action_log();
+
+        return ActionLog {
+            setup: setup.clone(),
+            ticks: 0,
+            actions: Vec::new(),
+            final_states: Vec::new(),
+        };
+    }
+
+    pub fn finish(&mut self, simulation: &mut Simulation) {
+        // takes the logged actions over and notes where the run ended
+        self.actions.append(&mut simulation.take_action_log());
+        self.ticks = simulation.tick;
+        self.final_states = agent_states(simulation);
+    }
+
+    pub fn load(path: &Path) -> Result<ActionLog> {
+        return load_ron_file(path);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        return save_ron_file(path, self);
+    }
+
+    pub fn replay_simulation(&self) -> Result<Simulation> {
+        // the logged world, with every agent doing exactly what it did in the log
+        let mut simulation: Simulation = Simulation::new(&self.setup)?;
+
+        for agent_id in 0..simulation.agent_count() {
+            let script: Vec<TimedAction> = self.actions
+                .iter()
+                .filter(|timed_action| timed_action.agent == agent_id)
+                .copied()
+                .collect();
+
+            simulation.set_controller(agent_id, Some(Box::new(ScriptedController::new(script))));
+        }
+
+        return Ok(simulation);
+    }
+
+    pub fn replay_runner(&self) -> Result<HeadlessRunner> {
+        return Ok(HeadlessRunner::from_simulation(self.replay_simulation()?));
+    }
+
+    pub fn first_difference(&self, simulation: &Simulation) -> Option<usize> {
+        // id of the first agent that isn't where the log says it ended, if any;
+        // only meaningful after the replay ran for the logged number of ticks
+        let states: Vec<AgentState> = agent_states(simulation);
+
+        if states.len() != self.final_states.len() {
+            return Some(states.len().min(self.final_states.len()));
+        }
+
+        for i in 0..states.len() {
+            // bit for bit, a replay either repeats the run or it doesn't
+            if states[i].center.x() != self.final_states[i].center.x()
+                || states[i].center.y() != self.final_states[i].center.y()
+                || states[i].angle_deg != self.final_states[i].angle_deg
+            {
+                return Some(i);
+            }
+        }
+
+        return None;
+    }
+}
+
+fn agent_states(simulation: &Simulation) -> Vec<AgentState> {
+    let mut states: Vec<AgentState> = Vec::with_capacity(simulation.agent_count());
+
+    for i in 0..simulation.agent_count() {
+        states.push(AgentState {
+            tick: simulation.tick,
+            agent_id: i,
+            center: simulation.world.agents[i].center,
+            angle_deg: simulation.world.agents[i].angle().get_deg(),
+        });
+    }
+
+    return states;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{agent::Action, controller::ControllerDescription, snapshot::Snapshot};
+
+    fn controlled_setup() -> WorldSetup {
+        // the first agent walks at random, the other one follows walls
+        let mut setup: WorldSetup = WorldSetup::from_file(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/world-shapes.ron")).unwrap();
+        setup.initial_controller = Some(ControllerDescription::RandomWalk { seed: 11, position: 0 });
+
+        assert!(matches!(setup.other_agents[0].controller, Some(ControllerDescription::WallFollower { .. })));
+
+        return setup;
+    }
+
+    fn random_position(snapshot: &Snapshot) -> u64 {
+        match &snapshot.controllers[0] {
+            Some(ControllerDescription::RandomWalk { position, .. }) => return *position,
+            _ => panic!("the first agent has no random walk"),
+        }
+    }
+
+    #[test]
+    fn replay_of_controlled_agents_ends_where_the_run_did() {
+        let setup: WorldSetup = controlled_setup();
+
+        let mut simulation: Simulation = Simulation::new(&setup).unwrap();
+        let mut log: ActionLog = ActionLog::start(&setup, &mut simulation);
+        let mut runner: HeadlessRunner = HeadlessRunner::from_simulation(simulation);
+
+        runner.run(300);
+        log.finish(&mut runner.simulation);
+
+        // both controllers did something, the random walk turned on its own
+        assert!(log.actions.iter().any(|timed_action| timed_action.agent == 0 && matches!(timed_action.action, Action::Turn(_))));
+        assert!(log.actions.iter().any(|timed_action| timed_action.agent == 1));
+
+        // through a file, as with --log-actions and replay
+        let log_path: PathBuf = std::env::temp_dir().join(format!("moving_agent_replay_{}.ron", std::process::id()));
+        log.save(&log_path).unwrap();
+        let log: ActionLog = ActionLog::load(&log_path).unwrap();
+        let _ = std::fs::remove_file(&log_path);
+
+        let mut replay: HeadlessRunner = log.replay_runner().unwrap();
+        replay.run(log.ticks);
+
+        assert_eq!(log.first_difference(&replay.simulation), None);
+    }
+
+    #[test]
+    fn random_walk_goes_on_from_a_snapshot() {
+        let setup: WorldSetup = controlled_setup();
+
+        let mut uninterrupted: HeadlessRunner = HeadlessRunner::new(&setup).unwrap();
+        uninterrupted.run(300);
+
+        let mut first_half: HeadlessRunner = HeadlessRunner::new(&setup).unwrap();
+        first_half.run(150);
+
+        let snapshot: Snapshot = first_half.simulation.snapshot();
+        let position: u64 = random_position(&snapshot);
+
+        assert!(position > 0);
+
+        let snapshot_path: PathBuf = std::env::temp_dir().join(format!("moving_agent_random_walk_{}.ron", std::process::id()));
+        snapshot.save(&snapshot_path).unwrap();
+        let snapshot: Snapshot = Snapshot::load(&snapshot_path).unwrap();
+        let _ = std::fs::remove_file(&snapshot_path);
+
+        // the generator is restored where it was, not reseeded
+        let mut second_half: HeadlessRunner = HeadlessRunner::from_simulation(Simulation::from_snapshot(snapshot));
+
+        assert_eq!(random_position(&second_half.simulation.snapshot()), position);
+
+        second_half.run(150);
+
+        assert_eq!(random_position(&second_half.simulation.snapshot()), random_position(&uninterrupted.simulation.snapshot()));
+
+        let expected: Vec<AgentState> = agent_states(&uninterrupted.simulation);
+        let states: Vec<AgentState> = agent_states(&second_half.simulation);
+
+        for i in 0..states.len() {
+            assert_eq!(states[i].tick, expected[i].tick);
+            assert_eq!((states[i].center.x(), states[i].center.y()), (expected[i].center.x(), expected[i].center.y()), "agent {}", i);
+            assert_eq!(states[i].angle_deg, expected[i].angle_deg, "agent {}", i);
+        }
+    }
+}
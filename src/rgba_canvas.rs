@@ -1,4 +1,17 @@
-use crate::common_structs::RGBAColor;
+use crate::{
+    common_structs::{Coord, RGBAColor},
+    error::{Error, Result},
+};
+
+// scanlines per row of pixels for anti-aliased fills
+const FILL_SUBSAMPLES: i32 = 4;
+
+// which points the edges of a filled polygon enclose
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillRule {
+    EvenOdd, // a line from the point crosses the edges an odd number of times: overlaps make holes
+    NonZero, // the edges wind around the point: overlaps of polygons going the same way stay filled
+}
 
 #[derive(Clone)]
 pub struct RGBACanvas {
@@ -10,40 +23,52 @@ pub struct RGBACanvas {
 }
 
 impl RGBACanvas {
-    pub fn new(width: i32, height: i32) -> RGBACanvas {
-        let u_width: usize = if width > 0 {width as usize} else {panic!("Canvas width should be positive non-zero integer")};
-        let u_height: usize = if height > 0 {height as usize} else {panic!("Canvas height should be positive non-zero integer")};
+    pub fn new(width: i32, height: i32) -> Result<RGBACanvas> {
+        if width <= 0 || height <= 0 {
+            return Err(Error::InvalidCanvasSize { width: width as f32, height: height as f32 });
+        }
 
-        return RGBACanvas {
+        let u_width: usize = width as usize;
+        let u_height: usize = height as usize;
+
+        return Ok(RGBACanvas {
             width,
             u_width,
             height,
             u_height,
             data: vec![0; u_width * u_height * 4],
-        };
+        });
     }
 
-    pub fn new_black(width: i32, height: i32) -> RGBACanvas {
-        let u_width: usize = if width > 0 {width as usize} else {panic!("Canvas width should be positive non-zero integer")};
-        let u_height: usize = if height > 0 {height as usize} else {panic!("Canvas height should be positive non-zero integer")};
+    pub fn new_black(width: i32, height: i32) -> Result<RGBACanvas> {
+        if width <= 0 || height <= 0 {
+            return Err(Error::InvalidCanvasSize { width: width as f32, height: height as f32 });
+        }
+
+        let u_width: usize = width as usize;
+        let u_height: usize = height as usize;
 
         let mut data: Vec<u8> = vec![0; u_width * u_height * 4];
         for i in 0..(u_width * u_height) {
             data[i * 4 + 3] = 255;
         }
 
-        return RGBACanvas {
+        return Ok(RGBACanvas {
             width,
             u_width,
             height,
             u_height,
             data,
-        };
+        });
     }
 
-    pub fn new_color(width: i32, height: i32, color: RGBAColor) -> RGBACanvas {
-        let u_width: usize = if width > 0 {width as usize} else {panic!("Canvas width should be positive non-zero integer")};
-        let u_height: usize = if height > 0 {height as usize} else {panic!("Canvas height should be positive non-zero integer")};
+    pub fn new_color(width: i32, height: i32, color: RGBAColor) -> Result<RGBACanvas> {
+        if width <= 0 || height <= 0 {
+            return Err(Error::InvalidCanvasSize { width: width as f32, height: height as f32 });
+        }
+
+        let u_width: usize = width as usize;
+        let u_height: usize = height as usize;
 
         let mut data: Vec<u8> = vec![0; u_width * u_height * 4];
         for i in 0..(u_width * u_height) {
@@ -53,31 +78,35 @@ impl RGBACanvas {
             data[i * 4 + 3] = 255;
         }
 
-        return RGBACanvas {
+        return Ok(RGBACanvas {
             width,
             u_width,
             height,
             u_height,
             data,
-        };
+        });
     }
 
-    pub fn new_f(width: f32, height: f32) -> RGBACanvas {
-        let u_width: usize = if width >= 1.0 {width as usize} else {panic!("Canvas width should be a number bigger than 1.0")};
-        let u_height: usize = if height >= 1.0 {height as usize} else {panic!("Canvas height should be a number bigger than 1.0")};
+    pub fn new_f(width: f32, height: f32) -> Result<RGBACanvas> {
+        if !(width >= 1.0 && height >= 1.0) {
+            return Err(Error::InvalidCanvasSize { width, height });
+        }
+
+        let u_width: usize = width as usize;
+        let u_height: usize = height as usize;
 
         let mut data: Vec<u8> = vec![0; u_width * u_height * 4];
         for i in 0..(u_width * u_height) {
             data[i * 4 + 3] = 255;
         }
 
-        return RGBACanvas {
+        return Ok(RGBACanvas {
             width: u_width as i32,
             u_width,
             height: u_height as i32,
             u_height,
             data,
-        };
+        });
     }
 
     pub fn copy(&self) -> RGBACanvas {
@@ -96,6 +125,112 @@ impl RGBACanvas {
         };
     }
 
+    pub fn fill_polygon(&mut self, edges: &[(Coord, Coord)], offset: Coord, color: RGBAColor, rule: FillRule, is_antialiased: bool) {
+        // scanline fill of the area the edges close, so edges can make several polygons and holes;
+        // without anti-aliasing a pixel is filled when its center is inside, with it pixels on the edges
+        // get the color as transparent as they are uncovered; pixels are blended by put_pixel
+        //
+        // edges are moved by the offset, its whole pixels only pick the pixels to fill (as in Vector2D::draw)
+        if edges.is_empty() {
+            return;
+        }
+
+        let (whole_x, whole_y, fraction) = split_offset(offset);
+        let edges: Vec<(Coord, Coord)> = edges.iter().map(|edge| (edge.0.new_offset(fraction), edge.1.new_offset(fraction))).collect();
+        let edges: &[(Coord, Coord)] = &edges;
+
+        let mut min_y: f32 = f32::MAX;
+        let mut max_y: f32 = f32::MIN;
+
+        for i in 0..edges.len() {
+            min_y = f32::min(min_y, f32::min(edges[i].0.y(), edges[i].1.y()));
+            max_y = f32::max(max_y, f32::max(edges[i].0.y(), edges[i].1.y()));
+        }
+
+        // rows and columns of the canvas, without the whole pixels of the offset
+        let first_row: i32 = i32::max(min_y.floor() as i32, 0i32.saturating_sub(whole_y));
+        let last_row: i32 = i32::min(max_y.ceil() as i32, self.height.saturating_sub(whole_y));
+        let first_column: i32 = 0i32.saturating_sub(whole_x);
+        let last_column: i32 = self.width.saturating_sub(whole_x);
+
+        if !is_antialiased {
+            for j in first_row..last_row {
+                let spans: Vec<(f32, f32)> = fill_spans(edges, j as f32 + 0.5, rule);
+
+                for k in 0..spans.len() {
+                    let from: i32 = i32::max((spans[k].0 - 0.5).ceil() as i32, first_column);
+                    let to: i32 = i32::min((spans[k].1 - 0.5).ceil() as i32, last_column);
+
+                    for i in from..to {
+                        self.put_pixel(i + whole_x, j + whole_y, color);
+                    }
+                }
+            }
+
+            return;
+        }
+
+        // coverage of the pixels of a row, from several scanlines through it,
+        // each adding the exact part of every pixel its spans cover
+        let mut coverage: Vec<f32> = vec![0.0; self.u_width];
+
+        for j in first_row..last_row {
+            let mut from: i32 = last_column;
+            let mut to: i32 = first_column;
+
+            for sample in 0..FILL_SUBSAMPLES {
+                let y: f32 = j as f32 + (sample as f32 + 0.5) / (FILL_SUBSAMPLES as f32);
+                let spans: Vec<(f32, f32)> = fill_spans(edges, y, rule);
+
+                for k in 0..spans.len() {
+                    let start: f32 = f32::max(spans[k].0, first_column as f32);
+                    let end: f32 = f32::min(spans[k].1, last_column as f32);
+
+                    if start >= end {
+                        continue;
+                    }
+
+                    from = i32::min(from, start.floor() as i32);
+                    to = i32::max(to, end.ceil() as i32);
+
+                    for i in (start.floor() as i32)..(end.ceil() as i32) {
+                        let covered: f32 = f32::min(end, (i + 1) as f32) - f32::max(start, i as f32);
+                        coverage[(i + whole_x) as usize] += covered / (FILL_SUBSAMPLES as f32);
+                    }
+                }
+            }
+
+            for i in from..to {
+                let covered: f32 = f32::min(coverage[(i + whole_x) as usize], 1.0);
+
+                if covered > 0.0 {
+                    self.put_pixel(i + whole_x, j + whole_y, color.change_transparency((color.a as f32 * covered).round() as u8));
+                }
+
+                coverage[(i + whole_x) as usize] = 0.0;
+            }
+        }
+    }
+
+    pub fn put_canvas(&mut self, x: i32, y: i32, canvas: &RGBACanvas) {
+        // another canvas copied in with its top left corner at x, y, without blending;
+        // the parts outside of this canvas are left out
+        let first_column: i32 = i32::max(x, 0);
+        let last_column: i32 = i32::min(x + canvas.width, self.width);
+
+        if first_column >= last_column {
+            return;
+        }
+
+        for j in i32::max(y, 0)..i32::min(y + canvas.height, self.height) {
+            let from: usize = 4 * (canvas.u_width * (j - y) as usize + (first_column - x) as usize);
+            let to: usize = 4 * (self.u_width * j as usize + first_column as usize);
+            let length: usize = 4 * (last_column - first_column) as usize;
+
+            self.data[to..to + length].copy_from_slice(&canvas.data[from..from + length]);
+        }
+    }
+
     pub fn get_color(&self, x: i32, y: i32) -> RGBAColor {
         let index: usize = 4 * self.u_width * y as usize + 4 * x as usize;
 
@@ -159,4 +294,182 @@ impl RGBACanvas {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn split_offset(offset: Coord) -> (i32, i32, Coord) {
+    // whole pixels of an offset (rounded down) and the rest
+    let whole_x: f32 = offset.x().floor();
+    let whole_y: f32 = offset.y().floor();
+
+    return (whole_x as i32, whole_y as i32, Coord::new(offset.x() - whole_x, offset.y() - whole_y));
+}
+
+fn fill_spans(edges: &[(Coord, Coord)], y: f32, rule: FillRule) -> Vec<(f32, f32)> {
+    // parts of the horizontal line at y that are inside the edges, from left to right
+    let mut crossings: Vec<(f32, i32)> = Vec::with_capacity(edges.len());
+
+    for i in 0..edges.len() {
+        let (a, b) = edges[i];
+
+        // each edge counts once, with its upper end in and its lower end out
+        if (a.y() <= y) != (b.y() <= y) {
+            let x: f32 = a.x() + (y - a.y()) * (b.x() - a.x()) / (b.y() - a.y());
+            let winding: i32 = if a.y() < b.y() {1} else {-1};
+
+            crossings.push((x, winding));
+        }
+    }
+
+    crossings.sort_by(|crossing_0, crossing_1| crossing_0.0.total_cmp(&crossing_1.0));
+
+    let mut spans: Vec<(f32, f32)> = Vec::new();
+    let mut winding: i32 = 0;
+
+    for k in 0..crossings.len() {
+        let was_inside: bool = winding != 0;
+
+        winding = match rule {
+            FillRule::EvenOdd => 1 - winding,
+            FillRule::NonZero => winding + crossings[k].1,
+        };
+
+        let is_inside: bool = winding != 0;
+
+        if !was_inside && is_inside {
+            spans.push((crossings[k].0, crossings[k].0));
+        } else if was_inside && !is_inside {
+            spans.last_mut().unwrap().1 = crossings[k].0;
+        }
+    }
+
+    return spans;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common_structs::Palette;
+
+    fn polygon(points: &[(f32, f32)]) -> Vec<(Coord, Coord)> {
+        // closed, the last point connects to the first
+        let mut edges: Vec<(Coord, Coord)> = Vec::with_capacity(points.len());
+
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            edges.push((Coord::new(a.0, a.1), Coord::new(b.0, b.1)));
+        }
+
+        return edges;
+    }
+
+    fn filled(edges: &[(Coord, Coord)], size: i32, rule: FillRule) -> Vec<String> {
+        // rows of the canvas, '#' for filled pixels and '.' for black ones
+        let mut canvas: RGBACanvas = RGBACanvas::new_black(size, size).unwrap();
+        canvas.fill_polygon(edges, Coord::new(0.0, 0.0), RGBAColor::new_p(Palette::White), rule, false);
+
+        let mut rows: Vec<String> = Vec::new();
+
+        for j in 0..size {
+            let mut row: String = String::new();
+
+            for i in 0..size {
+                match canvas.get_color(i, j).r {
+                    255 => row.push('#'),
+                    0 => row.push('.'),
+                    value => panic!("pixel {}, {} is {}", i, j, value),
+                }
+            }
+
+            rows.push(row);
+        }
+
+        return rows;
+    }
+
+    #[test]
+    fn overlaps_are_holes_only_with_even_odd() {
+        // two squares going the same way, as one polygon
+        let mut edges: Vec<(Coord, Coord)> = polygon(&[(1.0, 1.0), (5.0, 1.0), (5.0, 5.0), (1.0, 5.0)]);
+        edges.append(&mut polygon(&[(3.0, 3.0), (7.0, 3.0), (7.0, 7.0), (3.0, 7.0)]));
+
+        assert_eq!(filled(&edges, 8, FillRule::EvenOdd), vec![
+            "........",
+            ".####...",
+            ".####...",
+            ".##..##.",
+            ".##..##.",
+            "...####.",
+            "...####.",
+            "........",
+        ]);
+
+        assert_eq!(filled(&edges, 8, FillRule::NonZero), vec![
+            "........",
+            ".####...",
+            ".####...",
+            ".######.",
+            ".######.",
+            "...####.",
+            "...####.",
+            "........",
+        ]);
+
+        // a pentagram crosses itself, its center is wound around twice
+        let mut points: Vec<(f32, f32)> = Vec::new();
+
+        for k in 0..5 {
+            let angle: f32 = (k * 144) as f32 * std::f32::consts::PI / 180.0;
+            points.push((8.0 + 7.0 * angle.sin(), 8.0 - 7.0 * angle.cos()));
+        }
+
+        let even_odd: Vec<String> = filled(&polygon(&points), 16, FillRule::EvenOdd);
+        let non_zero: Vec<String> = filled(&polygon(&points), 16, FillRule::NonZero);
+
+        assert_eq!(&even_odd[8][7..9], "..");
+        assert_eq!(&non_zero[8][7..9], "##");
+        assert_eq!(&even_odd[3][7..9], "##");
+        assert_eq!(&non_zero[3][7..9], "##");
+    }
+
+    #[test]
+    fn inner_polygon_going_the_other_way_is_a_hole() {
+        let outer: Vec<(Coord, Coord)> = polygon(&[(0.0, 0.0), (6.0, 0.0), (6.0, 6.0), (0.0, 6.0)]);
+        let with_hole: Vec<(Coord, Coord)> = [outer.clone(), polygon(&[(2.0, 2.0), (2.0, 4.0), (4.0, 4.0), (4.0, 2.0)])].concat();
+        let with_inner: Vec<(Coord, Coord)> = [outer.clone(), polygon(&[(2.0, 2.0), (4.0, 2.0), (4.0, 4.0), (2.0, 4.0)])].concat();
+
+        let hole: Vec<&str> = vec!["######", "######", "##..##", "##..##", "######", "######"];
+        let full: Vec<&str> = vec!["######"; 6];
+
+        assert_eq!(filled(&with_hole, 6, FillRule::NonZero), hole);
+        assert_eq!(filled(&with_hole, 6, FillRule::EvenOdd), hole);
+        assert_eq!(filled(&with_inner, 6, FillRule::NonZero), full);
+        assert_eq!(filled(&with_inner, 6, FillRule::EvenOdd), hole);
+    }
+
+    #[test]
+    fn anti_aliased_edges_get_their_coverage() {
+        // white over black, so the red of a pixel is the alpha it was filled with
+        let mut canvas: RGBACanvas = RGBACanvas::new_black(5, 4).unwrap();
+        let edges: Vec<(Coord, Coord)> = polygon(&[(1.25, 1.0), (3.75, 1.0), (3.75, 3.0), (1.25, 3.0)]);
+
+        canvas.fill_polygon(&edges, Coord::new(0.0, 0.0), RGBAColor::new_p(Palette::White), FillRule::NonZero, true);
+
+        let reds = |j: i32| -> Vec<u8> { (0..5).map(|i| canvas.get_color(i, j).r).collect() };
+
+        assert_eq!(reds(0), vec![0, 0, 0, 0, 0]);
+        assert_eq!(reds(1), vec![0, 191, 255, 191, 0]);
+        assert_eq!(reds(2), vec![0, 191, 255, 191, 0]);
+        assert_eq!(reds(3), vec![0, 0, 0, 0, 0]);
+
+        // half a row: two of the four scanlines through it
+        let mut canvas: RGBACanvas = RGBACanvas::new_black(3, 3).unwrap();
+        let edges: Vec<(Coord, Coord)> = polygon(&[(1.0, 0.5), (2.0, 0.5), (2.0, 2.0), (1.0, 2.0)]);
+
+        canvas.fill_polygon(&edges, Coord::new(0.0, 0.0), RGBAColor::new_p(Palette::White), FillRule::NonZero, true);
+
+        assert_eq!(canvas.get_color(1, 0).r, 128);
+        assert_eq!(canvas.get_color(1, 1).r, 255);
+        assert_eq!(canvas.get_color(1, 2).r, 0);
+        assert_eq!(canvas.get_color(0, 0).r, 0);
+    }
+}
@@ -5,19 +5,73 @@
 // it does not need to be closed
 // can
 
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
 
-use crate::{common_structs::{Coord, Angle}, vector_2d::Vector2D, linear_texture::LinearTexture, rgba_canvas::RGBACanvas};
+use crate::{agent::CollisionModel, common_structs::{AlignedBox, Coord, Angle, RGBAColor}, controller::ControllerDescription, dynamic_object::DynamicObjectDescription, error::{Error, Result}, loading::load_ron_file, vector_2d::Vector2D, linear_texture::LinearTexture, rgba_canvas::{FillRule, RGBACanvas}, view::TopViewCamera};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct WorldSetup {
   pub initial_coord: Coord,
   pub initial_angle_deg: f32,
   pub agents_field_of_view_deg: f32,
   pub world_shapes: Vec<ShapeDescription>,
+  #[serde(default)]
+  pub dynamic_objects: Vec<DynamicObjectDescription>,
+  #[serde(default)]
+  pub initial_controller: Option<ControllerDescription>, // of the first agent, none means it only follows the user or scripts
+  #[serde(default)]
+  pub other_agents: Vec<AgentDescription>, // the first agent is the one given by initial_* fields
+  #[serde(default)]
+  pub agents_collision_model: CollisionModel,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct AgentDescription {
+  pub initial_coord: Coord,
+  pub initial_angle_deg: f32,
+  #[serde(default)]
+  pub field_of_view_deg: Option<f32>, // same as the first agent's if not given
+  #[serde(default)]
+  pub controller: Option<ControllerDescription>,
+  #[serde(default)]
+  pub collision_model: Option<CollisionModel>, // same as the first agent's if not given
 }
 
-#[derive(Deserialize, Serialize)]
+impl WorldSetup {
+  pub fn from_file(path: &Path) -> Result<WorldSetup> {
+    return load_ron_file(path);
+  }
+
+  pub fn validate(&self) -> Result<()> {
+    // what building the agents doesn't check: finite poses and fields of view between 0 and 180 degrees
+    // (shapes and dynamic objects are checked when they are built)
+    let mut agents: Vec<(Coord, f32, f32)> = vec![(self.initial_coord, self.initial_angle_deg, self.agents_field_of_view_deg)];
+
+    for descr in self.other_agents.iter() {
+      agents.push((descr.initial_coord, descr.initial_angle_deg, descr.field_of_view_deg.unwrap_or(self.agents_field_of_view_deg)));
+    }
+
+    for (agent, (coord, angle_deg, fov_deg)) in agents.into_iter().enumerate() {
+      if !(coord.x().is_finite() && coord.y().is_finite() && angle_deg.is_finite()) {
+        return Err(Error::InvalidAgent { agent, reason: String::from("position and angle should be finite numbers") });
+      }
+
+      // NaN fails this too
+      if !(fov_deg > 0.0 && fov_deg < 180.0) {
+        return Err(Error::InvalidAgent {
+          agent,
+          reason: format!("field of view {} should be more than 0 and less than 180 degrees", fov_deg),
+        });
+      }
+    }
+
+    return Ok(());
+  }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct ShapeDescription {
   pub name: String,
   pub anchor: Coord,
@@ -25,17 +79,17 @@ pub struct ShapeDescription {
   pub texture: LinearTexture,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Shape {
   name: String,
   pub elements: Vec<Vector2D>,
   // c_o_m: Coord, // center of mass, relative to anchor -- a point in the center of shape for collision detections
-  pub radius: f32, // distance from c_o_m to most distant point of the shape
+  pub radius: f32, // distance from the anchor to the most distant vertex, the circle the shape turns within
   pub anchor: Coord, // point for rotations and translations
 }
 
 impl Shape {
-  pub fn from_coord_list(name: String, coords: Vec<Coord>, texture: LinearTexture) -> Option<Shape> {
+  pub fn from_coord_list(name: String, coords: Vec<Coord>, texture: LinearTexture) -> Result<Shape> {
     // given list of coordinates, builds a closed shape using coords as vertices,
     // applying continuous texture
 
@@ -47,13 +101,24 @@ impl Shape {
       let mut elements: Vec<Vector2D> = Vec::new();
       // let c_o_m: Coord; // = Coord::new(0.0, 0.0);
       let anchor: Coord = Coord::new(0.0, 0.0);
-      let radius: f32 = 0.0;
+      let mut radius: f32 = 0.0;
       let mut shift: f32 = 0.0;
 
+      for i in 0..coords.len() {
+        if coords[i].x() == coords[(i+1)%coords.len()].x() && coords[i].y() == coords[(i+1)%coords.len()].y() {
+          return Err(Error::DegenerateShape {
+            name,
+            reason: format!("vertices {} and {} are the same point", i, (i+1)%coords.len()),
+          });
+        }
+      }
+
       for i in 0..coords.len() {
         // sum_x += coords[i].x();
         // sum_y += coords[i].y();
 
+        radius = f32::max(radius, f32::sqrt(coords[i].x() * coords[i].x() + coords[i].y() * coords[i].y()));
+
         if i > 0 {
           shift += elements[i-1].length();
         }
@@ -90,7 +155,7 @@ impl Shape {
         sum_y / coords.len() as f32,
       ); */
 
-      return Some(Shape {
+      return Ok(Shape {
         name,
         elements,
         // // c_o_m,
@@ -98,9 +163,11 @@ impl Shape {
         anchor,
       });
     } else {
-      println!("Number of vertices should be bigger than one!");
-
-      return None;
+      return Err(Error::TooFewVertices {
+        name,
+        count: coords.len(),
+        required: 2,
+      });
     }
   }
 
@@ -129,7 +196,7 @@ impl Shape {
     };
   }
 
-  pub fn new_box(name: String, width: f32, height: f32, texture: LinearTexture) -> Option<Shape> {
+  pub fn new_box(name: String, width: f32, height: f32, texture: LinearTexture) -> Result<Shape> {
     // creates a box with given dimensions and texture,
     // the texture wraps around clockwise
     // It is centered in origin and coordinate aligned
@@ -168,7 +235,7 @@ impl Shape {
         texture.new_shifted_phase(width + height * 2.0),
       ));
   
-      return Some(Shape {
+      return Ok(Shape {
         name,
         elements,
         // c_o_m,
@@ -176,15 +243,24 @@ impl Shape {
         anchor,
       });
     } else {
-      println!("Width and Height of box shape should be bigger than zero!");
-
-      return None;
+      return Err(Error::DegenerateShape {
+        name,
+        reason: format!("box width and height should be bigger than zero, got {}x{}", width, height),
+      });
     }
     
   }
 
-  pub fn new_regular_polygon(name: String, radius: f32, num_sides: usize, texture: LinearTexture) -> Option<Shape> {
-    if radius > 0.0 && num_sides > 2 {
+  pub fn new_regular_polygon(name: String, radius: f32, num_sides: usize, texture: LinearTexture) -> Result<Shape> {
+    if num_sides < 3 {
+      return Err(Error::TooFewVertices {
+        name,
+        count: num_sides,
+        required: 3,
+      });
+    }
+
+    if radius > 0.0 {
 
       let mut elements: Vec<Vector2D> = Vec::with_capacity(num_sides);
       // let c_o_m: Coord = Coord::new(0.0, 0.0);
@@ -211,7 +287,7 @@ impl Shape {
         ));
       }
 
-      return Some(Shape {
+      return Ok(Shape {
         name,
         elements,
         // c_o_m,
@@ -219,9 +295,10 @@ impl Shape {
         anchor,
       });
     } else {
-      println!("Radius of Regular Polygon shape should be non-zero value, and it should have at least 3 sides!");
-
-      return  None;
+      return Err(Error::DegenerateShape {
+        name,
+        reason: format!("regular polygon radius should be bigger than zero, got {}", radius),
+      });
     }
   }
 
@@ -267,6 +344,18 @@ impl Shape {
 }
 
 impl Shape {
+  pub fn from_descr(descr: &ShapeDescription) -> Result<Shape> {
+    let mut shape: Shape = Shape::from_coord_list(
+      String::from(&descr.name),
+      descr.vertices.clone(),
+      descr.texture,
+    )?;
+
+    shape.shift(descr.anchor);
+
+    return Ok(shape);
+  }
+
   pub fn to_descr(&self) -> ShapeDescription {
     let mut vertices: Vec<Coord> = Vec::new();
 
@@ -284,18 +373,210 @@ impl Shape {
     
 }
 
+// geometry of the shape as a whole, everything in world coordinates
+impl Shape {
+  pub fn vertices(&self) -> Vec<Coord> {
+    // both ends of every element, once each
+    // (ends that meet can differ by rounding errors of rotations, so very close points count as the same)
+    let mut vertices: Vec<Coord> = Vec::with_capacity(self.elements.len() * 2);
+
+    for i in 0..self.elements.len() {
+      let element: Vector2D = self.elements[i].new_shifted(self.anchor);
+      let ends: [Coord; 2] = [element.base, element.tip_point()];
+
+      for end in ends {
+        let is_new: bool = !vertices.iter().any(|vertex| distance(*vertex, end) < 0.00001);
+
+        if is_new {
+          vertices.push(end);
+        }
+      }
+    }
+
+    return vertices;
+  }
+
+  pub fn bounding_box(&self) -> AlignedBox {
+    let vertices: Vec<Coord> = self.vertices();
+
+    if vertices.is_empty() {
+      return AlignedBox::around_point(self.anchor, 0.0);
+    }
+
+    let mut bounds: AlignedBox = AlignedBox::around_point(vertices[0], 0.0);
+
+    for i in 1..vertices.len() {
+      bounds = AlignedBox::new(
+        f32::min(bounds.x_0, vertices[i].x()),
+        f32::min(bounds.y_0, vertices[i].y()),
+        f32::max(bounds.x_1, vertices[i].x()),
+        f32::max(bounds.y_1, vertices[i].y()),
+      );
+    }
+
+    return bounds;
+  }
+
+  pub fn bounding_circle(&self) -> (Coord, f32) {
+    // smallest circle around all vertices, as center and radius
+    // (self.radius is the circle around the anchor instead, which stays valid when the shape rotates)
+    // incremental construction: whenever a point is outside, the circle is rebuilt through it
+
+    let points: Vec<Coord> = self.vertices();
+
+    if points.is_empty() {
+      return (self.anchor, 0.0);
+    }
+
+    let mut center: Coord = points[0];
+    let mut radius: f32 = 0.0;
+
+    for i in 1..points.len() {
+      if is_in_circle(points[i], center, radius) {
+        continue;
+      }
+
+      center = points[i];
+      radius = 0.0;
+
+      for j in 0..i {
+        if is_in_circle(points[j], center, radius) {
+          continue;
+        }
+
+        center = Coord::new((points[i].x() + points[j].x()) / 2.0, (points[i].y() + points[j].y()) / 2.0);
+        radius = distance(points[i], center);
+
+        for k in 0..j {
+          if !is_in_circle(points[k], center, radius) {
+            (center, radius) = circumcircle(points[i], points[j], points[k]);
+          }
+        }
+      }
+    }
+
+    return (center, radius);
+  }
+
+  pub fn convex_hull(&self) -> Vec<Coord> {
+    // vertices of the convex hull, going around it (monotone chain),
+    // collinear points are left out
+
+    let mut points: Vec<Coord> = self.vertices();
+
+    if points.len() < 3 {
+      return points;
+    }
+
+    points.sort_by(|a, b| a.x().total_cmp(&b.x()).then(a.y().total_cmp(&b.y())));
+
+    let mut hull: Vec<Coord> = Vec::with_capacity(points.len() * 2);
+
+    // lower part, then upper part
+    for pass in 0..2 {
+      let part_start: usize = hull.len();
+
+      for n in 0..points.len() {
+        let point: Coord = if pass == 0 {points[n]} else {points[points.len() - 1 - n]};
+
+        while hull.len() >= part_start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+          hull.pop();
+        }
+
+        hull.push(point);
+      }
+
+      // last point of a part is the first point of the next one
+      hull.pop();
+    }
+
+    return hull;
+  }
+}
+
+fn distance(a: Coord, b: Coord) -> f32 {
+  return f32::sqrt((a.x() - b.x()) * (a.x() - b.x()) + (a.y() - b.y()) * (a.y() - b.y()));
+}
+
+fn is_in_circle(point: Coord, center: Coord, radius: f32) -> bool {
+  // with a little tolerance for rounding errors
+  return distance(point, center) <= radius * (1.0 + 1e-5) + 1e-6;
+}
+
+fn cross(o: Coord, a: Coord, b: Coord) -> f32 {
+  // z of (a - o) x (b - o), positive when o -> a -> b turns counterclockwise in x-right, y-up axes
+  return (a.x() - o.x()) * (b.y() - o.y()) - (a.y() - o.y()) * (b.x() - o.x());
+}
+
+fn circumcircle(a: Coord, b: Coord, c: Coord) -> (Coord, f32) {
+  let d: f32 = 2.0 * (a.x() * (b.y() - c.y()) + b.x() * (c.y() - a.y()) + c.x() * (a.y() - b.y()));
+
+  if d == 0.0 {
+    // collinear -- circle over the two most distant points
+    let pairs: [(Coord, Coord); 3] = [(a, b), (b, c), (a, c)];
+    let mut widest: (Coord, Coord) = pairs[0];
+
+    for pair in pairs {
+      if distance(pair.0, pair.1) > distance(widest.0, widest.1) {
+        widest = pair;
+      }
+    }
+
+    let center: Coord = Coord::new((widest.0.x() + widest.1.x()) / 2.0, (widest.0.y() + widest.1.y()) / 2.0);
+
+    return (center, distance(widest.0, center));
+  }
+
+  let a_2: f32 = a.x() * a.x() + a.y() * a.y();
+  let b_2: f32 = b.x() * b.x() + b.y() * b.y();
+  let c_2: f32 = c.x() * c.x() + c.y() * c.y();
+
+  let center: Coord = Coord::new(
+    (a_2 * (b.y() - c.y()) + b_2 * (c.y() - a.y()) + c_2 * (a.y() - b.y())) / d,
+    (a_2 * (c.x() - b.x()) + b_2 * (a.x() - c.x()) + c_2 * (b.x() - a.x())) / d,
+  );
+
+  return (center, distance(a, center));
+}
+
 // just for grouping methods by category
 impl Shape {
   pub fn add_shape(&mut self, added_shape: Shape) {
     for i in 0..added_shape.elements.len() {
       self.elements.push(added_shape.elements[i]);
     }
+
+    self.radius = f32::max(self.radius, added_shape.radius);
   }
 
   pub fn draw(&self, canvas: &mut RGBACanvas) {
     for i in 0..self.elements.len() {
       // self.elements[i].new_shifted(self.anchor).draw_simple(canvas);
-      self.elements[i].new_shifted(self.anchor).draw_smooth(canvas);
+      self.elements[i].new_shifted(self.anchor).draw(canvas, Coord::new(0.0, 0.0), 1.0, 1.0);
+    }
+  }
+
+  pub fn draw_filled(&self, canvas: &mut RGBACanvas, camera: &TopViewCamera, color: RGBAColor, rule: FillRule, is_antialiased: bool) {
+    // the area the elements enclose, as the camera sees it; the outline itself is not drawn
+    let mut edges: Vec<(Coord, Coord)> = Vec::with_capacity(self.elements.len());
+
+    for i in 0..self.elements.len() {
+      let element: Vector2D = self.elements[i].new_shifted(self.anchor);
+
+      edges.push((
+        camera.view_point(element.base).new_scaled(camera.scale),
+        camera.view_point(element.tip_point()).new_scaled(camera.scale),
+      ));
+    }
+
+    canvas.fill_polygon(&edges, camera.pixel_offset(canvas.width, canvas.height), color, rule, is_antialiased);
+  }
+
+  pub fn fill_color(&self) -> RGBAColor {
+    // color the first element starts with, for drawing the shape filled
+    match self.elements.first() {
+      Some(element) => return element.texture.get_color(element.length(), 0.0),
+      None => return RGBAColor::new(),
     }
   }
 
@@ -313,4 +594,98 @@ impl Shape {
       self.elements[i].tip = self.elements[i].tip.new_rotated(alpha);
     }
   }  
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn concave_shape() -> Shape {
+    // an L, the inner corner at (1, 1) is not on the hull
+    let coords: Vec<Coord> = vec![
+      Coord::new(0.0, 0.0),
+      Coord::new(4.0, 0.0),
+      Coord::new(4.0, 1.0),
+      Coord::new(1.0, 1.0),
+      Coord::new(1.0, 3.0),
+      Coord::new(0.0, 3.0),
+    ];
+
+    return Shape::from_coord_list(String::from("L"), coords, LinearTexture::new_plain(RGBAColor::new())).unwrap();
+  }
+
+  fn is_close(a: f32, b: f32) -> bool {
+    return (a - b).abs() < 0.0001;
+  }
+
+  #[test]
+  fn setup_with_bad_field_of_view_is_invalid() {
+    let mut setup: WorldSetup = WorldSetup {
+      initial_coord: Coord::new(0.0, 0.0),
+      initial_angle_deg: 0.0,
+      agents_field_of_view_deg: 90.0,
+      world_shapes: Vec::new(),
+      dynamic_objects: Vec::new(),
+      initial_controller: None,
+      other_agents: Vec::new(),
+      agents_collision_model: CollisionModel::Circle,
+    };
+
+    assert!(setup.validate().is_ok());
+
+    setup.other_agents.push(AgentDescription {
+      initial_coord: Coord::new(3.0, 0.0),
+      initial_angle_deg: 0.0,
+      field_of_view_deg: Some(f32::NAN),
+      controller: None,
+      collision_model: None,
+    });
+    assert!(matches!(setup.validate(), Err(Error::InvalidAgent { agent: 1, .. })));
+
+    setup.other_agents[0].field_of_view_deg = None;
+    setup.agents_field_of_view_deg = 180.0;
+    assert!(matches!(setup.validate(), Err(Error::InvalidAgent { agent: 0, .. })));
+    assert!(crate::simulation::Simulation::new(&setup).is_err());
+  }
+
+  #[test]
+  fn bounding_box_of_concave_shape() {
+    let mut shape: Shape = concave_shape();
+    shape.shift(Coord::new(10.0, -5.0));
+
+    let bounds: AlignedBox = shape.bounding_box();
+
+    assert!(is_close(bounds.x_0, 10.0) && is_close(bounds.y_0, -5.0));
+    assert!(is_close(bounds.x_1, 14.0) && is_close(bounds.y_1, -2.0));
+  }
+
+  #[test]
+  fn bounding_circle_of_concave_shape() {
+    // the two farthest vertices are on the smallest circle, and the others are inside it
+    let shape: Shape = concave_shape();
+    let (center, radius): (Coord, f32) = shape.bounding_circle();
+
+    assert!(is_close(center.x(), 2.0) && is_close(center.y(), 1.5));
+    assert!(is_close(radius, 2.5));
+
+    for vertex in shape.vertices() {
+      assert!(distance(vertex, center) <= radius + 0.0001);
+    }
+  }
+
+  #[test]
+  fn convex_hull_of_concave_shape() {
+    let hull: Vec<Coord> = concave_shape().convex_hull();
+    let expected: [(f32, f32); 5] = [(0.0, 0.0), (4.0, 0.0), (4.0, 1.0), (1.0, 3.0), (0.0, 3.0)];
+
+    assert_eq!(hull.len(), expected.len());
+
+    for (x, y) in expected {
+      assert!(hull.iter().any(|point| is_close(point.x(), x) && is_close(point.y(), y)));
+    }
+
+    // every turn along the hull goes the same way
+    for i in 0..hull.len() {
+      assert!(cross(hull[i], hull[(i + 1) % hull.len()], hull[(i + 2) % hull.len()]) > 0.0);
+    }
+  }
+}
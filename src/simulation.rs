@@ -0,0 +1,327 @@
+// the simulation: one world and the agents moving in it, advanced in fixed ticks
+// frontends (GUI or headless) only feed actions in and read the state out
+//
+// agents live in world.agents, their ids are indices there;
+// the controlled agent is the one that receives keyboard actions in the GUI
+//
+// an agent can have a controller, which is asked for actions every tick,
+// agents without one only do what they are told through apply_action and step
+//
+// actions given between ticks wait for the next one, so every action is applied
+// at a known tick and a log of them (see replay) repeats the run exactly
+
+use crate::{
+    agent::{Action, Agent},
+    common_structs::Coord,
+    controller::{Controller, ControllerDescription, Senses},
+    dynamic_object::ObstacleSegment,
+    error::Result,
+    headless::TimedAction,
+    shape::WorldSetup,
+    snapshot::Snapshot,
+    world::World,
+};
+
+// fixed simulation time step, in seconds
+pub const DELTA_T: f64 = 0.0166667;
+
+pub struct Simulation {
+    pub world: World,
+    pub controlled_agent: usize,
+    pub tick: u64,
+    controllers: Vec<Option<Box<dyn Controller>>>, // by agent id
+    pending_actions: Vec<(usize, Action)>, // given since the last tick
+    action_log: Option<Vec<TimedAction>>, // every applied action while logging
+}
+
+impl Simulation {
+    pub fn new(setup: &WorldSetup) -> Result<Simulation> {
+        setup.validate()?;
+
+        let world: World = World::from_setup(setup)?;
+
+        let mut controllers: Vec<Option<Box<dyn Controller>>> = Vec::with_capacity(world.agents.len());
+
+        controllers.push(setup.initial_controller.as_ref().map(|descr| descr.create()));
+
+        for i in 0..setup.other_agents.len() {
+            controllers.push(setup.other_agents[i].controller.as_ref().map(|descr| descr.create()));
+        }
+
+        let mut simulation: Simulation = Simulation {
+            world,
+            controlled_agent: 0,
+            tick: 0,
+            controllers,
+            pending_actions: Vec::new(),
+            action_log: None,
+        };
+
+        simulation.update_agents_obstacles();
+
+        return Ok(simulation);
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot) -> Simulation {
+        // walls are made from the saved shapes, agents learn about them as when they were added
+        let mut world: World = World::new();
+
+        world.add_shapes_as_walls(&snapshot.shapes);
+        world.objects = snapshot.objects;
+
+        for agent in snapshot.agents {
+            world.add_agent(agent);
+        }
+
+        let controllers: Vec<Option<Box<dyn Controller>>> = snapshot.controllers
+            .iter()
+            .map(|descr| descr.as_ref().map(|descr| descr.create()))
+            .collect();
+
+        let mut simulation: Simulation = Simulation {
+            world,
+            controlled_agent: 0,
+            tick: snapshot.tick,
+            controllers,
+            pending_actions: snapshot.pending_actions,
+            action_log: None,
+        };
+
+        simulation.select_agent(snapshot.controlled_agent);
+        simulation.update_agents_obstacles();
+
+        return simulation;
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        // everything needed to go on from this tick
+        let mut controllers: Vec<Option<ControllerDescription>> = Vec::with_capacity(self.world.agents.len());
+
+        for i in 0..self.world.agents.len() {
+            controllers.push(match self.controllers.get(i) {
+                Some(Some(controller)) => controller.description(),
+                _ => None,
+            });
+        }
+
+        return Snapshot {
+            tick: self.tick,
+            controlled_agent: self.controlled_agent,
+            shapes: self.world.shapes.clone(),
+            objects: self.world.objects.clone(),
+            agents: self.world.agents.clone(),
+            controllers,
+            pending_actions: self.pending_actions.clone(),
+        };
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        // the simulation goes on from the snapshot, an action log being kept is dropped,
+        // it wouldn't start from the same world any more
+        *self = Simulation::from_snapshot(snapshot);
+    }
+
+    pub fn agent(&self) -> &Agent {
+        return &self.world.agents[self.controlled_agent];
+    }
+
+    pub fn agent_mut(&mut self) -> &mut Agent {
+        return &mut self.world.agents[self.controlled_agent];
+    }
+
+    pub fn agent_count(&self) -> usize {
+        return self.world.agents.len();
+    }
+
+    pub fn select_agent(&mut self, id: usize) -> bool {
+        if id < self.world.agents.len() {
+            self.controlled_agent = id;
+            self.agent_mut().is_updated = true;
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    pub fn select_next_agent(&mut self) {
+        let next: usize = (self.controlled_agent + 1) % self.world.agents.len();
+        self.select_agent(next);
+    }
+
+    pub fn set_controller(&mut self, agent_id: usize, controller: Option<Box<dyn Controller>>) -> Option<Box<dyn Controller>> {
+        // gives the agent a new controller (or takes it away), returns the previous one
+        if agent_id >= self.world.agents.len() {
+            return controller;
+        }
+
+        if agent_id >= self.controllers.len() {
+            self.controllers.resize_with(self.world.agents.len(), || None);
+        }
+
+        return std::mem::replace(&mut self.controllers[agent_id], controller);
+    }
+
+    pub fn has_controller(&self, agent_id: usize) -> bool {
+        return agent_id < self.controllers.len() && self.controllers[agent_id].is_some();
+    }
+
+    pub fn key_press(&mut self, key: char) {
+        // keyboard goes to the controller of the controlled agent
+        if let Some(Some(controller)) = self.controllers.get_mut(self.controlled_agent) {
+            controller.key_press(key);
+        }
+    }
+
+    pub fn key_release(&mut self, key: char) {
+        if let Some(Some(controller)) = self.controllers.get_mut(self.controlled_agent) {
+            controller.key_release(key);
+        }
+    }
+
+    pub fn apply_action(&mut self, action: Action) {
+        // action for the controlled agent
+        self.apply_agent_action(self.controlled_agent, action);
+    }
+
+    pub fn apply_agent_action(&mut self, agent_id: usize, action: Action) {
+        // the action is applied with the next tick, before the ones given to step
+        if agent_id < self.world.agents.len() {
+            self.pending_actions.push((agent_id, action));
+        }
+    }
+
+    pub fn start_action_log(&mut self) {
+        // from now on every applied action is kept together with its tick
+        self.action_log = Some(Vec::new());
+    }
+
+    pub fn is_logging_actions(&self) -> bool {
+        return self.action_log.is_some();
+    }
+
+    pub fn take_action_log(&mut self) -> Vec<TimedAction> {
+        // stops logging, returns what has been logged
+        return self.action_log.take().unwrap_or_default();
+    }
+
+    pub fn step(&mut self, actions: &[Action]) {
+        // one tick with all actions going to the controlled agent
+        let agent_actions: Vec<(usize, Action)> = actions.iter().map(|action| (self.controlled_agent, *action)).collect();
+
+        self.step_agents(&agent_actions);
+    }
+
+    pub fn step_agents(&mut self, actions: &[(usize, Action)]) {
+        // move dynamic objects, ask controllers what their agents do,
+        // apply actions given since the last tick and all actions given for the current tick
+        // (in given order, each to its agent) followed by actions of controllers (by agent id),
+        // then advance the clock
+
+        self.world.step(DELTA_T as f32);
+        self.update_agents_obstacles();
+
+        if !self.world.objects.is_empty() {
+            for i in 0..self.world.agents.len() {
+                self.world.agents[i].is_updated = true;
+            }
+        }
+
+        let mut all_actions: Vec<(usize, Action)> = std::mem::take(&mut self.pending_actions);
+        all_actions.extend_from_slice(actions);
+
+        for i in 0..self.world.agents.len() {
+            if let Some(Some(controller)) = self.controllers.get_mut(i) {
+                let senses: Senses = Senses::with_options(
+                    &self.world.agents[i],
+                    controller.ray_count(),
+                    controller.is_depth_only(),
+                    self.tick,
+                );
+                let controller_actions: Vec<Action> = controller.act(&senses);
+
+                for k in 0..controller_actions.len() {
+                    all_actions.push((i, controller_actions[k]));
+                }
+            }
+        }
+
+        // controllers have seen collisions of the previous tick, now collect the new ones
+        for i in 0..self.world.agents.len() {
+            self.world.agents[i].reset_collisions();
+        }
+
+        for i in 0..all_actions.len() {
+            self.do_agent_action(all_actions[i].0, all_actions[i].1);
+        }
+
+        if let Some(action_log) = self.action_log.as_mut() {
+            for i in 0..all_actions.len() {
+                if all_actions[i].0 < self.world.agents.len() {
+                    action_log.push(TimedAction {
+                        tick: self.tick,
+                        agent: all_actions[i].0,
+                        action: all_actions[i].1,
+                    });
+                }
+            }
+        }
+
+        for i in 0..self.world.agents.len() {
+            self.integrate_agent(i);
+        }
+
+        self.update_agents_obstacles();
+
+        self.tick += 1;
+    }
+
+    pub fn elapsed_time(&self) -> f64 {
+        return self.tick as f64 * DELTA_T;
+    }
+
+    fn do_agent_action(&mut self, agent_id: usize, action: Action) {
+        // turns happen at once, moves and rotations only push the agent,
+        // it moves when the tick is integrated in step_agents
+        if agent_id >= self.world.agents.len() {
+            return;
+        }
+
+        self.world.agents[agent_id].apply_action(action);
+
+        self.world.is_updated = true;
+    }
+
+    fn integrate_agent(&mut self, agent_id: usize) {
+        // agents move one after another, each seeing where the previous ones ended up
+        let obstacles: Vec<ObstacleSegment> = self.world.obstacles_for_agent(agent_id);
+        let agent: &mut Agent = &mut self.world.agents[agent_id];
+        let previous_center: Coord = agent.center;
+        let previous_angle: f32 = agent.angle().get_rad();
+
+        agent.update_dynamic_obstacles(obstacles);
+        agent.integrate(DELTA_T as f32);
+
+        let center: Coord = agent.center;
+        let radius: f32 = agent.shape.radius;
+
+        if center.x() != previous_center.x() || center.y() != previous_center.y() {
+            // the agent shoves pushable objects, and is held back by those that can't move
+            let correction: Coord = self.world.push_objects(center, radius);
+            self.world.agents[agent_id].bump(correction);
+
+            self.world.is_updated = true;
+        } else if self.world.agents[agent_id].angle().get_rad() != previous_angle {
+            self.world.is_updated = true;
+        }
+    }
+
+    fn update_agents_obstacles(&mut self) {
+        // every agent sees dynamic objects and all the other agents
+        for i in 0..self.world.agents.len() {
+            let obstacles: Vec<ObstacleSegment> = self.world.obstacles_for_agent(i);
+
+            self.world.agents[i].update_dynamic_obstacles(obstacles);
+            self.world.agents[i].is_updated = true;
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// snapshots of a running simulation, to continue it later, or several times from the same point:
+// static shapes, dynamic objects and agents exactly as they are (a restored run goes on
+// the same way as the original one would), controllers together with the state of their
+// random generators, and the tick count
+//
+// taken with Simulation::snapshot, turned back into a simulation with Simulation::from_snapshot
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::{Action, Agent},
+    controller::ControllerDescription,
+    dynamic_object::DynamicObject,
+    error::Result,
+    loading::{load_ron_file, save_ron_file},
+    shape::Shape,
+};
+
+#[derive(Deserialize, Serialize)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub controlled_agent: usize,
+    pub shapes: Vec<Shape>, // static shapes of the world, walls are made from them again
+    pub objects: Vec<DynamicObject>,
+    pub agents: Vec<Agent>, // by agent id
+    pub controllers: Vec<Option<ControllerDescription>>, // by agent id, none also for controllers that can't be described
+    #[serde(default)]
+    pub pending_actions: Vec<(usize, Action)>, // given since the last tick
+}
+
+impl Snapshot {
+    pub fn load(path: &Path) -> Result<Snapshot> {
+        return load_ron_file(path);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        return save_ron_file(path, self);
+    }
+}
@@ -1,5 +1,7 @@
 // this is to be an improvement and replacement of struct Line
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
   common_structs::{
     Coord,
@@ -8,12 +10,13 @@ use crate::{
     Dot,
     Marker,
     Palette,
+    AlignedBox,
   },
   linear_texture::LinearTexture,
-  rgba_canvas::RGBACanvas,
+  rgba_canvas::{split_offset, RGBACanvas},
 };
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Deserialize, Serialize)]
 pub struct Vector2D {
   pub base: Coord,  // starting point
   pub tip: Coord, // vector value relative to starting point
@@ -198,6 +201,26 @@ impl Vector2D {
   }
 
   pub fn intersect(&self, other: &Vector2D) -> Option<Vector2D> {
+    match self.intersection_params(other) {
+      Some((t, u)) => {
+        // lines intersect
+        return Some(
+          Vector2D::from_scalar(
+            t * self.length,
+            LinearTexture::new_plain(
+              other.texture.get_color(other.length, u * other.length),
+            ),
+          ),
+        );
+      }
+      None => {
+        return None;
+      }
+    }
+  }
+
+  pub fn intersection_params(&self, other: &Vector2D) -> Option<(f32, f32)> {
+    // parameters of intersection point along self (t) and along other (u), both 0.0..1.0
     let t: f32; // parameter along self 0.0..1.0
     let u: f32; // parameter along other 0.0..1.0
     let det: f32 = self.tip.x() * other.tip.y() - self.tip.y() * other.tip.x();
@@ -212,15 +235,7 @@ impl Vector2D {
       u = (d_b_y * self.tip.x() - d_b_x * self.tip.y()) / det;
 
       if t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0 {
-        // lines intersect
-        return Some(
-          Vector2D::from_scalar(
-            t * self.length,
-            LinearTexture::new_plain(
-              other.texture.get_color(other.length, u * other.length),
-            ),
-          ),
-        );        
+        return Some((t, u));
       }
     }
 
@@ -272,6 +287,35 @@ impl Vector2D {
     return self.phi;
   }
 
+  pub fn closest_point(&self, point: Coord) -> Coord {
+    // point of the segment nearest to given point, ends included
+    let length_squared: f32 = self.tip.x() * self.tip.x() + self.tip.y() * self.tip.y();
+
+    if length_squared == 0.0 {
+      return self.base;
+    }
+
+    let t: f32 = (
+      (point.x() - self.base.x()) * self.tip.x() + (point.y() - self.base.y()) * self.tip.y()
+    ) / length_squared;
+
+    return self.base.new_offset(self.tip.new_scaled(t.clamp(0.0, 1.0)));
+  }
+
+  pub fn tip_point(&self) -> Coord {
+    // absolute position of the tip
+    return self.base.new_offset(self.tip);
+  }
+
+  pub fn bounding_box(&self) -> AlignedBox {
+    return AlignedBox::new(
+      self.base.x(),
+      self.base.y(),
+      self.base.x() + self.tip.x(),
+      self.base.y() + self.tip.y(),
+    );
+  }
+
   pub fn is_from_origin(&self) -> bool {
     return self.base.x() == 0.0 && self.base.y() == 0.0;
   }
@@ -300,89 +344,141 @@ impl Vector2D {
     }
   }
 
-  pub fn draw_simple_s(&self, canvas: &mut RGBACanvas, shift: Coord, scale: f32) {
-    // parametric drawing on canvas, with scaling and shifting
-    // scaling: the bigger number, the bigger sizes on screen -> step is smaller
-    // shifting: just shift to given coord
-    
+  pub fn draw(&self, canvas: &mut RGBACanvas, offset: Coord, scale: f32, thickness: f32) {
+    // anti-aliased line of given thickness in pixels, at point * scale + offset on canvas,
+    // every pixel colored by the texture at its place along the vector;
+    // pixels get the part of them the line covers as opacity (coverage-based antialiasing),
+    // also at the ends, which are cut square at base and tip
+    //
+    // the line is worked out without the whole pixels of the offset, which only pick the pixels it goes to,
+    // so lines moved by whole pixels get exactly the same pixels
+
+    let (whole_x, whole_y, fraction) = split_offset(offset);
+    let start: Coord = self.base.new_scaled(scale).new_offset(fraction);
+    let end: Coord = self.tip_point().new_scaled(scale).new_offset(fraction);
+    let delta: Coord = Coord::new(end.x() - start.x(), end.y() - start.y());
+    let length_px: f32 = f32::sqrt(delta.x() * delta.x() + delta.y() * delta.y());
+
+    if length_px < 0.0001 || thickness <= 0.0 {
+      return;
+    }
 
-    let delta_x: f32 = self.tip.x() / (self.length * scale);
-    let delta_y: f32 = self.tip.y() / (self.length * scale);
-    let mut current_pos: Coord = self.base.new_offset(shift);
+    let direction: Coord = delta.new_scaled(1.0 / length_px);
+    let half_thickness: f32 = thickness / 2.0;
+    let reach: f32 = half_thickness + 1.0; // furthest a touched pixel center can be from the line
+    let is_steep: bool = delta.y().abs() > delta.x().abs();
 
-    for t in 0..((self.length * scale) as usize + 0) {
-      current_pos.move_x(delta_x);
-      current_pos.move_y(delta_y);
+    // walking along the major axis, one row or column at a time,
+    // only the pixels within reach of the line across it are looked at
+    let (major_start, major_end, major_delta, minor_delta) = if is_steep {
+      (start.y(), end.y(), delta.y(), delta.x())
+    } else {
+      (start.x(), end.x(), delta.x(), delta.y())
+    };
+    let (major_limit, minor_limit) = if is_steep {(canvas.height, canvas.width)} else {(canvas.width, canvas.height)};
+    let (major_whole, minor_whole) = if is_steep {(whole_y, whole_x)} else {(whole_x, whole_y)};
+    let minor_reach: f32 = reach * length_px / major_delta.abs();
 
-      canvas.put_pixel(
-        (current_pos.x() * scale) as i32,
-        (current_pos.y() * scale) as i32,
-        self.texture.get_color(self.length, (t as f32) / scale));
-    }
-  }
+    // rows and columns without the whole pixels of the offset
+    let first: i32 = i32::max((f32::min(major_start, major_end) - reach).floor() as i32, 0i32.saturating_sub(major_whole));
+    let last: i32 = i32::min((f32::max(major_start, major_end) + reach).ceil() as i32, major_limit.saturating_sub(major_whole));
 
-  pub fn draw_smooth(&self, canvas: &mut RGBACanvas) {
-    // draw smooth line on canvas
-    // inefficient variant:
-    // get axis aligned box
-    // iterate through it, with little padding
-    // for each point, find distance to line (the normal is needed)
-    // if less than 1.0, get color, and scale it with 1.0 - distance,
-    // and put on canvas
-
-    if self.tip.x() >= 0.0 && self.tip.y() >= 0.0 {
-      for j in (self.base.y() as i32 - 1)..((self.base.y() + self.tip.y()) as i32 + 2) {
-        for i in (self.base.x() as i32 - 1)..((self.base.x() + self.tip.x()) as i32 + 2) {
-          match self.new_orthogonal_from_point(Coord::new_i(i, j)) {
-            Some(orth) => {
-              if orth.length < 1.0 {
-                canvas.put_pixel(i, j, orth.texture.get_color(0.0, 0.0).new_scaled(1.0 - orth.length));
-              }
-            }
-            None => {}
-          };
-        }
-      }
-    } else if self.tip.x() < 0.0 && self.tip.y() >= 0.0 {
-      for j in (self.base.y() as i32 - 1)..((self.base.y() + self.tip.y()) as i32 + 2) {
-        for i in ((self.base.x() + self.tip.x()) as i32 - 1)..(self.base.x() as i32 + 2) {
-          match self.new_orthogonal_from_point(Coord::new_i(i, j)) {
-            Some(orth) => {
-              if orth.length < 1.0 {
-                canvas.put_pixel(i, j, orth.texture.get_color(0.0, 0.0).new_scaled(1.0 - orth.length));
-              }
-            }
-            None => {}
-          };
-        }
-      }
-    } else if self.tip.x() >= 0.0 && self.tip.y() < 0.0 {
-      for j in ((self.base.y() + self.tip.y()) as i32 - 1)..(self.base.y() as i32 + 2) {
-        for i in (self.base.x() as i32 - 1)..((self.base.x() + self.tip.x()) as i32 + 2) {
-          match self.new_orthogonal_from_point(Coord::new_i(i, j)) {
-            Some(orth) => {
-              if orth.length < 1.0 {
-                canvas.put_pixel(i, j, orth.texture.get_color(0.0, 0.0).new_scaled(1.0 - orth.length));
-              }
-            }
-            None => {}
-          };
+    for major in first..last {
+      let along_major: f32 = major as f32 + 0.5 - major_start;
+      let minor_center: f32 = if is_steep {start.x()} else {start.y()} + along_major * minor_delta / major_delta;
+
+      let minor_first: i32 = i32::max((minor_center - minor_reach).floor() as i32, 0i32.saturating_sub(minor_whole));
+      let minor_last: i32 = i32::min((minor_center + minor_reach).ceil() as i32, minor_limit.saturating_sub(minor_whole));
+
+      for minor in minor_first..minor_last {
+        let (i, j) = if is_steep {(minor, major)} else {(major, minor)};
+        let relative: Coord = Coord::new(i as f32 + 0.5 - start.x(), j as f32 + 0.5 - start.y());
+
+        let along: f32 = relative.x() * direction.x() + relative.y() * direction.y();
+        let across: f32 = f32::abs(relative.y() * direction.x() - relative.x() * direction.y());
+
+        let side_coverage: f32 = (half_thickness + 0.5 - across).clamp(0.0, 1.0);
+        let end_coverage: f32 = (f32::min(along, length_px - along) + 0.5).clamp(0.0, 1.0);
+        let coverage: f32 = side_coverage * end_coverage;
+
+        if coverage > 0.0 {
+          let position: f32 = along.clamp(0.0, length_px) / length_px * self.length;
+          let color: RGBAColor = self.texture.get_color(self.length, position);
+
+          canvas.put_pixel(i + whole_x, j + whole_y, color.change_transparency((color.a as f32 * coverage).round() as u8));
         }
       }
-    } else /* if self.tip.x() < 0.0 && self.tip.y() < 0.0 */ {
-      for j in ((self.base.y() + self.tip.y()) as i32 - 1)..(self.base.y() as i32 + 2) {
-        for i in ((self.base.x() + self.tip.x()) as i32 - 1)..(self.base.x() as i32 + 2) {
-          match self.new_orthogonal_from_point(Coord::new_i(i, j)) {
-            Some(orth) => {
-              if orth.length < 1.0 {
-                canvas.put_pixel(i, j, orth.texture.get_color(0.0, 0.0).new_scaled(1.0 - orth.length));
-              }
-            }
-            None => {}
-          };
-        }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn white_line(from: (f32, f32), to: (f32, f32)) -> Vector2D {
+    return Vector2D::new(
+      Coord::new(from.0, from.1),
+      Coord::new(to.0 - from.0, to.1 - from.1),
+      LinearTexture::new_plain(RGBAColor::new_p(Palette::White)),
+    );
+  }
+
+  fn reds(canvas: &RGBACanvas, j: i32) -> Vec<u8> {
+    // white over black, so the red of a pixel is the coverage it got
+    return (0..canvas.width).map(|i| canvas.get_color(i, j).r).collect();
+  }
+
+  #[test]
+  fn horizontal_line_through_pixel_centers() {
+    let mut canvas: RGBACanvas = RGBACanvas::new_black(8, 5).unwrap();
+
+    white_line((1.5, 2.5), (5.5, 2.5)).draw(&mut canvas, Coord::new(0.0, 0.0), 1.0, 1.0);
+
+    // the ends are cut square through the end pixels
+    assert_eq!(reds(&canvas, 2), vec![0, 128, 255, 255, 255, 128, 0, 0]);
+
+    for j in [0, 1, 3, 4] {
+      assert_eq!(reds(&canvas, j), vec![0; 8], "row {}", j);
+    }
+  }
+
+  #[test]
+  fn diagonal_line_is_symmetric() {
+    let mut canvas: RGBACanvas = RGBACanvas::new_black(8, 8).unwrap();
+
+    white_line((1.0, 1.0), (7.0, 7.0)).draw(&mut canvas, Coord::new(0.0, 0.0), 1.0, 1.0);
+
+    for j in 0..8 {
+      for i in 0..8 {
+        let red: u8 = canvas.get_color(i, j).r;
+
+        // across the line, and end for end
+        assert_eq!(red, canvas.get_color(j, i).r, "pixel {}, {}", i, j);
+        assert_eq!(red, canvas.get_color(7 - i, 7 - j).r, "pixel {}, {}", i, j);
       }
     }
 
+    assert_eq!(canvas.get_color(3, 3).r, 255);
+    assert_eq!(canvas.get_color(4, 3).r, 75); // its center 0.71 pixels away from the line
+    assert_eq!(canvas.get_color(5, 3).r, 0);
+  }
+
+  #[test]
+  fn lines_far_outside_the_canvas_draw_nothing() {
+    let empty: RGBACanvas = RGBACanvas::new_black(6, 4).unwrap();
+    let mut canvas: RGBACanvas = RGBACanvas::new_black(6, 4).unwrap();
+
+    white_line((1.0e6, 1.0e6), (1.0e6 + 50.0, 1.0e6 + 20.0)).draw(&mut canvas, Coord::new(0.0, 0.0), 1.0, 1.0);
+    white_line((-10.0, -1.0e7), (100.0, -1.0e7)).draw(&mut canvas, Coord::new(0.0, 0.0), 1.0, 3.0);
+    white_line((0.0, 0.0), (3.0, 2.0)).draw(&mut canvas, Coord::new(-2.0e7, 0.0), 40.0, 1.0);
+
+    assert_eq!(canvas.data, empty.data);
+
+    // only the part on the canvas of a very long line is looked at
+    white_line((-1.0e9, 1.5), (1.0e9, 1.5)).draw(&mut canvas, Coord::new(0.0, 0.0), 1.0, 1.0);
+
+    assert_eq!(reds(&canvas, 1), vec![255; 6]);
+    assert_eq!(reds(&canvas, 0), vec![0; 6]);
   }
 }
@@ -0,0 +1,556 @@
+// views of the simulation that frontends can show or save:
+// each view mode is rendered into an RGBACanvas of requested size
+
+use crate::{
+    agent::{Agent, WallHit},
+    backdrop::TopViewBackdrop,
+    common_structs::{Angle, Coord, Marker, RGBAColor},
+    error::Result,
+    linear_texture::LinearTexture,
+    rgba_canvas::RGBACanvas,
+    simulation::Simulation,
+    vector_2d::Vector2D,
+};
+
+// pixels per world unit in the top view
+pub const DEFAULT_TOP_VIEW_SCALE: f32 = 50.0;
+pub const MIN_TOP_VIEW_SCALE: f32 = 1.0;
+pub const MAX_TOP_VIEW_SCALE: f32 = 1000.0;
+
+// part of the view filled by the world when it is fitted in
+const FIT_FILL: f32 = 0.9;
+
+// pixel offsets this close to whole pixels are taken as whole, it is how far the center
+// of an aligned camera misses them after going to world units and back
+const WHOLE_PIXEL_TOLERANCE: f32 = 0.001;
+
+// neither view of the split view gets less than this part of the canvas
+pub const MIN_SPLIT_SHARE: f32 = 0.1;
+pub const MAX_SPLIT_SHARE: f32 = 0.9;
+
+// between the eyes of the stereo views, in world units (walls are 1.0 high)
+pub const DEFAULT_EYE_DISTANCE: f32 = 0.1;
+
+// view rays drawn over the top view, and segments of the arc closing the field of view cone
+pub const VIEW_RAY_OVERLAY_COUNT: i32 = 15;
+const CONE_ARC_SEGMENTS: i32 = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewMode {
+    Top,
+    FirstPerson,
+    Depth,
+    Split(SplitLayout), // top view with the view rays next to the first-person view
+    // first-person views from two eyes this far apart
+    Parallel(f32), // left eye's view on the left
+    CrossEye(f32), // left eye's view on the right
+    Anaglyph(f32), // red for the left eye, cyan for the right one
+}
+
+impl ViewMode {
+    pub fn from_name(name: &str) -> Option<ViewMode> {
+        match name {
+            "top" => Some(ViewMode::Top),
+            "first-person" | "fp" => Some(ViewMode::FirstPerson),
+            "depth" => Some(ViewMode::Depth),
+            "split" => Some(ViewMode::Split(SplitLayout::default())),
+            "parallel" => Some(ViewMode::Parallel(DEFAULT_EYE_DISTANCE)),
+            "cross-eye" => Some(ViewMode::CrossEye(DEFAULT_EYE_DISTANCE)),
+            "anaglyph" => Some(ViewMode::Anaglyph(DEFAULT_EYE_DISTANCE)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        // as accepted by from_name, also used in file names
+        match self {
+            ViewMode::Top => "top",
+            ViewMode::FirstPerson => "first-person",
+            ViewMode::Depth => "depth",
+            ViewMode::Split(_) => "split",
+            ViewMode::Parallel(_) => "parallel",
+            ViewMode::CrossEye(_) => "cross-eye",
+            ViewMode::Anaglyph(_) => "anaglyph",
+        }
+    }
+
+    pub fn next(&self) -> ViewMode {
+        // for cycling through view modes with a single key
+        match self {
+            ViewMode::Top => ViewMode::FirstPerson,
+            ViewMode::FirstPerson => ViewMode::Depth,
+            ViewMode::Depth => ViewMode::Split(SplitLayout::default()),
+            ViewMode::Split(_) => ViewMode::Parallel(DEFAULT_EYE_DISTANCE),
+            ViewMode::Parallel(eye_distance) => ViewMode::CrossEye(*eye_distance),
+            ViewMode::CrossEye(eye_distance) => ViewMode::Anaglyph(*eye_distance),
+            ViewMode::Anaglyph(_) => ViewMode::Top,
+        }
+    }
+
+    pub fn with_split_layout(&self, layout: SplitLayout) -> ViewMode {
+        // the split view laid out as given, other modes as they are
+        match self {
+            ViewMode::Split(_) => ViewMode::Split(layout),
+            _ => *self,
+        }
+    }
+
+    pub fn with_eye_distance(&self, eye_distance: f32) -> ViewMode {
+        // stereo views with the eyes this far apart, other modes as they are
+        match self {
+            ViewMode::Parallel(_) => ViewMode::Parallel(eye_distance),
+            ViewMode::CrossEye(_) => ViewMode::CrossEye(eye_distance),
+            ViewMode::Anaglyph(_) => ViewMode::Anaglyph(eye_distance),
+            _ => *self,
+        }
+    }
+
+    pub fn shows_top_view(&self) -> bool {
+        return matches!(self, ViewMode::Top | ViewMode::Split(_));
+    }
+}
+
+// how the split view shares its canvas: the top view is on the left, or above when stacked,
+// and gets its share of the width (or height), the first-person view gets the rest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SplitLayout {
+    pub is_stacked: bool,
+    pub top_view_share: f32,
+}
+
+impl SplitLayout {
+    pub fn new(is_stacked: bool, top_view_share: f32) -> SplitLayout {
+        return SplitLayout {
+            is_stacked,
+            top_view_share: top_view_share.clamp(MIN_SPLIT_SHARE, MAX_SPLIT_SHARE),
+        };
+    }
+
+    pub fn top_view_area(&self, canvas_width: i32, canvas_height: i32) -> (i32, i32, i32, i32) {
+        // x, y, width and height of the top view in the canvas
+        if self.is_stacked {
+            return (0, 0, canvas_width, self.split_at(canvas_height));
+        } else {
+            return (0, 0, self.split_at(canvas_width), canvas_height);
+        }
+    }
+
+    pub fn first_person_area(&self, canvas_width: i32, canvas_height: i32) -> (i32, i32, i32, i32) {
+        // x, y, width and height of the first-person view in the canvas
+        if self.is_stacked {
+            let split: i32 = self.split_at(canvas_height);
+            return (0, split, canvas_width, canvas_height - split);
+        } else {
+            let split: i32 = self.split_at(canvas_width);
+            return (split, 0, canvas_width - split, canvas_height);
+        }
+    }
+
+    fn split_at(&self, length: i32) -> i32 {
+        // at least a pixel for each view
+        return i32::max(i32::min((length as f32 * self.top_view_share).round() as i32, length - 1), 1);
+    }
+}
+
+impl Default for SplitLayout {
+    fn default() -> SplitLayout {
+        return SplitLayout::new(false, 0.5);
+    }
+}
+
+// how shapes are drawn in the top view
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TopViewRenderMode {
+    Vertices(Marker), // only the base of every element, as a dot
+    Wireframe,
+    FilledWireframe, // closed shapes filled with their color, half transparent, under the wireframe
+    Solid, // closed shapes filled with their color, static ones darker, under the wireframe
+    OccludedWireframe(usize), // only the parts of walls and objects the agent with this id can see, agents are always drawn
+}
+
+impl TopViewRenderMode {
+    pub fn from_name(name: &str) -> Option<TopViewRenderMode> {
+        match name {
+            "squares" => Some(TopViewRenderMode::Vertices(Marker::Square(2))),
+            "discs" => Some(TopViewRenderMode::Vertices(Marker::Disc(3))),
+            "wireframe" => Some(TopViewRenderMode::Wireframe),
+            "filled" => Some(TopViewRenderMode::FilledWireframe),
+            "solid" => Some(TopViewRenderMode::Solid),
+            "occluded" => Some(TopViewRenderMode::OccludedWireframe(0)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        // as accepted by from_name
+        match self {
+            TopViewRenderMode::Vertices(Marker::Square(_)) => "squares",
+            TopViewRenderMode::Vertices(Marker::Disc(_)) => "discs",
+            TopViewRenderMode::Wireframe => "wireframe",
+            TopViewRenderMode::FilledWireframe => "filled",
+            TopViewRenderMode::Solid => "solid",
+            TopViewRenderMode::OccludedWireframe(_) => "occluded",
+        }
+    }
+
+    pub fn next(&self) -> TopViewRenderMode {
+        // for cycling through render modes with a single key
+        match self {
+            TopViewRenderMode::Wireframe => TopViewRenderMode::FilledWireframe,
+            TopViewRenderMode::FilledWireframe => TopViewRenderMode::Solid,
+            TopViewRenderMode::Solid => TopViewRenderMode::OccludedWireframe(0),
+            TopViewRenderMode::OccludedWireframe(_) => TopViewRenderMode::Vertices(Marker::Square(2)),
+            TopViewRenderMode::Vertices(Marker::Square(_)) => TopViewRenderMode::Vertices(Marker::Disc(3)),
+            TopViewRenderMode::Vertices(Marker::Disc(_)) => TopViewRenderMode::Wireframe,
+        }
+    }
+
+    pub fn seen_by(&self, agent_id: usize) -> TopViewRenderMode {
+        // occluded wireframe as seen by the agent, other modes as they are
+        match self {
+            TopViewRenderMode::OccludedWireframe(_) => TopViewRenderMode::OccludedWireframe(agent_id),
+            _ => *self,
+        }
+    }
+}
+
+// where the top view looks from: the world point in the middle of the view, pixels per world unit
+// and the turn of the view (zero: world x to the right, y down, as in world files),
+// and how it draws the shapes
+#[derive(Clone, Copy)]
+pub struct TopViewCamera {
+    pub center: Coord,
+    pub scale: f32,
+    pub angle: Angle,
+    pub render_mode: TopViewRenderMode,
+}
+
+impl TopViewCamera {
+    pub fn new(center: Coord, scale: f32) -> TopViewCamera {
+        return TopViewCamera {
+            center,
+            scale,
+            angle: Angle::new(),
+            render_mode: TopViewRenderMode::Wireframe,
+        };
+    }
+
+    pub fn behind_agent(agent: &Agent, scale: f32) -> TopViewCamera {
+        // centered on the agent and turned so that it always looks up
+        return TopViewCamera {
+            center: agent.center,
+            scale,
+            angle: Angle::new_rad(-std::f32::consts::FRAC_PI_2 - agent.angle().get_rad()),
+            render_mode: TopViewRenderMode::Wireframe,
+        };
+    }
+
+    pub fn pixel_aligned(&self) -> TopViewCamera {
+        // the center moved by less than a pixel so that the world lands on whole pixels:
+        // views of aligned cameras that differ only in their centers are shifted copies of each other
+        let turned: Coord = self.center.new_rotated(self.angle).new_scaled(self.scale);
+        let aligned: Coord = Coord::new(turned.x().round(), turned.y().round()).new_scaled(1.0 / self.scale);
+
+        let mut camera: TopViewCamera = *self;
+        camera.center = aligned.new_rotated(Angle::new_rad(-self.angle.get_rad()));
+
+        return camera;
+    }
+
+    pub fn pixel_offset(&self, canvas_width: i32, canvas_height: i32) -> Coord {
+        // where the world origin is drawn: view points are scaled and moved by the offset (see pixel);
+        // aligned cameras get whole pixels, so that their views are drawn alike to the last bit
+        let turned: Coord = self.view_point(self.center).new_scaled(self.scale);
+
+        return Coord::new(
+            whole_if_close((canvas_width / 2) as f32 - turned.x()),
+            whole_if_close((canvas_height / 2) as f32 - turned.y()),
+        );
+    }
+
+    pub fn view_point(&self, point: Coord) -> Coord {
+        // the point turned around the world origin by the angle of the view, as in view_vector
+        if self.angle.get_rad() == 0.0 {
+            return point;
+        }
+
+        return point.new_rotated(self.angle);
+    }
+
+    pub fn view_vector(&self, vector: &Vector2D) -> Vector2D {
+        // the vector turned around the world origin by the angle of the view,
+        // to be drawn with Vector2D::draw at the pixel offset
+        if self.angle.get_rad() == 0.0 {
+            return *vector;
+        }
+
+        let mut turned: Vector2D = vector.new_rotated(self.angle);
+        turned.shange_base(self.view_point(vector.base));
+
+        return turned;
+    }
+
+    pub fn pixel(&self, point: Coord, canvas_width: i32, canvas_height: i32) -> Coord {
+        // where a world point is drawn
+        let offset: Coord = self.pixel_offset(canvas_width, canvas_height);
+
+        return self.view_point(point).new_scaled(self.scale).new_offset(offset);
+    }
+
+    pub fn point(&self, pixel: Coord, canvas_width: i32, canvas_height: i32) -> Coord {
+        // world point under a pixel, the other way round than pixel()
+        let offset: Coord = self.pixel_offset(canvas_width, canvas_height);
+        let turned: Coord = Coord::new((pixel.x() - offset.x()) / self.scale, (pixel.y() - offset.y()) / self.scale);
+
+        return turned.new_rotated(Angle::new_rad(-self.angle.get_rad()));
+    }
+
+    pub fn zoom_at(&mut self, pixel: Coord, factor: f32, canvas_width: i32, canvas_height: i32) {
+        // the world point under the pixel stays where it is
+        let point: Coord = self.point(pixel, canvas_width, canvas_height);
+
+        self.scale = (self.scale * factor).clamp(MIN_TOP_VIEW_SCALE, MAX_TOP_VIEW_SCALE);
+
+        let moved: Coord = self.point(pixel, canvas_width, canvas_height);
+        self.center = Coord::new(
+            self.center.x() + point.x() - moved.x(),
+            self.center.y() + point.y() - moved.y(),
+        );
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        // moves the view by pixels, the world follows the mouse
+        let shift: Coord = Coord::new(dx / self.scale, dy / self.scale).new_rotated(Angle::new_rad(-self.angle.get_rad()));
+
+        self.center = Coord::new(self.center.x() - shift.x(), self.center.y() - shift.y());
+    }
+
+    pub fn fit(&mut self, points: &[Coord], canvas_width: i32, canvas_height: i32) {
+        // all points in the view, with a margin, keeping the angle of the view
+        if points.is_empty() || canvas_width <= 0 || canvas_height <= 0 {
+            return;
+        }
+
+        let mut min: Coord = Coord::new(f32::MAX, f32::MAX);
+        let mut max: Coord = Coord::new(f32::MIN, f32::MIN);
+
+        for i in 0..points.len() {
+            // bounds of the points as the view is turned, around the world origin
+            let turned: Coord = points[i].new_rotated(self.angle);
+
+            min = Coord::new(f32::min(min.x(), turned.x()), f32::min(min.y(), turned.y()));
+            max = Coord::new(f32::max(max.x(), turned.x()), f32::max(max.y(), turned.y()));
+        }
+
+        let width: f32 = f32::max(max.x() - min.x(), 1.0);
+        let height: f32 = f32::max(max.y() - min.y(), 1.0);
+
+        self.scale = (FIT_FILL * f32::min(canvas_width as f32 / width, canvas_height as f32 / height))
+            .clamp(MIN_TOP_VIEW_SCALE, MAX_TOP_VIEW_SCALE);
+
+        let middle: Coord = Coord::new((min.x() + max.x()) / 2.0, (min.y() + max.y()) / 2.0);
+        self.center = middle.new_rotated(Angle::new_rad(-self.angle.get_rad()));
+    }
+}
+
+fn whole_if_close(pixels: f32) -> f32 {
+    // whole pixels for pixels only rounding away from them
+    if (pixels - pixels.round()).abs() < WHOLE_PIXEL_TOLERANCE {
+        return pixels.round();
+    }
+
+    return pixels;
+}
+
+pub fn clamp_top_view_scale(scale: f32) -> f32 {
+    // within the limits the window zooms in, the default scale for NaN
+    if scale.is_nan() {
+        return DEFAULT_TOP_VIEW_SCALE;
+    }
+
+    return scale.clamp(MIN_TOP_VIEW_SCALE, MAX_TOP_VIEW_SCALE);
+}
+
+pub fn render_view(
+    simulation: &Simulation,
+    view_mode: ViewMode,
+    scale: f32,
+    top_view_mode: TopViewRenderMode,
+    canvas_width: i32,
+    canvas_height: i32,
+) -> Result<RGBACanvas> {
+    // the top view centered on the controlled agent, occluded as that agent sees it
+    let mut camera: TopViewCamera = TopViewCamera::new(simulation.agent().center, scale);
+    camera.render_mode = top_view_mode.seen_by(simulation.controlled_agent);
+
+    return render_view_with_camera(simulation, view_mode, &camera, canvas_width, canvas_height);
+}
+
+pub fn render_view_with_camera(
+    simulation: &Simulation,
+    view_mode: ViewMode,
+    camera: &TopViewCamera,
+    canvas_width: i32,
+    canvas_height: i32,
+) -> Result<RGBACanvas> {
+    match view_mode {
+        ViewMode::Top => {
+            return simulation.world.render_top_view_from(camera, canvas_width, canvas_height);
+        }
+        ViewMode::FirstPerson => {
+            return simulation.agent().render_first_person_view(canvas_width, canvas_height);
+        }
+        ViewMode::Depth => {
+            return simulation.agent().render_depth_view(canvas_width, canvas_height);
+        }
+        ViewMode::Split(layout) => {
+            return render_split_view(simulation, camera, &layout, None, canvas_width, canvas_height);
+        }
+        ViewMode::Parallel(_) | ViewMode::CrossEye(_) | ViewMode::Anaglyph(_) => {
+            return render_stereo_view(simulation.agent(), view_mode, canvas_width, canvas_height);
+        }
+    }
+}
+
+pub fn render_stereo_view(agent: &Agent, view_mode: ViewMode, canvas_width: i32, canvas_height: i32) -> Result<RGBACanvas> {
+    // first-person views from both eyes of the agent, which are across its heading around the anchor;
+    // other view modes get the plain first-person view
+    match view_mode {
+        ViewMode::Parallel(eye_distance) => {
+            return render_side_by_side(agent, eye_distance, false, canvas_width, canvas_height);
+        }
+        ViewMode::CrossEye(eye_distance) => {
+            return render_side_by_side(agent, eye_distance, true, canvas_width, canvas_height);
+        }
+        ViewMode::Anaglyph(eye_distance) => {
+            return render_anaglyph(agent, eye_distance, canvas_width, canvas_height);
+        }
+        _ => {
+            return agent.render_first_person_view(canvas_width, canvas_height);
+        }
+    }
+}
+
+fn render_side_by_side(agent: &Agent, eye_distance: f32, is_crossed: bool, canvas_width: i32, canvas_height: i32) -> Result<RGBACanvas> {
+    // each eye gets half of the canvas, for cross-eyed viewing the left eye's view is on the right
+    let half_width: i32 = i32::max(canvas_width / 2, 1);
+
+    let left_eye_view: RGBACanvas = agent.render_first_person_view_from(agent.eye_position(-eye_distance / 2.0), half_width, canvas_height)?;
+    let right_eye_view: RGBACanvas = agent.render_first_person_view_from(agent.eye_position(eye_distance / 2.0), half_width, canvas_height)?;
+
+    let mut rendered_view: RGBACanvas = RGBACanvas::new_black(canvas_width, canvas_height)?;
+
+    if is_crossed {
+        rendered_view.put_canvas(0, 0, &right_eye_view);
+        rendered_view.put_canvas(canvas_width - half_width, 0, &left_eye_view);
+    } else {
+        rendered_view.put_canvas(0, 0, &left_eye_view);
+        rendered_view.put_canvas(canvas_width - half_width, 0, &right_eye_view);
+    }
+
+    return Ok(rendered_view);
+}
+
+fn render_anaglyph(agent: &Agent, eye_distance: f32, canvas_width: i32, canvas_height: i32) -> Result<RGBACanvas> {
+    // half-color anaglyph: red is the brightness seen by the left eye, green and blue are the right eye's colors
+    let left_eye_view: RGBACanvas = agent.render_first_person_view_from(agent.eye_position(-eye_distance / 2.0), canvas_width, canvas_height)?;
+    let mut rendered_view: RGBACanvas = agent.render_first_person_view_from(agent.eye_position(eye_distance / 2.0), canvas_width, canvas_height)?;
+
+    for j in 0..canvas_height {
+        for i in 0..canvas_width {
+            let left: RGBAColor = left_eye_view.get_color(i, j);
+            let right: RGBAColor = rendered_view.get_color(i, j);
+            let brightness: f32 = 0.299 * left.r as f32 + 0.587 * left.g as f32 + 0.114 * left.b as f32;
+
+            rendered_view.put_pixel_simple(i, j, RGBAColor::new_rgb(brightness as u8, right.g, right.b));
+        }
+    }
+
+    return Ok(rendered_view);
+}
+
+pub fn render_split_view(
+    simulation: &Simulation,
+    camera: &TopViewCamera,
+    layout: &SplitLayout,
+    backdrop: Option<&mut TopViewBackdrop>, // reused between frames, if given
+    canvas_width: i32,
+    canvas_height: i32,
+) -> Result<RGBACanvas> {
+    // both views of the same tick in one canvas, the top view with the rays of the controlled agent
+    let (top_x, top_y, top_width, top_height) = layout.top_view_area(canvas_width, canvas_height);
+    let (first_person_x, first_person_y, first_person_width, first_person_height) = layout.first_person_area(canvas_width, canvas_height);
+
+    let top_view: RGBACanvas = match backdrop {
+        Some(backdrop) => {
+            let mut top_view: RGBACanvas = backdrop.render(&simulation.world, camera, top_width, top_height)?;
+            draw_view_rays(simulation.agent(), &mut top_view, &camera.pixel_aligned());
+            top_view
+        }
+        None => {
+            let mut top_view: RGBACanvas = simulation.world.render_top_view_from(camera, top_width, top_height)?;
+            draw_view_rays(simulation.agent(), &mut top_view, camera);
+            top_view
+        }
+    };
+
+    let first_person_view: RGBACanvas = simulation.agent().render_first_person_view(first_person_width, first_person_height)?;
+
+    let mut rendered_view: RGBACanvas = RGBACanvas::new_black(canvas_width, canvas_height)?;
+    rendered_view.put_canvas(top_x, top_y, &top_view);
+    rendered_view.put_canvas(first_person_x, first_person_y, &first_person_view);
+
+    return Ok(rendered_view);
+}
+
+pub fn draw_view_rays(agent: &Agent, canvas: &mut RGBACanvas, camera: &TopViewCamera) {
+    // over the top view: the field of view of the agent as a cone as far as it can see,
+    // and some of its view rays, each up to what it hits
+    let eye: Coord = agent.shape.anchor;
+    let heading: f32 = agent.angle().get_rad();
+    let half_field_of_view: f32 = agent.field_of_view().get_rad() / 2.0;
+    let reach: f32 = agent.max_view_distance();
+
+    let cone_color: RGBAColor = RGBAColor::new_rgba(255, 255, 0, 96);
+    let ray_color: RGBAColor = RGBAColor::new_rgba(255, 127, 0, 160);
+    let hit_color: RGBAColor = RGBAColor::new_rgb(255, 127, 0);
+
+    let mut previous: Coord = eye;
+
+    for i in 0..(CONE_ARC_SEGMENTS + 1) {
+        let angle: f32 = heading - half_field_of_view + 2.0 * half_field_of_view * (i as f32) / (CONE_ARC_SEGMENTS as f32);
+        let point: Coord = eye.new_offset(Coord::new(reach, 0.0).new_rotated(Angle::new_rad(angle)));
+
+        draw_overlay_line(canvas, camera, previous, point, cone_color);
+        previous = point;
+    }
+
+    draw_overlay_line(canvas, camera, previous, eye, cone_color);
+
+    let hits: Vec<Option<WallHit>> = agent.cast_view_rays(VIEW_RAY_OVERLAY_COUNT);
+
+    for i in 0..hits.len() {
+        match hits[i] {
+            Some(hit) => {
+                draw_overlay_line(canvas, camera, eye, hit.point, ray_color);
+
+                let pixel: Coord = camera.pixel(hit.point, canvas.width, canvas.height);
+                canvas.put_square(pixel.get_x_i(), pixel.get_y_i(), 1, hit_color);
+            }
+            None => {
+                let angle: Angle = Angle::new_rad(heading + agent.view_ray_angle(i as i32, VIEW_RAY_OVERLAY_COUNT).get_rad());
+                draw_overlay_line(canvas, camera, eye, eye.new_offset(Coord::new(reach, 0.0).new_rotated(angle)), ray_color);
+            }
+        }
+    }
+}
+
+fn draw_overlay_line(canvas: &mut RGBACanvas, camera: &TopViewCamera, from: Coord, to: Coord, color: RGBAColor) {
+    let line: Vector2D = Vector2D::new(
+        from,
+        Coord::new(to.x() - from.x(), to.y() - from.y()),
+        LinearTexture::new_plain(color),
+    );
+
+    camera.view_vector(&line).draw(canvas, camera.pixel_offset(canvas.width, canvas.height), camera.scale, 1.0);
+}
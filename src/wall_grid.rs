@@ -0,0 +1,416 @@
+// uniform grid over static walls -- "world segmentation"
+// every cell keeps indices of walls passing through it,
+// so range queries and ray casts only look at walls in the cells they touch
+// instead of testing every wall in the world
+
+use crate::{
+    common_structs::{AlignedBox, Coord},
+    vector_2d::Vector2D,
+};
+
+pub const DEFAULT_CELL_SIZE: f32 = 2.0;
+const MAX_CELLS_PER_SIDE: f32 = 1024.0;
+
+#[derive(Clone)]
+pub struct WallGrid {
+    walls: Vec<Vector2D>,
+    bounds: AlignedBox,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>, // wall indices, row-major
+}
+
+impl WallGrid {
+    pub fn new() -> WallGrid {
+        return WallGrid::from_walls(Vec::new());
+    }
+
+    pub fn from_walls(walls: Vec<Vector2D>) -> WallGrid {
+        return WallGrid::with_cell_size(walls, DEFAULT_CELL_SIZE);
+    }
+
+    pub fn with_cell_size(walls: Vec<Vector2D>, cell_size: f32) -> WallGrid {
+        let mut bounds: AlignedBox = AlignedBox::new(0.0, 0.0, 0.0, 0.0);
+
+        for i in 0..walls.len() {
+            let wall_box: AlignedBox = walls[i].bounding_box();
+
+            if i == 0 {
+                bounds = wall_box;
+            } else {
+                bounds = AlignedBox::new(
+                    f32::min(bounds.x_0, wall_box.x_0),
+                    f32::min(bounds.y_0, wall_box.y_0),
+                    f32::max(bounds.x_1, wall_box.x_1),
+                    f32::max(bounds.y_1, wall_box.y_1),
+                );
+            }
+        }
+
+        // very big worlds get coarser cells, so that the grid itself stays small
+        let cell_size: f32 = f32::max(
+            if cell_size > 0.0 {cell_size} else {DEFAULT_CELL_SIZE},
+            f32::max(bounds.width(), bounds.height()) / MAX_CELLS_PER_SIDE,
+        );
+
+        let columns: usize = (bounds.width() / cell_size) as usize + 1;
+        let rows: usize = (bounds.height() / cell_size) as usize + 1;
+
+        let mut grid: WallGrid = WallGrid {
+            walls: Vec::new(),
+            bounds,
+            cell_size,
+            columns,
+            rows,
+            cells: vec![Vec::new(); columns * rows],
+        };
+
+        for i in 0..walls.len() {
+            grid.insert(i, &walls[i]);
+        }
+
+        grid.walls = walls;
+
+        return grid;
+    }
+
+    pub fn walls(&self) -> &Vec<Vector2D> {
+        return &self.walls;
+    }
+
+    pub fn wall(&self, index: usize) -> &Vector2D {
+        return &self.walls[index];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.walls.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.walls.is_empty();
+    }
+
+    pub fn query_box(&self, area: &AlignedBox) -> Vec<usize> {
+        // indices of all walls that pass through given area, each only once,
+        // and maybe a few more near it: those that pass through the same cells and whose bounding boxes overlap the area
+
+        let mut found: Vec<usize> = Vec::new();
+
+        if self.walls.is_empty() || self.bounds.box_intersection(area).is_none() {
+            return found;
+        }
+
+        let (c_0, r_0) = self.cell_of(Coord::new(area.x_0, area.y_0));
+        let (c_1, r_1) = self.cell_of(Coord::new(area.x_1, area.y_1));
+
+        for r in r_0..(r_1 + 1) {
+            for c in c_0..(c_1 + 1) {
+                for &index in self.cells[r * self.columns + c].iter() {
+                    if self.walls[index].bounding_box().box_intersection(area).is_some() {
+                        found.push(index);
+                    }
+                }
+            }
+        }
+
+        found.sort_unstable();
+        found.dedup();
+
+        return found;
+    }
+
+    pub fn query_range(&self, location: Coord, range: f32) -> Vec<usize> {
+        return self.query_box(&AlignedBox::around_point(location, range));
+    }
+
+    pub fn circle_penetration(&self, center: Coord, radius: f32) -> Coord {
+        // how far a circle has to be moved to stop overlapping the walls,
+        // summed over all walls it touches (their ends included)
+
+        let mut push: Coord = Coord::new(0.0, 0.0);
+
+        for &i in self.query_range(center, radius).iter() {
+            let closest: Coord = self.walls[i].closest_point(center);
+            let away: Coord = Coord::new(center.x() - closest.x(), center.y() - closest.y());
+            let distance: f32 = f32::sqrt(away.x() * away.x() + away.y() * away.y());
+
+            if distance < radius && distance > 0.0 {
+                push = push.new_offset(away.new_scaled((radius - distance) / distance));
+            }
+        }
+
+        return push;
+    }
+
+    pub fn cast_ray(&self, ray: &Vector2D) -> Option<(usize, Vector2D)> {
+        // nearest wall hit by the ray
+        // returns index of the wall and the intersection vector as given by Vector2D::intersect
+
+        match self.cast_ray_params(ray) {
+            Some((index, _, _)) => {
+                return Some((index, ray.intersect(&self.walls[index]).unwrap()));
+            }
+            None => {
+                return None;
+            }
+        }
+    }
+
+    pub fn cast_ray_params(&self, ray: &Vector2D) -> Option<(usize, f32, f32)> {
+        // nearest wall hit by the ray, walking the grid cells along the ray (Amanatides-Woo)
+        // returns index of the wall, parameter t along the ray (0.0 at base, 1.0 at tip)
+        // and parameter u along the wall, as given by Vector2D::intersection_params
+
+        if self.walls.is_empty() || ray.length() <= 0.0 {
+            return None;
+        }
+
+        let (t_enter, t_exit) = match self.clip_to_bounds(ray) {
+            Some(range) => range,
+            None => return None,
+        };
+
+        let dir: Coord = ray.tip;
+        let entry_point: Coord = ray.base.new_offset(dir.new_scaled(t_enter));
+        let (mut c, mut r) = self.cell_of(entry_point);
+
+        let step_c: i64 = if dir.x() > 0.0 {1} else {-1};
+        let step_r: i64 = if dir.y() > 0.0 {1} else {-1};
+
+        let t_delta_c: f32 = if dir.x() != 0.0 {self.cell_size / dir.x().abs()} else {f32::INFINITY};
+        let t_delta_r: f32 = if dir.y() != 0.0 {self.cell_size / dir.y().abs()} else {f32::INFINITY};
+
+        let mut t_max_c: f32 = if dir.x() != 0.0 {
+            let boundary: f32 = self.bounds.x_0 + (c as f32 + if step_c > 0 {1.0} else {0.0}) * self.cell_size;
+            (boundary - ray.base.x()) / dir.x()
+        } else {
+            f32::INFINITY
+        };
+        let mut t_max_r: f32 = if dir.y() != 0.0 {
+            let boundary: f32 = self.bounds.y_0 + (r as f32 + if step_r > 0 {1.0} else {0.0}) * self.cell_size;
+            (boundary - ray.base.y()) / dir.y()
+        } else {
+            f32::INFINITY
+        };
+
+        let mut nearest: Option<(usize, f32, f32)> = None;
+        let mut nearest_t: f32 = f32::INFINITY;
+
+        loop {
+            for &index in self.cells[r * self.columns + c].iter() {
+                if let Some((t, u)) = ray.intersection_params(&self.walls[index]) {
+                    if t < nearest_t {
+                        nearest_t = t;
+                        nearest = Some((index, t, u));
+                    }
+                }
+            }
+
+            let t_cell_exit: f32 = f32::min(t_max_c, t_max_r);
+
+            // a hit inside current cell can not be beaten by anything further along the ray
+            if nearest_t <= t_cell_exit || t_cell_exit > t_exit {
+                break;
+            }
+
+            if t_max_c < t_max_r {
+                let next: i64 = c as i64 + step_c;
+                if next < 0 || next >= self.columns as i64 {
+                    break;
+                }
+                c = next as usize;
+                t_max_c += t_delta_c;
+            } else {
+                let next: i64 = r as i64 + step_r;
+                if next < 0 || next >= self.rows as i64 {
+                    break;
+                }
+                r = next as usize;
+                t_max_r += t_delta_r;
+            }
+        }
+
+        return nearest;
+    }
+
+    fn insert(&mut self, index: usize, wall: &Vector2D) {
+        // put wall index into every cell its segment passes through
+        let wall_box: AlignedBox = wall.bounding_box();
+        let (c_0, r_0) = self.cell_of(Coord::new(wall_box.x_0, wall_box.y_0));
+        let (c_1, r_1) = self.cell_of(Coord::new(wall_box.x_1, wall_box.y_1));
+
+        for r in r_0..(r_1 + 1) {
+            for c in c_0..(c_1 + 1) {
+                if segment_touches_box(wall, &self.cell_box(c, r)) {
+                    self.cells[r * self.columns + c].push(index);
+                }
+            }
+        }
+    }
+
+    fn cell_of(&self, point: Coord) -> (usize, usize) {
+        // cell containing the point, clamped to the grid
+        let c: f32 = ((point.x() - self.bounds.x_0) / self.cell_size).floor();
+        let r: f32 = ((point.y() - self.bounds.y_0) / self.cell_size).floor();
+
+        return (
+            (c.max(0.0) as usize).min(self.columns - 1),
+            (r.max(0.0) as usize).min(self.rows - 1),
+        );
+    }
+
+    fn cell_box(&self, c: usize, r: usize) -> AlignedBox {
+        let x_0: f32 = self.bounds.x_0 + c as f32 * self.cell_size;
+        let y_0: f32 = self.bounds.y_0 + r as f32 * self.cell_size;
+
+        return AlignedBox::new(x_0, y_0, x_0 + self.cell_size, y_0 + self.cell_size);
+    }
+
+    fn clip_to_bounds(&self, ray: &Vector2D) -> Option<(f32, f32)> {
+        // parameter range of the ray lying inside the grid (slab method)
+        let grid_box: AlignedBox = AlignedBox::new(
+            self.bounds.x_0,
+            self.bounds.y_0,
+            self.bounds.x_0 + self.columns as f32 * self.cell_size,
+            self.bounds.y_0 + self.rows as f32 * self.cell_size,
+        );
+
+        return clip_segment(ray.base, ray.tip, &grid_box);
+    }
+}
+
+pub(crate) fn clip_segment(base: Coord, dir: Coord, area: &AlignedBox) -> Option<(f32, f32)> {
+    // Liang-Barsky clipping of base + t * dir, t in 0.0..1.0, against the box
+    let mut t_0: f32 = 0.0;
+    let mut t_1: f32 = 1.0;
+
+    let checks: [(f32, f32); 4] = [
+        (-dir.x(), base.x() - area.x_0),
+        (dir.x(), area.x_1 - base.x()),
+        (-dir.y(), base.y() - area.y_0),
+        (dir.y(), area.y_1 - base.y()),
+    ];
+
+    for (p, q) in checks {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t: f32 = q / p;
+
+            if p < 0.0 {
+                t_0 = f32::max(t_0, t);
+            } else {
+                t_1 = f32::min(t_1, t);
+            }
+        }
+    }
+
+    if t_0 > t_1 {
+        return None;
+    }
+
+    return Some((t_0, t_1));
+}
+
+fn segment_touches_box(segment: &Vector2D, area: &AlignedBox) -> bool {
+    return clip_segment(segment.base, segment.tip, area).is_some();
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{common_structs::RGBAColor, linear_texture::LinearTexture};
+
+    fn random_coord(rng: &mut ChaCha8Rng, half_size: f32) -> Coord {
+        return Coord::new(rng.gen_range(-half_size..half_size), rng.gen_range(-half_size..half_size));
+    }
+
+    fn nearest_by_scan(walls: &[Vector2D], ray: &Vector2D) -> Option<f32> {
+        // parameter along the ray of the nearest hit, looking at every wall
+        let mut nearest: Option<f32> = None;
+
+        for i in 0..walls.len() {
+            if let Some((t, _)) = ray.intersection_params(&walls[i]) {
+                nearest = match nearest {
+                    Some(nearest_t) if nearest_t <= t => Some(nearest_t),
+                    _ => Some(t),
+                };
+            }
+        }
+
+        return nearest;
+    }
+
+    #[test]
+    fn query_box_matches_scan_over_all_walls() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(5);
+        let texture: LinearTexture = LinearTexture::new_plain(RGBAColor::new());
+
+        let mut walls: Vec<Vector2D> = Vec::new();
+
+        for _ in 0..300 {
+            walls.push(Vector2D::new(random_coord(&mut rng, 40.0), random_coord(&mut rng, 6.0), texture));
+        }
+
+        let grid: WallGrid = WallGrid::with_cell_size(walls.clone(), 1.3);
+
+        for _ in 0..500 {
+            // boxes inside, across and outside of the walls' area, down to a single point
+            let corner: Coord = random_coord(&mut rng, 60.0);
+            let size: Coord = Coord::new(rng.gen_range(0.0..20.0), rng.gen_range(0.0..20.0));
+            let area: AlignedBox = AlignedBox::new(corner.x(), corner.y(), corner.x() + size.x(), corner.y() + size.y());
+
+            let found: Vec<usize> = grid.query_box(&area);
+
+            for i in 0..walls.len() {
+                if segment_touches_box(&walls[i], &area) {
+                    assert!(found.contains(&i), "wall {} passes through the area, but isn't found", i);
+                }
+            }
+
+            for k in 0..found.len() {
+                assert!(walls[found[k]].bounding_box().box_intersection(&area).is_some(), "wall {} is nowhere near the area", found[k]);
+                assert!(k == 0 || found[k - 1] < found[k], "found more than once or out of order");
+            }
+        }
+
+        assert!(WallGrid::new().query_box(&AlignedBox::around_point(Coord::new(0.0, 0.0), 10.0)).is_empty());
+    }
+
+    #[test]
+    fn cast_ray_matches_scan_over_all_walls() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(3);
+        let texture: LinearTexture = LinearTexture::new_plain(RGBAColor::new());
+
+        let mut walls: Vec<Vector2D> = Vec::new();
+
+        for _ in 0..300 {
+            walls.push(Vector2D::new(random_coord(&mut rng, 40.0), random_coord(&mut rng, 3.0), texture));
+        }
+
+        // fine cells, so rays cross many of them
+        let grid: WallGrid = WallGrid::with_cell_size(walls.clone(), 0.7);
+
+        for _ in 0..2000 {
+            // rays start inside and outside of the walls' area, short and long
+            let ray: Vector2D = Vector2D::new(random_coord(&mut rng, 60.0), random_coord(&mut rng, 80.0), texture);
+
+            match (grid.cast_ray_params(&ray), nearest_by_scan(&walls, &ray)) {
+                (Some((index, t, _)), Some(scanned_t)) => {
+                    assert!((t - scanned_t).abs() < 0.00001, "grid hit at {}, scan at {}", t, scanned_t);
+                    assert!(ray.intersection_params(&walls[index]).is_some());
+                }
+                (None, None) => {}
+                (grid_hit, scanned_hit) => {
+                    panic!("grid found {:?}, scan found {:?}", grid_hit.map(|hit| hit.1), scanned_hit);
+                }
+            }
+        }
+    }
+}
@@ -1,94 +1,691 @@
 // here, the object describing the world
 
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
 use crate::{
+    agent::Agent,
+    error::Result,
     common_structs::{
         Coord,
+        AlignedBox,
+        Dot,
+        RGBAColor,
     },
-    rgba_canvas::RGBACanvas,
-    shape::Shape,
+    dynamic_object::{DynamicObject, DynamicObjectDescription, ObstacleSegment},
+    linear_texture::LinearTexture,
+    rgba_canvas::{FillRule, RGBACanvas},
+    shape::{Shape, ShapeDescription, WorldSetup},
     vector_2d::Vector2D,
+    view::{TopViewCamera, TopViewRenderMode},
+    wall_grid::{clip_segment, WallGrid},
 };
 
+// of walls and shapes in the top view, in pixels
+const LINE_WIDTH: f32 = 1.0;
+
+// how far in front of a point in the top view a wall can be and still not hide it
+const OCCLUSION_TOLERANCE: f32 = 0.01;
+// pixels between points of a wall checked for occlusion in the top view, where the result changes the pixels in between
+// are bisected, so a gap or an obstacle narrower than this can be missed
+const OCCLUSION_STEP: usize = 4;
+
+// closed shapes are filled with their color at this alpha in the filled wireframe
+const FILL_ALPHA: u8 = 80;
+// static shapes are filled with their color this dark in the solid view, so their outlines stand out
+const SOLID_SHADE: f32 = 0.4;
+
+static STATIC_VERSIONS: AtomicU64 = AtomicU64::new(0);
+
+// what the occluded wireframe is seen from, walls are in the wall index
+struct Occlusion {
+    eye: Coord,
+    occluders: Vec<Vector2D>, // sides of objects and of other agents
+}
 
 pub struct World {
-    pub shapes: Vec<Shape>,
-    pub walls: Vec<Vector2D>,
+    pub shapes: Vec<Shape>, // static shapes, as they were added
+    pub wall_index: Arc<WallGrid>, // walls and their spatial index, rebuilt whenever walls are added and shared with agents
+    pub objects: Vec<DynamicObject>,
+    pub agents: Vec<Agent>, // agent's id is its index here
     pub is_updated: bool,
+    static_version: u64,
 }
 
 impl World {
     pub fn new() -> World {
-        let mut new_world: World = World {
+        let new_world: World = World {
             shapes: Vec::new(),
-            walls: Vec::new(),
+            wall_index: Arc::new(WallGrid::new()),
+            objects: Vec::new(),
+            agents: Vec::new(),
             is_updated: true,
+            static_version: next_static_version(),
         };
 
-        new_world.add_shapes();
-
         return new_world;
     }
 
+    pub fn from_setup(setup: &WorldSetup) -> Result<World> {
+        let mut new_world: World = World::new();
+
+        new_world.add_shape_descriptions(&setup.world_shapes)?;
+        new_world.add_dynamic_objects(&setup.dynamic_objects)?;
+
+        new_world.add_agent(Agent::from_setup(setup));
+
+        for i in 0..setup.other_agents.len() {
+            let id: usize = new_world.agents.len();
+            new_world.add_agent(Agent::from_descr(id, &setup.other_agents[i], setup));
+        }
+
+        return Ok(new_world);
+    }
+
 /////////////////////////////////////////////////////////
 
-    pub fn render_top_view(&self, agent_shape: &Shape, center: Coord, scale: f32, canvas_width: i32, canvas_height: i32) -> RGBACanvas{
+    pub fn render_top_view(
+        &self,
+        center: Coord,
+        scale: f32,
+        render_mode: TopViewRenderMode,
+        canvas_width: i32,
+        canvas_height: i32,
+    ) -> Result<RGBACanvas> {
         // create top view for the world that is scaled and shifted
-        //
-        // centering:
-        // center coord should be mapped to the center of canvas
-        // somewhat roundabout way: having center coordinate, the dimentions of canvas and scale
-        // find coordinate of old origin relative to new origin
-
-        let mut rendered_view: RGBACanvas = RGBACanvas::new_black(canvas_width, canvas_height);
-        let new_origin: Coord = Coord::new(
-            -(center.x() - ((canvas_width / 2) as f32) / scale),
-            -(center.y() - ((canvas_height / 2) as f32) / scale),
+        let mut camera: TopViewCamera = TopViewCamera::new(center, scale);
+        camera.render_mode = render_mode;
+
+        return self.render_top_view_from(&camera, canvas_width, canvas_height);
+    }
+
+    pub fn render_top_view_from(&self, camera: &TopViewCamera, canvas_width: i32, canvas_height: i32) -> Result<RGBACanvas> {
+        // top view as the camera sees it: centered on its center, scaled and turned,
+        // shapes drawn in the render mode of the camera
+        let mut rendered_view: RGBACanvas = RGBACanvas::new_black(canvas_width, canvas_height)?;
+
+        self.draw_static_layer(&mut rendered_view, camera);
+        self.draw_dynamic_layer(&mut rendered_view, camera);
+
+        return Ok(rendered_view);
+    }
+
+    pub fn draw_static_layer(&self, canvas: &mut RGBACanvas, camera: &TopViewCamera) {
+        // static shapes and their walls, which change only with static_version
+        // (except in the occluded wireframe, which depends on where the agent is)
+        if is_filled(camera.render_mode) {
+            for i in 0..self.shapes.len() {
+                self.shapes[i].draw_filled(canvas, camera, fill_color(&self.shapes[i], true, camera.render_mode), FillRule::NonZero, true);
+            }
+        }
+
+        let occlusion: Option<Occlusion> = self.occlusion(camera.render_mode);
+
+        let walls: &Vec<Vector2D> = self.walls();
+
+        for i in 0..walls.len() {
+            self.draw_element(&walls[i], occlusion.as_ref(), canvas, camera);
+        }
+    }
+
+    pub fn draw_dynamic_layer(&self, canvas: &mut RGBACanvas, camera: &TopViewCamera) {
+        // objects and agents, over the static layer
+        if is_filled(camera.render_mode) {
+            for i in 0..self.objects.len() {
+                let shape: &Shape = &self.objects[i].shape;
+                shape.draw_filled(canvas, camera, fill_color(shape, false, camera.render_mode), FillRule::NonZero, true);
+            }
+
+            for k in 0..self.agents.len() {
+                let shape: &Shape = &self.agents[k].shape;
+                shape.draw_filled(canvas, camera, fill_color(shape, false, camera.render_mode), FillRule::NonZero, true);
+            }
+        }
+
+        // objects are hidden behind walls, other objects and agents in the occluded wireframe, agents are always seen
+        let occlusion: Option<Occlusion> = self.occlusion(camera.render_mode);
+
+        for i in 0..self.objects.len() {
+            for j in 0..self.objects[i].shape.elements.len() {
+                let element: Vector2D = self.objects[i].shape.elements[j].new_shifted(self.objects[i].shape.anchor);
+                self.draw_element(&element, occlusion.as_ref(), canvas, camera);
+            }
+        }
+
+        for k in 0..self.agents.len() {
+            let agent_shape: &Shape = &self.agents[k].shape;
+
+            for i in 0..agent_shape.elements.len() {
+                let element: Vector2D = agent_shape.elements[i].new_shifted(agent_shape.anchor);
+                self.draw_element(&element, None, canvas, camera);
+            }
+        }
+    }
+
+    pub fn static_version(&self) -> u64 {
+        // changes whenever static shapes do, and differs between worlds, e.g. to know when a drawing of them is outdated
+        return self.static_version;
+    }
+
+    fn occlusion(&self, render_mode: TopViewRenderMode) -> Option<Occlusion> {
+        // where walls are seen from in the occluded wireframe, and what moves in between besides walls
+        match render_mode {
+            TopViewRenderMode::OccludedWireframe(agent_id) if agent_id < self.agents.len() => {
+                let agent: &Agent = &self.agents[agent_id];
+                let obstacles: Vec<ObstacleSegment> = self.obstacles_for_agent(agent.id);
+                let mut occluders: Vec<Vector2D> = Vec::with_capacity(obstacles.len());
+
+                for i in 0..obstacles.len() {
+                    occluders.push(obstacles[i].segment);
+                }
+
+                return Some(Occlusion { eye: agent.shape.anchor, occluders });
+            }
+            _ => return None,
+        }
+    }
+
+    fn draw_element(&self, element: &Vector2D, occlusion: Option<&Occlusion>, canvas: &mut RGBACanvas, camera: &TopViewCamera) {
+        // one wall or side of a shape, in world coordinates
+        let width: i32 = canvas.width;
+        let height: i32 = canvas.height;
+
+        match (camera.render_mode, occlusion) {
+            (TopViewRenderMode::Vertices(marker), _) => {
+                let color: RGBAColor = element.texture.get_color(element.length(), 0.0);
+                Dot::new(camera.pixel(element.base, width, height), color, marker).draw(canvas);
+            }
+            (TopViewRenderMode::OccludedWireframe(_), Some(occlusion)) => {
+                // a point of the element for every pixel of its length on the canvas, drawn if nothing is in between it and the eye;
+                // pixels are an affine map of world points, so the part of the element on the canvas has the same parameters in both
+                let base: Coord = camera.pixel(element.base, width, height);
+                let tip: Coord = camera.pixel(element.tip_point(), width, height);
+                let canvas_box: AlignedBox = AlignedBox::new(-1.0, -1.0, (width + 1) as f32, (height + 1) as f32);
+
+                let (t_0, t_1): (f32, f32) = match clip_segment(base, Coord::new(tip.x() - base.x(), tip.y() - base.y()), &canvas_box) {
+                    Some(range) => range,
+                    None => return,
+                };
+
+                let steps: usize = ((t_1 - t_0) * element.length() * camera.scale).ceil() as usize;
+                let along = |t: usize| -> f32 { t_0 + (t_1 - t_0) * (t as f32 + 0.5) / (steps as f32) };
+                let point = |t: usize| -> Coord { element.base.new_offset(element.tip.new_scaled(along(t))) };
+
+                let visibility: Vec<bool> = visible_steps(steps, |t| self.is_visible_from(occlusion, point(t)));
+
+                for t in 0..steps {
+                    if visibility[t] {
+                        let pixel: Coord = camera.pixel(point(t), width, height);
+                        canvas.put_pixel(pixel.get_x_i(), pixel.get_y_i(), element.texture.get_color(element.length(), along(t) * element.length()));
+                    }
+                }
+            }
+            _ => {
+                camera.view_vector(element).draw(canvas, camera.pixel_offset(width, height), camera.scale, LINE_WIDTH);
+            }
+        }
+    }
+
+    fn is_visible_from(&self, occlusion: &Occlusion, point: Coord) -> bool {
+        // nothing crosses the line of sight, except at its very end, where the point itself can be on a wall
+        let line_of_sight: Vector2D = Vector2D::new(
+            occlusion.eye,
+            Coord::new(point.x() - occlusion.eye.x(), point.y() - occlusion.eye.y()),
+            LinearTexture::new_plain(RGBAColor::new()),
         );
+        let length: f32 = line_of_sight.length();
 
-        for i in 0..self.walls.len() {
-            self.walls[i].draw_simple_s(&mut rendered_view, new_origin, scale);
-            // self.walls[i].draw_smooth(&mut rendered_view);
+        if let Some((_, t, _)) = self.wall_index.cast_ray_params(&line_of_sight) {
+            if (1.0 - t) * length >= OCCLUSION_TOLERANCE {
+                return false;
+            }
         }
 
-        for i in 0..agent_shape.elements.len() {
-            agent_shape.elements[i].new_shifted(agent_shape.anchor).draw_simple_s(&mut rendered_view, new_origin, scale);   
+        for i in 0..occlusion.occluders.len() {
+            if let Some((t, _)) = line_of_sight.intersection_params(&occlusion.occluders[i]) {
+                if (1.0 - t) * length >= OCCLUSION_TOLERANCE {
+                    return false;
+                }
+            }
         }
 
-        return rendered_view;
+        return true;
     }
 
-    fn add_shapes(&mut self) {
-        // maybe for dynamic objects
+    pub fn outline_points(&self) -> Vec<Coord> {
+        // ends of all walls, and anchors of objects and agents, e.g. to fit the whole world into a view
+        let walls: &Vec<Vector2D> = self.walls();
+        let mut points: Vec<Coord> = Vec::with_capacity(walls.len() * 2 + self.objects.len() + self.agents.len());
 
+        for i in 0..walls.len() {
+            points.push(walls[i].base);
+            points.push(walls[i].tip_point());
+        }
+
+        for i in 0..self.objects.len() {
+            points.push(self.objects[i].position());
+        }
+
+        for i in 0..self.agents.len() {
+            points.push(self.agents[i].center);
+        }
+
+        return points;
+    }
+
+    pub fn add_dynamic_objects(&mut self, object_descriptions: &Vec<DynamicObjectDescription>) -> Result<()> {
+        let mut objects: Vec<DynamicObject> = Vec::with_capacity(object_descriptions.len());
+
+        for i in 0..object_descriptions.len() {
+            objects.push(DynamicObject::from_descr(self.objects.len() + i, &object_descriptions[i])?);
+        }
+
+        self.objects.append(&mut objects);
+
+        return Ok(());
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        // advance all dynamic objects by one time step
+
+        for object in self.objects.iter_mut() {
+            object.step(dt, &self.wall_index);
+        }
+
+        if !self.objects.is_empty() {
+            self.is_updated = true;
+        }
+    }
+
+    pub fn push_objects(&mut self, center: Coord, radius: f32) -> Coord {
+        // pushable objects overlapping a circle (e.g. the agent) are moved out of its way;
+        // returns how much the pusher itself has to move back,
+        // for objects that are stuck against walls
+
+        let mut pusher_correction: Coord = Coord::new(0.0, 0.0);
+
+        for object in self.objects.iter_mut() {
+            if !object.is_pushable {
+                continue;
+            }
+
+            let mut away: Coord = Coord::new(object.position().x() - center.x(), object.position().y() - center.y());
+            let mut distance: f32 = f32::sqrt(away.x() * away.x() + away.y() * away.y());
+
+            if distance == 0.0 {
+                away = Coord::new(1.0, 0.0);
+                distance = 1.0;
+            }
+
+            let overlap: f32 = radius + object.radius() - distance;
+
+            if overlap > 0.0 {
+                let direction: Coord = away.new_scaled(1.0 / distance);
+
+                object.shift(direction.new_scaled(overlap));
+                object.resolve_walls(&self.wall_index);
+
+                let remaining_overlap: f32 = radius + object.radius() - f32::sqrt(
+                    (object.position().x() - center.x()) * (object.position().x() - center.x()) +
+                    (object.position().y() - center.y()) * (object.position().y() - center.y())
+                );
+
+                if remaining_overlap > 0.0 {
+                    pusher_correction = pusher_correction.new_offset(direction.new_scaled(-remaining_overlap));
+                }
+
+                self.is_updated = true;
+            }
+        }
+
+        return pusher_correction;
+    }
+
+    pub fn add_agent(&mut self, mut agent: Agent) -> usize {
+        // the agent gets the next free id, and learns about walls of this world
+        let id: usize = self.agents.len();
+
+        agent.id = id;
+        agent.update_wall_index(Arc::clone(&self.wall_index));
+        self.agents.push(agent);
+
+        return id;
+    }
+
+    pub fn obstacles_for_agent(&self, agent_id: usize) -> Vec<ObstacleSegment> {
+        // everything that moves, except the agent itself
+        let mut segments: Vec<ObstacleSegment> = self.dynamic_segments();
+
+        for i in 0..self.agents.len() {
+            if self.agents[i].id != agent_id {
+                segments.append(&mut self.agents[i].segments());
+            }
+        }
+
+        return segments;
+    }
+
+    pub fn dynamic_segments(&self) -> Vec<ObstacleSegment> {
+        // sides of all dynamic objects in world coordinates
+        let mut segments: Vec<ObstacleSegment> = Vec::new();
+
+        for i in 0..self.objects.len() {
+            segments.append(&mut self.objects[i].segments());
+        }
+
+        return segments;
     }
-    
 
     pub fn add_shapes_as_walls(&mut self, shapes: &Vec<Shape>) {
         // redo adding shapes:
         // for static objects it is better to have them as flat array of Vector2D's
         // this will then be simpler for collisions
+        let mut walls: Vec<Vector2D> = self.wall_index.walls().clone();
 
         for j in 0..shapes.len() {
             for i in 0..shapes[j].elements.len() {
-                self.walls.push(shapes[j].elements[i].new_shifted(shapes[j].anchor));
+                walls.push(shapes[j].elements[i].new_shifted(shapes[j].anchor));
             }
+
+            self.shapes.push(shapes[j].clone());
         }
+
+        self.set_walls(walls);
+    }
+
+    pub fn add_shape(&mut self, shape: Shape) -> usize {
+        // index of the new shape
+        self.shapes.push(shape);
+        self.rebuild_walls();
+
+        return self.shapes.len() - 1;
+    }
+
+    pub fn replace_shape(&mut self, index: usize, shape: Shape) {
+        if index < self.shapes.len() {
+            self.shapes[index] = shape;
+            self.rebuild_walls();
+        }
+    }
+
+    pub fn remove_shape(&mut self, index: usize) -> Option<Shape> {
+        if index < self.shapes.len() {
+            let removed: Shape = self.shapes.remove(index);
+            self.rebuild_walls();
+
+            return Some(removed);
+        } else {
+            return None;
+        }
+    }
+
+    fn rebuild_walls(&mut self) {
+        // after shapes were changed: walls and their index are made again
+        let mut walls: Vec<Vector2D> = Vec::new();
+
+        for j in 0..self.shapes.len() {
+            for i in 0..self.shapes[j].elements.len() {
+                walls.push(self.shapes[j].elements[i].new_shifted(self.shapes[j].anchor));
+            }
+        }
+
+        self.set_walls(walls);
+
+        for i in 0..self.agents.len() {
+            self.agents[i].is_updated = true;
+        }
+
+        self.is_updated = true;
+    }
+
+    fn set_walls(&mut self, walls: Vec<Vector2D>) {
+        // agents share the index of the world, the ones already added get the new one
+        self.wall_index = Arc::new(WallGrid::from_walls(walls));
+        self.static_version = next_static_version();
+
+        for i in 0..self.agents.len() {
+            self.agents[i].update_wall_index(Arc::clone(&self.wall_index));
+        }
+    }
+
+    pub fn walls(&self) -> &Vec<Vector2D> {
+        // static walls, in the order of the shapes they come from
+        return self.wall_index.walls();
+    }
+
+    pub fn shape_of_wall(&self, wall_index: usize) -> Option<usize> {
+        // index of the shape a wall came from, walls are stored shape after shape
+        let mut first_wall: usize = 0;
+
+        for j in 0..self.shapes.len() {
+            if wall_index < first_wall + self.shapes[j].elements.len() {
+                return Some(j);
+            }
+
+            first_wall += self.shapes[j].elements.len();
+        }
+
+        return None;
+    }
+
+    pub fn add_shape_descriptions(&mut self, shape_descriptions: &Vec<ShapeDescription>) -> Result<()> {
+        // either all shapes are added, or none if any of them is broken
+        let mut shapes: Vec<Shape> = Vec::new();
+
+        for i in 0..shape_descriptions.len() {
+            shapes.push(Shape::from_descr(&shape_descriptions[i])?);
+        }
+
+        self.add_shapes_as_walls(&shapes);
+
+        return Ok(());
     }
 
     pub fn get_local_walls(&self, location: Coord, range: f32) -> Vec<Vector2D> {
-        // this function is supposed to return only walls that are local to a point,
-        // that is, theoretically visible to agent
-        // get all walls that have bases or tips in the provided range
+        // this function returns only walls that are local to a point,
+        // that is, theoretically visible to agent:
+        // all walls that pass through the square of given half-size around the location
         // so that invisible walls are not checked for intersection with sweeping ray
-        //
-        // 
-        //
-
 
-        let local_walls: Vec<Vector2D> = Vec::new();
+        let indices: Vec<usize> = self.wall_index.query_range(location, range);
+        let mut local_walls: Vec<Vector2D> = Vec::with_capacity(indices.len());
 
+        for i in 0..indices.len() {
+            local_walls.push(*self.wall_index.wall(indices[i]));
+        }
 
         return local_walls;
     }
-    
-}
\ No newline at end of file
+
+    pub fn walls_in_box(&self, area: &AlignedBox) -> Vec<usize> {
+        // indices into walls()
+        return self.wall_index.query_box(area);
+    }
+
+    pub fn cast_ray(&self, ray: &Vector2D) -> Option<(usize, Vector2D)> {
+        // nearest wall hit by the ray: its index and the vector from ray base to the hit point
+        return self.wall_index.cast_ray(ray);
+    }
+}
+
+fn visible_steps(steps: usize, is_visible: impl Fn(usize) -> bool) -> Vec<bool> {
+    // visibility of every step along a wall, checked every OCCLUSION_STEP steps
+    // and bisected between two checks that differ
+    let mut visibility: Vec<bool> = vec![false; steps];
+
+    if steps == 0 {
+        return visibility;
+    }
+
+    let mut start: usize = 0;
+    let mut is_start_visible: bool = is_visible(0);
+
+    visibility[0] = is_start_visible;
+
+    while start + 1 < steps {
+        let end: usize = usize::min(start + OCCLUSION_STEP, steps - 1);
+        let is_end_visible: bool = is_visible(end);
+
+        if is_start_visible == is_end_visible {
+            for t in start..=end {
+                visibility[t] = is_start_visible;
+            }
+        } else {
+            // the last step like the start and the first step like the end
+            let mut low: usize = start;
+            let mut high: usize = end;
+
+            while high - low > 1 {
+                let middle: usize = (low + high) / 2;
+
+                if is_visible(middle) == is_start_visible {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+
+            for t in start..=low {
+                visibility[t] = is_start_visible;
+            }
+
+            for t in high..=end {
+                visibility[t] = is_end_visible;
+            }
+        }
+
+        start = end;
+        is_start_visible = is_end_visible;
+    }
+
+    return visibility;
+}
+
+fn is_filled(render_mode: TopViewRenderMode) -> bool {
+    return render_mode == TopViewRenderMode::FilledWireframe || render_mode == TopViewRenderMode::Solid;
+}
+
+fn fill_color(shape: &Shape, is_static: bool, render_mode: TopViewRenderMode) -> RGBAColor {
+    // half transparent, or solid with darker static shapes
+    let color: RGBAColor = shape.fill_color();
+
+    if render_mode != TopViewRenderMode::Solid {
+        return color.change_transparency(FILL_ALPHA);
+    } else if is_static {
+        return color.new_scaled(SOLID_SHADE);
+    } else {
+        return color;
+    }
+}
+
+fn next_static_version() -> u64 {
+    // unique over all worlds, so that a restored or another world never looks like one drawn before
+    return STATIC_VERSIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    use crate::common_structs::Angle;
+
+    fn square(center: (f32, f32), half_size: f32) -> Shape {
+        let coords: Vec<Coord> = vec![
+            Coord::new(center.0 - half_size, center.1 - half_size),
+            Coord::new(center.0 + half_size, center.1 - half_size),
+            Coord::new(center.0 + half_size, center.1 + half_size),
+            Coord::new(center.0 - half_size, center.1 + half_size),
+        ];
+
+        return Shape::from_coord_list(String::from("square"), coords, LinearTexture::new_plain(RGBAColor::new())).unwrap();
+    }
+
+    fn ends(walls: &[Vector2D]) -> Vec<(f32, f32, f32, f32)> {
+        // walls compared by where they are, in any order
+        let mut ends: Vec<(f32, f32, f32, f32)> = walls.iter().map(|wall| (wall.base.x(), wall.base.y(), wall.tip.x(), wall.tip.y())).collect();
+        ends.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        return ends;
+    }
+
+    #[test]
+    fn local_walls_are_the_walls_through_the_square_around_the_location() {
+        let mut world: World = World::new();
+
+        for j in 0..8 {
+            for i in 0..8 {
+                world.add_shape(square((i as f32 * 3.0, j as f32 * 3.0), 0.5 + (i + j) as f32 * 0.1));
+            }
+        }
+
+        // squares are axis aligned, so their walls pass through whatever their bounding boxes overlap
+        for &(location, range) in [((0.0, 0.0), 1.0), ((10.0, 7.5), 2.0), ((12.0, 12.0), 30.0), ((-5.0, 3.0), 1.0), ((4.4, 4.4), 0.0)].iter() {
+            let location: Coord = Coord::new(location.0, location.1);
+            let area: AlignedBox = AlignedBox::around_point(location, range);
+            let near: Vec<Vector2D> = world.walls().iter().filter(|wall| wall.bounding_box().box_intersection(&area).is_some()).copied().collect();
+
+            assert_eq!(ends(&world.get_local_walls(location, range)), ends(&near), "around {}, {} within {}", location.x(), location.y(), range);
+        }
+    }
+
+    #[test]
+    fn agents_share_the_wall_index_after_shapes_change() {
+        let mut world: World = World::new();
+
+        world.add_agent(Agent::new(Coord::new(0.0, 0.0), Angle::new_deg(0.0), Angle::new_deg(90.0)));
+        world.add_agent(Agent::new(Coord::new(1.0, 0.5), Angle::new_deg(90.0), Angle::new_deg(90.0)));
+
+        // the world and both agents hold the one index
+        assert_eq!(Arc::strong_count(&world.wall_index), 3);
+        assert!(world.agents[0].cast_view_rays(5).iter().all(|ray| ray.is_none()));
+
+        // a box around both agents, every ray of theirs hits it
+        world.add_shape(square((0.0, 0.0), 5.0));
+
+        assert_eq!(Arc::strong_count(&world.wall_index), 3);
+
+        for k in 0..world.agents.len() {
+            assert!(world.agents[k].cast_view_rays(5).iter().all(|ray| ray.is_some()), "agent {} doesn't see the box", k);
+        }
+
+        let added_index: Arc<WallGrid> = Arc::clone(&world.wall_index);
+
+        world.remove_shape(0);
+
+        assert_eq!(Arc::strong_count(&added_index), 1);
+        assert_eq!(Arc::strong_count(&world.wall_index), 3);
+
+        for k in 0..world.agents.len() {
+            assert!(world.agents[k].cast_view_rays(5).iter().all(|ray| ray.is_none()), "agent {} still sees the box", k);
+        }
+    }
+
+    #[test]
+    fn visible_steps_match_checking_every_step() {
+        // hidden stretches of different lengths, the shortest not shorter than OCCLUSION_STEP
+        let hidden: [(usize, usize); 3] = [(3, 7), (10, 30), (41, 45)];
+        let is_visible = |t: usize| -> bool { !hidden.iter().any(|&(from, to)| t >= from && t < to) };
+
+        for steps in [0, 1, 2, 5, 44, 45, 50, 101] {
+            let checks: Cell<usize> = Cell::new(0);
+            let visibility: Vec<bool> = visible_steps(steps, |t| {
+                checks.set(checks.get() + 1);
+                return is_visible(t);
+            });
+
+            assert_eq!(visibility, (0..steps).map(is_visible).collect::<Vec<bool>>(), "{} steps", steps);
+            assert!(checks.get() <= steps, "{} checks for {} steps", checks.get(), steps);
+        }
+
+        let checks: Cell<usize> = Cell::new(0);
+        visible_steps(1000, |t| {
+            checks.set(checks.get() + 1);
+            return is_visible(t);
+        });
+
+        assert!(checks.get() < 1000 / OCCLUSION_STEP + 20, "{} checks", checks.get());
+    }
+}